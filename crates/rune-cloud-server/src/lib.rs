@@ -0,0 +1,300 @@
+//! A stand-in for a cloud storage server, for developing and testing games that use cloud storage.
+//! It serves a directory over the small part of WebDAV the runtime uses. Each player's files are
+//! kept in a collection under the game's url, named by the player id the game gives the runtime.
+
+use std::{
+    error::Error,
+    fs,
+    io::Cursor,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+type Reply = Response<Cursor<Vec<u8>>>;
+
+/// A server listening for requests, which it answers once it's run.
+pub struct CloudServer {
+    server: Server,
+    root: PathBuf,
+    token: Option<String>,
+}
+
+impl CloudServer {
+    /// Listens on `addr`, serving the files under `root`. If there's a token, only requests that
+    /// send it as a bearer token are served.
+    pub fn new(
+        addr: &str,
+        root: PathBuf,
+        token: Option<String>,
+    ) -> Result<CloudServer, Box<dyn Error + Send + Sync>> {
+        fs::create_dir_all(&root)?;
+        let server = Server::http(addr)?;
+        Ok(CloudServer {
+            server,
+            root,
+            token,
+        })
+    }
+
+    /// The address the server is listening on, which has the port the system picked if the
+    /// address asked for port 0.
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Answers requests until the process exits.
+    pub fn run(&self) {
+        // Requests are handled one at a time, so checking a file's etag and replacing the file
+        // can't race with another request.
+        for mut request in self.server.incoming_requests() {
+            let reply = self.handle(&mut request);
+            println!(
+                "{} {} {}",
+                request.method(),
+                request.url(),
+                reply.status_code().0
+            );
+            let _ = request.respond(reply);
+        }
+    }
+
+    fn handle(&self, request: &mut Request) -> Reply {
+        if let Some(token) = &self.token {
+            if header(request, "Authorization") != Some(format!("Bearer {}", token)) {
+                return status(401);
+            }
+        }
+
+        let path = match resolve(&self.root, request.url()) {
+            Some(path) => path,
+            None => return status(400),
+        };
+        match request.method().clone() {
+            Method::Get => get(&path),
+            Method::Put => put(request, &path),
+            Method::Delete if path == self.root => status(403),
+            Method::Delete => delete(request, &path),
+            Method::NonStandard(method) if method.as_str() == "MKCOL" => mkcol(&path),
+            Method::NonStandard(method) if method.as_str() == "PROPFIND" => {
+                propfind(request, &self.root, &path)
+            }
+            _ => status(405),
+        }
+    }
+}
+
+fn get(path: &Path) -> Reply {
+    match fs::read(path) {
+        Ok(data) => {
+            let etag = etag(&data);
+            Response::from_data(data).with_header(etag_header(&etag))
+        }
+        Err(_) => status(404),
+    }
+}
+
+fn put(request: &mut Request, path: &Path) -> Reply {
+    if path.is_dir() {
+        return status(405);
+    }
+    // Like WebDAV, parent directories have to be made first, with MKCOL.
+    if !path.parent().is_some_and(Path::is_dir) {
+        return status(409);
+    }
+    let if_match = header(request, "If-Match");
+    let if_none_match = header(request, "If-None-Match");
+    if !preconditions(path, if_match.as_deref(), if_none_match.as_deref()) {
+        return status(412);
+    }
+
+    let mut data = Vec::new();
+    if request.as_reader().read_to_end(&mut data).is_err() {
+        return status(400);
+    }
+    let existed = path.exists();
+    match fs::write(path, &data) {
+        Ok(()) => status(if existed { 204 } else { 201 }).with_header(etag_header(&etag(&data))),
+        Err(_) => status(500),
+    }
+}
+
+fn delete(request: &Request, path: &Path) -> Reply {
+    if !path.exists() {
+        return status(404);
+    }
+    if !preconditions(path, header(request, "If-Match").as_deref(), None) {
+        return status(412);
+    }
+
+    let removed = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match removed {
+        Ok(()) => status(204),
+        Err(_) => status(500),
+    }
+}
+
+fn mkcol(path: &Path) -> Reply {
+    if path.exists() {
+        return status(405);
+    }
+    if !path.parent().is_some_and(Path::is_dir) {
+        return status(409);
+    }
+    match fs::create_dir(path) {
+        Ok(()) => status(201),
+        Err(_) => status(500),
+    }
+}
+
+fn propfind(request: &Request, root: &Path, path: &Path) -> Reply {
+    if !path.exists() {
+        return status(404);
+    }
+    let depth = match header(request, "Depth").as_deref() {
+        Some("0") => 0,
+        Some("1") => 1,
+        _ => usize::MAX,
+    };
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    describe(root, path, depth, &mut xml);
+    xml.push_str("</D:multistatus>\n");
+
+    Response::from_data(xml)
+        .with_status_code(207)
+        .with_header(Header::from_bytes("Content-Type", "application/xml; charset=utf-8").unwrap())
+}
+
+/// Writes the PROPFIND response for a file, or a directory and what's in it down to `depth`.
+fn describe(root: &Path, path: &Path, depth: usize, xml: &mut String) {
+    let href = href(root, path);
+    if path.is_dir() {
+        xml.push_str(&format!(
+            "  <D:response><D:href>{}/</D:href><D:propstat><D:prop><D:resourcetype><D:collection/></D:resourcetype></D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+            href
+        ));
+        if depth > 0 {
+            for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+                describe(root, &entry.path(), depth - 1, xml);
+            }
+        }
+    } else if let Ok(data) = fs::read(path) {
+        // Etags are hashes of the contents, which means reading every file. That's fine for a
+        // stand-in, but a real server would store them.
+        xml.push_str(&format!(
+            "  <D:response><D:href>{}</D:href><D:propstat><D:prop><D:resourcetype/><D:getcontentlength>{}</D:getcontentlength><D:getetag>{}</D:getetag></D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+            href,
+            data.len(),
+            etag(&data)
+        ));
+    }
+}
+
+/// Whether a request's If-Match and If-None-Match headers allow it to change a file.
+fn preconditions(path: &Path, if_match: Option<&str>, if_none_match: Option<&str>) -> bool {
+    let current = fs::read(path).ok().map(|data| etag(&data));
+    let matches = |condition: &str| match &current {
+        Some(etag) => condition
+            .split(',')
+            .map(str::trim)
+            .any(|condition| condition == "*" || condition == etag),
+        None => false,
+    };
+    let allowed = match if_match {
+        Some(condition) => matches(condition),
+        None => true,
+    };
+    allowed && !if_none_match.is_some_and(matches)
+}
+
+/// A hash of a file's contents, which stays the same across restarts and builds of the server.
+fn etag(data: &[u8]) -> String {
+    // FNV-1a
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("\"{:016x}-{}\"", hash, data.len())
+}
+
+fn etag_header(etag: &str) -> Header {
+    Header::from_bytes("ETag", etag).unwrap()
+}
+
+fn header(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str().to_owned())
+}
+
+fn status(code: u16) -> Reply {
+    Response::from_data(Vec::new()).with_status_code(code)
+}
+
+/// The file or directory a request url names under the root, or None if it tries to leave it.
+fn resolve(root: &Path, url: &str) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next().unwrap_or("");
+    let mut resolved = root.to_path_buf();
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let segment = percent_decode(segment);
+        if segment == "." || segment == ".." || segment.contains(['/', '\\', ':']) {
+            return None;
+        }
+        resolved.push(segment);
+    }
+    Some(resolved)
+}
+
+fn href(root: &Path, path: &Path) -> String {
+    let mut href = String::new();
+    for component in path.strip_prefix(root).unwrap().components() {
+        href.push('/');
+        href.push_str(&percent_encode(&component.as_os_str().to_string_lossy()));
+    }
+    href
+}
+
+fn percent_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = encoded
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,40 @@
+//! Serves a directory as cloud storage for Rune games. Point `[cloud] url` in rune.toml at it,
+//! such as `http://127.0.0.1:8787/my-game/`.
+
+use std::{path::PathBuf, process};
+
+use clap::Parser;
+use rune_cloud_server::CloudServer;
+
+#[derive(Parser)]
+#[command(about = "Serves a directory as cloud storage for Rune games")]
+struct Cli {
+    /// The directory the games' files are kept in
+    #[arg(long, default_value = "cloud-data")]
+    root: PathBuf,
+    /// The address to listen on
+    #[arg(long, default_value = "127.0.0.1:8787")]
+    addr: String,
+    /// Only serves requests that send this bearer token, which is the token the game gives the
+    /// runtime with its player id
+    #[arg(long)]
+    token: Option<String>,
+}
+
+fn main() {
+    let args = Cli::parse();
+    let server = match CloudServer::new(&args.addr, args.root.clone(), args.token) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!(
+                "Failed to serve {} at {}: {}",
+                args.root.display(),
+                args.addr,
+                err
+            );
+            process::exit(1);
+        }
+    };
+    println!("Serving {} at http://{}/", args.root.display(), args.addr);
+    server.run();
+}
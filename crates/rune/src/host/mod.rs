@@ -1,9 +1,9 @@
 #[cfg(target_arch = "wasm32")]
 mod web;
-#[cfg(any(target_os = "macos", target_os = "windows"))]
+#[cfg(not(target_arch = "wasm32"))]
 mod native;
 
 #[cfg(target_arch = "wasm32")]
 pub use web::game::Game;
-#[cfg(any(target_os = "macos", target_os = "windows"))]
+#[cfg(not(target_arch = "wasm32"))]
 pub use native::game::Game;
@@ -1,4 +1,7 @@
-use std::{path::PathBuf, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::{Ok, Result};
 use gilrs::Gilrs;
@@ -7,9 +10,12 @@ use wasmtime::{
     component::{Component, Linker},
     Config, Engine, Store,
 };
-use winit::{dpi::PhysicalSize, window::Window};
+use winit::dpi::PhysicalSize;
 
-use crate::{Runtime, RuntimePre};
+use crate::{
+    runtime::{debug, storage::CloudConfig, AudioDevices, Gpu, PipelineCache},
+    Runtime, RuntimePre,
+};
 
 pub use crate::runtime::RuneRuntimeState;
 
@@ -21,6 +27,8 @@ pub struct Game {
     pub instance_pre: RuntimePre<RuneRuntimeState>,
     pub runtime: Option<Runtime>,
     pub store: Option<Store<RuneRuntimeState>>,
+    /// When set, the next rendered frame is written to this path as a PNG before it is presented.
+    pub screenshot_path: Option<PathBuf>,
 }
 
 impl std::fmt::Debug for Game {
@@ -50,37 +58,47 @@ impl Game {
             instance_pre: RuntimePre::new(linker.instantiate_pre(&component)?)?,
             runtime: None,
             store: None,
+            screenshot_path: None,
         })
     }
 
     pub async fn init(
         &mut self,
-        window: &Window,
+        window_size: PhysicalSize<u32>,
         input_path: PathBuf,
-        audio_device: cpal::Device,
-        instance: wgpu_core::global::Global,
+        audio_devices: AudioDevices,
+        gpu: Gpu,
         surface: wgpu_core::id::SurfaceId,
         adapter: wgpu_core::id::AdapterId,
         device: wgpu_core::id::DeviceId,
         queue: wgpu_core::id::QueueId,
-        gilrs: Gilrs,
+        gilrs: Option<Gilrs>,
+        pipeline_cache: Option<PipelineCache>,
+        record_audio: Option<PathBuf>,
+        microphone: bool,
+        data_dir: Option<PathBuf>,
+        cloud: Option<CloudConfig>,
     ) -> Result<(), anyhow::Error> {
-        let window_size = window.inner_size();
-
         let runtime_state = RuneRuntimeState::new(
             Uuid::new_v4(),
             input_path,
             window_size,
-            audio_device,
-            instance,
+            audio_devices,
+            gpu,
             surface,
             adapter,
             device,
             queue,
             gilrs,
+            pipeline_cache,
         );
 
         let mut store = Store::new(&self.engine, runtime_state);
+        // Set before init, since that's where most games create their audio context.
+        store.data_mut().record_audio = record_audio;
+        store.data_mut().microphone = microphone;
+        store.data_mut().data_dir = data_dir;
+        store.data_mut().cloud_config = cloud;
 
         let runtime = self.instance_pre.instantiate_async(&mut store).await?;
 
@@ -100,6 +118,7 @@ impl Game {
         delta_time: Duration,
     ) -> Result<(), anyhow::Error> {
         let store = self.store.as_mut().unwrap();
+        store.data_mut().update_audio_devices();
         self.runtime
             .as_ref()
             .unwrap()
@@ -128,17 +147,49 @@ impl Game {
 
         let store = self.store.as_mut().expect("Store must be initialized");
         let ctx = store.data_mut();
-        let surface_id = ctx.surface;
+
+        if let Some(path) = self.screenshot_path.take() {
+            // A screenshot that can't be saved is reported, rather than stopping the game.
+            match ctx.capture_surface_texture() {
+                Some(capture) => {
+                    if let Err(err) =
+                        save_screenshot(&path, capture.width, capture.height, capture.data)
+                    {
+                        debug::warn(&format!(
+                            "Couldn't save the screenshot {}: {}",
+                            path.display(),
+                            err
+                        ));
+                    } else {
+                        debug::log(&format!("Saved screenshot to {}", path.display()));
+                    }
+                }
+                None => debug::warn(&format!(
+                    "No frame was rendered to capture for {}",
+                    path.display()
+                )),
+            }
+        }
+
+        ctx.end_gpu_profiler_frame();
 
         if ctx.gpu_state.present_surface {
-            ctx.instance.surface_present(surface_id)?;
+            ctx.present_surface();
             ctx.gpu_state.present_surface = false;
+            ctx.gpu_state.surface_texture = None;
         }
+        ctx.gpu_state.frame += 1;
 
         Ok(())
     }
 
+    /// Resizes the surface, keeping the rest of the guest's surface configuration.
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        // A minimized window reports a zero size, which isn't a valid surface configuration.
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
         let store = self.store.as_mut().unwrap();
         let ctx = store.data_mut();
         let surface_id = ctx.surface;
@@ -149,7 +200,21 @@ impl Game {
         surface_config.width = size.width;
         surface_config.height = size.height;
 
-        ctx.instance
-            .surface_configure(surface_id, device_id, &surface_config);
+        // The null backend has no surface to configure.
+        if let Some(global) = ctx.gpu.wgpu() {
+            global.surface_configure(surface_id, device_id, &surface_config);
+        }
+    }
+}
+
+/// Writes a captured frame to a PNG file, creating its directory if needed.
+fn save_screenshot(path: &Path, width: u32, height: u32, data: Vec<u8>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+    let image = image::RgbaImage::from_raw(width, height, data).ok_or_else(|| {
+        anyhow::anyhow!("the captured frame doesn't have {}x{} pixels", width, height)
+    })?;
+    image.save(path)?;
+    Ok(())
 }
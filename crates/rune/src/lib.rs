@@ -31,8 +31,11 @@ wasmtime::component::bindgen!({
     path: "wit/runtime",
     async: true,
     with: {
+        "rune:runtime/audio/audio-device": crate::runtime::audio::AudioDevice,
         "rune:runtime/audio/audio-buffer": web_audio_api::AudioBuffer,
+        "rune:runtime/audio/pending-audio-buffer": crate::runtime::PendingAudioBuffer,
         "rune:runtime/audio/audio-context": web_audio_api::context::AudioContext,
+        "rune:runtime/audio/offline-audio-context": web_audio_api::context::OfflineAudioContext,
         "rune:runtime/audio/audio-param": web_audio_api::AudioParam,
         "rune:runtime/audio/analyzer-node": web_audio_api::node::AnalyserNode,
         "rune:runtime/audio/audio-buffer-source-node": web_audio_api::node::AudioBufferSourceNode,
@@ -48,18 +51,22 @@ wasmtime::component::bindgen!({
         "rune:runtime/audio/iir-filter-node": web_audio_api::node::IIRFilterNode,
         "rune:runtime/audio/oscillator-node": web_audio_api::node::OscillatorNode,
         "rune:runtime/audio/panner-node": web_audio_api::node::PannerNode,
+        "rune:runtime/audio/periodic-wave": web_audio_api::PeriodicWave,
         "rune:runtime/audio/audio-render-capacity": web_audio_api::AudioRenderCapacity,
         "rune:runtime/audio/stereo-panner-node": web_audio_api::node::StereoPannerNode,
         "rune:runtime/audio/wave-shaper-node": web_audio_api::node::WaveShaperNode,
         "rune:runtime/audio/audio-listener": web_audio_api::AudioListener,
+        "rune:runtime/audio/audio-worklet-node": crate::runtime::AudioWorklet,
+        "rune:runtime/audio/media-stream-audio-source-node": crate::runtime::AudioInput,
+        "rune:runtime/audio/audio-stream-source-node": crate::runtime::AudioStream,
 
         "rune:runtime/gpu/gpu-adapter": wgpu_core::id::AdapterId,
         "rune:runtime/gpu/gpu-device": wgpu_core::id::DeviceId,
         "rune:runtime/gpu/gpu-queue": wgpu_core::id::QueueId,
         "rune:runtime/gpu/gpu-buffer": wgpu_core::id::BufferId,
         "rune:runtime/gpu/gpu-command-encoder": wgpu_core::id::CommandEncoderId,
-        "rune:runtime/gpu/gpu-compute-pass-encoder": wgpu_core::command::ComputePass,
-        "rune:runtime/gpu/gpu-render-pass-encoder": wgpu_core::command::RenderPass,
+        "rune:runtime/gpu/gpu-compute-pass-encoder": crate::runtime::gpu::ComputePassEncoder,
+        "rune:runtime/gpu/gpu-render-pass-encoder": crate::runtime::gpu::RenderPassEncoder,
         "rune:runtime/gpu/gpu-render-bundle": wgpu_core::id::RenderBundleId,
         "rune:runtime/gpu/gpu-render-bundle-encoder": wgpu_core::command::RenderBundleEncoder,
         "rune:runtime/gpu/gpu-shader-module": wgpu_core::id::ShaderModuleId,
@@ -123,6 +130,22 @@ impl KeyboardState {
     }
 }
 
+pub struct PointerState {
+    /// The cursor's position in physical window pixels, while it's over the window.
+    pub cursor_position: Option<(f64, f64)>,
+    /// Active touches as id and position in physical window pixels.
+    pub touches: Vec<(u64, f64, f64)>,
+}
+
+impl PointerState {
+    pub fn new() -> PointerState {
+        Self {
+            cursor_position: None,
+            touches: Vec::new(),
+        }
+    }
+}
+
 pub struct GamepadState {
     pub active_buttons: Vec<(u64, Button)>,
 }
@@ -1,11 +1,10 @@
-use cpal::Device;
-
-pub struct AudioState {
-    pub device: Device,
-}
-
-impl AudioState {
-    pub fn new(device: Device) -> AudioState {
-        AudioState { device }
-    }
+/// An audio output or input device, as it was when the game asked for it.
+#[derive(Clone)]
+pub struct AudioDevice {
+    /// The sink id contexts use to play to an output, or the device id an input is opened with.
+    pub id: String,
+    pub name: String,
+    pub default_sample_rate: Option<u32>,
+    pub is_default: bool,
+    pub is_input: bool,
 }
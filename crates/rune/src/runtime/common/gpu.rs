@@ -1,9 +1,20 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
 
-use wgpu_core::id::{BufferId, QuerySetId, TextureId};
-use wgpu_types::{BufferUsages, QueryType, TextureDimension, TextureFormat, TextureUsages};
+use wgpu_core::id::{
+    BindGroupLayoutId, BufferId, CommandEncoderId, QuerySetId, RenderPipelineId,
+    SamplerId, ShaderModuleId, TextureId,
+};
+use wgpu_types::{
+    Backends, BufferUsages, Extent3d, FilterMode, QueryType, TextureDimension, TextureFormat,
+    TextureUsages,
+};
 
-use crate::gpu::GpuBufferMapState;
+use crate::debug::{GpuResource, GpuResourceKind};
+use crate::gpu::{GpuBufferMapState, GpuDeviceLostInfo, GpuError, GpuErrorFilter, GpuScalingMode};
 
 pub struct Buffer {
     pub usage: BufferUsages,
@@ -19,6 +30,7 @@ pub struct QuerySet {
 pub struct Texture {
     pub height: u32,
     pub width: u32,
+    pub depth_or_array_layers: u32,
     pub mip_level_count: u32,
     pub sample_count: u32,
     pub dimension: TextureDimension,
@@ -26,11 +38,329 @@ pub struct Texture {
     pub usage: TextureUsages,
 }
 
+impl Texture {
+    /// Estimates the memory the texture occupies, summed over its mip levels and samples.
+    pub fn estimated_size(&self) -> u64 {
+        let size = Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: self.depth_or_array_layers,
+        };
+        let (block_width, block_height) = self.format.block_dimensions();
+        let block_size = self.format.block_copy_size(None).unwrap_or(4) as u64;
+
+        let mut total = 0;
+        for mip in 0..self.mip_level_count {
+            let mip_size = size.mip_level_size(mip, self.dimension);
+            let blocks_wide = mip_size.width.div_ceil(block_width) as u64;
+            let blocks_high = mip_size.height.div_ceil(block_height) as u64;
+            let layers = mip_size.depth_or_array_layers as u64;
+            total += blocks_wide * blocks_high * layers * block_size;
+        }
+        total * self.sample_count as u64
+    }
+}
+
+/// A GPU object the guest created and hasn't dropped yet, kept for the live-resource report.
+pub struct TrackedResource {
+    pub kind: GpuResourceKind,
+    pub label: Option<String>,
+    /// Estimated memory in bytes, zero for objects without a meaningful allocation.
+    pub size: u64,
+    pub created_frame: u64,
+}
+
+/// Objects generate-mipmaps draws with, created the first time each is needed.
+#[derive(Default)]
+pub struct MipmapPipelines {
+    pub color_shader: Option<ShaderModuleId>,
+    pub depth_shader: Option<ShaderModuleId>,
+    pub sampler: Option<SamplerId>,
+    /// Keyed by the texture format and the fragment entry point.
+    pub pipelines: HashMap<(TextureFormat, &'static str), (RenderPipelineId, BindGroupLayoutId)>,
+}
+
+/// A fixed-size target the guest renders into, which is scaled to the surface when presented.
+pub struct RenderScaling {
+    pub width: u32,
+    pub height: u32,
+    pub filter: FilterMode,
+    pub mode: GpuScalingMode,
+    /// The texture current-texture hands out, created on first use.
+    pub target: Option<TextureId>,
+    pub sampler: Option<SamplerId>,
+}
+
+impl RenderScaling {
+    /// The rectangle the target is drawn to on a surface of the given size, as x, y, width and
+    /// height. Unless stretched, the rest of the surface is letterboxed.
+    pub fn viewport(&self, surface_width: u32, surface_height: u32) -> (f32, f32, f32, f32) {
+        if self.mode == GpuScalingMode::Stretch {
+            return (0.0, 0.0, surface_width as f32, surface_height as f32);
+        }
+        let fit = (surface_width as f32 / self.width as f32).min(surface_height as f32 / self.height as f32);
+        // A surface smaller than the target can't take a whole-number scale, so it's fitted.
+        let scale = match self.mode == GpuScalingMode::Integer && fit >= 1.0 {
+            true => fit.floor(),
+            false => fit,
+        };
+        let width = self.width as f32 * scale;
+        let height = self.height as f32 * scale;
+        (
+            ((surface_width as f32 - width) / 2.0).floor(),
+            ((surface_height as f32 - height) / 2.0).floor(),
+            width,
+            height,
+        )
+    }
+
+    /// Maps a position on a surface of the given size to a position in the target. Positions
+    /// on a surface with no area, as when the window is minimized, map to the origin.
+    pub fn to_target(&self, x: f32, y: f32, surface_width: u32, surface_height: u32) -> (f32, f32) {
+        let (left, top, width, height) = self.viewport(surface_width, surface_height);
+        if width <= 0.0 || height <= 0.0 {
+            return (0.0, 0.0);
+        }
+        (
+            (x - left) * self.width as f32 / width,
+            (y - top) * self.height as f32 / height,
+        )
+    }
+}
+
+/// Which graphics API the runtime uses, or the null backend, which needs no GPU at all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuBackend {
+    Auto,
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+    Null,
+}
+
+impl GpuBackend {
+    /// Reads the backend from the `RUNE_GPU_BACKEND` environment variable.
+    pub fn from_env() -> Option<GpuBackend> {
+        let value = std::env::var("RUNE_GPU_BACKEND").ok()?;
+        match value.parse() {
+            Ok(backend) => Some(backend),
+            Err(err) => {
+                crate::runtime::debug::warn(&format!("Ignoring RUNE_GPU_BACKEND: {}", err));
+                None
+            }
+        }
+    }
+
+    /// Parses a backend by name, rejecting any the runtime isn't built with on `os`, which is
+    /// named like `std::env::consts::OS`.
+    pub fn parse_for(s: &str, os: &str) -> Result<GpuBackend, String> {
+        let backend = match s.to_lowercase().as_str() {
+            "auto" => GpuBackend::Auto,
+            "vulkan" => GpuBackend::Vulkan,
+            "metal" => GpuBackend::Metal,
+            "dx12" => GpuBackend::Dx12,
+            "gl" => GpuBackend::Gl,
+            "null" => GpuBackend::Null,
+            _ => {
+                return Err(format!(
+                    "unknown GPU backend '{}', expected one of: auto, vulkan, metal, dx12, gl, null",
+                    s
+                ))
+            }
+        };
+        match backend.is_built_for(os) {
+            true => Ok(backend),
+            false => Err(format!(
+                "the {} GPU backend isn't available on {}, expected one of: {}",
+                s,
+                os,
+                GpuBackend::built_for(os).join(", ")
+            )),
+        }
+    }
+
+    /// Whether the runtime is built with this backend on `os`. This follows the wgpu-core
+    /// features each platform enables in Cargo.toml.
+    pub fn is_built_for(&self, os: &str) -> bool {
+        match self {
+            GpuBackend::Auto | GpuBackend::Null => true,
+            GpuBackend::Vulkan => matches!(os, "linux" | "android" | "windows"),
+            GpuBackend::Metal => matches!(os, "macos" | "ios"),
+            GpuBackend::Dx12 => os == "windows",
+            GpuBackend::Gl => !matches!(os, "macos" | "ios"),
+        }
+    }
+
+    fn built_for(os: &str) -> Vec<&'static str> {
+        [
+            ("auto", GpuBackend::Auto),
+            ("vulkan", GpuBackend::Vulkan),
+            ("metal", GpuBackend::Metal),
+            ("dx12", GpuBackend::Dx12),
+            ("gl", GpuBackend::Gl),
+            ("null", GpuBackend::Null),
+        ]
+        .into_iter()
+        .filter(|(_, backend)| backend.is_built_for(os))
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    pub fn backends(&self) -> Backends {
+        match self {
+            GpuBackend::Auto => Backends::all(),
+            GpuBackend::Vulkan => Backends::VULKAN,
+            GpuBackend::Metal => Backends::METAL,
+            GpuBackend::Dx12 => Backends::DX12,
+            GpuBackend::Gl => Backends::GL,
+            GpuBackend::Null => Backends::empty(),
+        }
+    }
+}
+
+impl FromStr for GpuBackend {
+    type Err = String;
+
+    /// Parses a backend the runtime is built with on this platform.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GpuBackend::parse_for(s, std::env::consts::OS)
+    }
+}
+
+/// Tracks the state of a render or compute pass on the null backend.
+pub struct NullPass {
+    pub encoder: CommandEncoderId,
+    pub has_pipeline: bool,
+    pub ended: bool,
+}
+
+/// A render or compute pass, which the null backend tracks itself instead of recording with wgpu.
+pub enum PassEncoder<P> {
+    Wgpu(P),
+    Null(NullPass),
+}
+
+pub type RenderPassEncoder = PassEncoder<wgpu_core::command::RenderPass>;
+pub type ComputePassEncoder = PassEncoder<wgpu_core::command::ComputePass>;
+
+pub struct ErrorScope {
+    pub filter: GpuErrorFilter,
+    pub error: Option<GpuError>,
+}
+
+/// How many frames of timestamps can be waiting for readback before the profiler skips a frame.
+pub const PROFILER_FRAMES: usize = 3;
+/// The most passes the profiler times in a single frame.
+pub const PROFILER_MAX_PASSES: u32 = 64;
+
+/// Times render and compute passes with timestamp queries the host injects into each pass.
+pub struct GpuProfiler {
+    pub enabled: bool,
+    pub frames: Vec<ProfilerFrame>,
+    /// The frame that passes are currently being recorded into.
+    pub current: usize,
+    /// Maps pass resource reps to their index in the current frame.
+    pub active_passes: HashMap<u32, usize>,
+    /// Nanoseconds per timestamp tick.
+    pub timestamp_period: f32,
+    pub last_timings: Vec<(String, f64)>,
+    pub stats: Vec<(String, PassStats)>,
+}
+
+/// The queries and buffers for one frame of timestamps.
+pub struct ProfilerFrame {
+    pub query_set: QuerySetId,
+    pub resolve_buffer: BufferId,
+    pub readback_buffer: BufferId,
+    pub passes: Vec<ProfiledPass>,
+    /// Set by the map callback once the readback buffer can be read.
+    pub mapped: Arc<AtomicBool>,
+    pub pending: bool,
+}
+
+pub struct ProfiledPass {
+    pub name: String,
+    /// Whether the name came from the pass's label or a debug group, rather than being the default.
+    pub named: bool,
+}
+
+#[derive(Default)]
+pub struct PassStats {
+    pub frames: u64,
+    pub total_ms: f64,
+    pub max_ms: f64,
+}
+
+impl GpuProfiler {
+    /// Adds a frame of timings, keeping them for the guest and adding them to the run's totals.
+    pub fn record_timings(&mut self, timings: Vec<(String, f64)>) {
+        for (name, ms) in &timings {
+            let index = match self.stats.iter().position(|(n, _)| n == name) {
+                Some(index) => index,
+                None => {
+                    self.stats.push((name.clone(), PassStats::default()));
+                    self.stats.len() - 1
+                }
+            };
+            let stats = &mut self.stats[index].1;
+            stats.frames += 1;
+            stats.total_ms += ms;
+            stats.max_ms = stats.max_ms.max(*ms);
+        }
+        self.last_timings = timings;
+    }
+
+    /// Formats the run's totals as a table, one row per pass name.
+    pub fn report(&self) -> String {
+        let width = self
+            .stats
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("Pass".len());
+
+        let mut report = format!(
+            "{:<width$}  {:>8}  {:>10}  {:>10}\n",
+            "Pass", "Frames", "Avg (ms)", "Max (ms)"
+        );
+        for (name, stats) in &self.stats {
+            report.push_str(&format!(
+                "{:<width$}  {:>8}  {:>10.3}  {:>10.3}\n",
+                name,
+                stats.frames,
+                stats.total_ms / stats.frames as f64,
+                stats.max_ms
+            ));
+        }
+        report
+    }
+}
+
 pub struct GpuState {
     pub buffers: HashMap<BufferId, Buffer>,
     pub query_sets: HashMap<QuerySetId, QuerySet>,
     pub textures: HashMap<TextureId, Texture>,
-    pub present_surface: bool
+    pub error_scopes: Vec<ErrorScope>,
+    pub surface_texture: Option<TextureId>,
+    pub present_surface: bool,
+    pub profiler: Option<GpuProfiler>,
+    /// Live guest resources, keyed by their resource table index.
+    pub resources: HashMap<u32, TrackedResource>,
+    /// Labels of the command encoders, passes and command buffers the guest is recording,
+    /// which aren't live resources, keyed by their resource table index.
+    pub labels: HashMap<u32, String>,
+    /// Frames presented so far, used to date resource creation.
+    pub frame: u64,
+    pub mipmaps: MipmapPipelines,
+    pub render_scaling: Option<RenderScaling>,
+    /// Set by wgpu from whichever thread notices the device is gone.
+    pub device_lost: Arc<Mutex<Option<GpuDeviceLostInfo>>>,
+    /// The surface reported it no longer matches the window, so it's configured again before the next frame.
+    pub reconfigure_surface: bool,
+    /// Errors no error scope captured, which fail headless runs.
+    pub uncaptured_errors: u64,
 }
 
 impl GpuState {
@@ -39,7 +369,156 @@ impl GpuState {
             buffers: HashMap::new(),
             query_sets: HashMap::new(),
             textures: HashMap::new(),
-            present_surface: false
+            error_scopes: Vec::new(),
+            surface_texture: None,
+            present_surface: false,
+            profiler: None,
+            resources: HashMap::new(),
+            labels: HashMap::new(),
+            frame: 0,
+            mipmaps: MipmapPipelines::default(),
+            render_scaling: None,
+            device_lost: Arc::new(Mutex::new(None)),
+            reconfigure_surface: false,
+            uncaptured_errors: 0,
+        }
+    }
+
+    /// Lists live resources, oldest first.
+    pub fn live_resources(&self) -> Vec<GpuResource> {
+        let mut resources: Vec<_> = self
+            .resources
+            .iter()
+            .map(|(rep, resource)| {
+                (
+                    *rep,
+                    GpuResource {
+                        kind: resource.kind,
+                        label: resource.label.clone(),
+                        size: resource.size,
+                        created_frame: resource.created_frame,
+                    },
+                )
+            })
+            .collect();
+        resources.sort_by_key(|(rep, resource)| (resource.created_frame, *rep));
+        resources.into_iter().map(|(_, resource)| resource).collect()
+    }
+
+    /// Formats live resources as a per-kind summary of counts and estimated memory, followed by
+    /// each resource.
+    pub fn resource_report(&self) -> String {
+        let resources = self.live_resources();
+
+        let mut kinds: Vec<(GpuResourceKind, usize, u64)> = Vec::new();
+        for resource in &resources {
+            match kinds.iter_mut().find(|(kind, _, _)| *kind == resource.kind) {
+                Some((_, count, size)) => {
+                    *count += 1;
+                    *size += resource.size;
+                }
+                None => kinds.push((resource.kind, 1, resource.size)),
+            }
         }
+
+        let mut report = format!("{:<20}  {:>8}  {:>12}\n", "Kind", "Count", "Memory (KiB)");
+        for (kind, count, size) in &kinds {
+            report.push_str(&format!(
+                "{:<20}  {:>8}  {:>12.1}\n",
+                format!("{:?}", kind),
+                count,
+                *size as f64 / 1024.0
+            ));
+        }
+        report.push('\n');
+        for resource in &resources {
+            report.push_str(&format!(
+                "{:?} {:?}, {} bytes, created on frame {}\n",
+                resource.kind,
+                resource.label.as_deref().unwrap_or("<unlabelled>"),
+                resource.size,
+                resource.created_frame
+            ));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scaling(width: u32, height: u32, mode: GpuScalingMode) -> RenderScaling {
+        RenderScaling {
+            width,
+            height,
+            filter: FilterMode::Nearest,
+            mode,
+            target: None,
+            sampler: None,
+        }
+    }
+
+    #[test]
+    fn fit_letterboxes_the_rest_of_the_surface() {
+        let scaling = scaling(320, 180, GpuScalingMode::Fit);
+        assert_eq!(scaling.viewport(1280, 800), (0.0, 40.0, 1280.0, 720.0));
+        assert_eq!(scaling.viewport(1000, 720), (0.0, 78.0, 1000.0, 562.5));
+        assert_eq!(scaling.to_target(640.0, 400.0, 1280, 800), (160.0, 90.0));
+        assert_eq!(scaling.to_target(0.0, 0.0, 1280, 800), (0.0, -10.0));
+    }
+
+    #[test]
+    fn integer_scales_by_whole_numbers() {
+        let scaling = scaling(320, 180, GpuScalingMode::Integer);
+        assert_eq!(scaling.viewport(1000, 720), (20.0, 90.0, 960.0, 540.0));
+        assert_eq!(scaling.to_target(20.0, 90.0, 1000, 720), (0.0, 0.0));
+        assert_eq!(scaling.to_target(980.0, 630.0, 1000, 720), (320.0, 180.0));
+    }
+
+    #[test]
+    fn integer_fits_surfaces_smaller_than_the_target() {
+        let scaling = scaling(320, 180, GpuScalingMode::Integer);
+        assert_eq!(scaling.viewport(160, 90), (0.0, 0.0, 160.0, 90.0));
+    }
+
+    #[test]
+    fn odd_sizes_centre_on_whole_pixels() {
+        let scaling = scaling(321, 181, GpuScalingMode::Integer);
+        assert_eq!(scaling.viewport(1000, 1000), (18.0, 228.0, 963.0, 543.0));
+        let scaling = self::scaling(320, 180, GpuScalingMode::Integer);
+        assert_eq!(scaling.viewport(1281, 721), (0.0, 0.0, 1280.0, 720.0));
+    }
+
+    #[test]
+    fn stretch_fills_the_surface() {
+        let scaling = scaling(320, 180, GpuScalingMode::Stretch);
+        assert_eq!(scaling.viewport(1000, 500), (0.0, 0.0, 1000.0, 500.0));
+        assert_eq!(scaling.to_target(500.0, 250.0, 1000, 500), (160.0, 90.0));
+        assert_eq!(scaling.to_target(1000.0, 0.0, 1000, 500), (320.0, 0.0));
+    }
+
+    #[test]
+    fn zero_sized_surfaces_map_to_the_origin() {
+        for mode in [GpuScalingMode::Fit, GpuScalingMode::Integer, GpuScalingMode::Stretch] {
+            let scaling = scaling(320, 180, mode);
+            let (_, _, width, height) = scaling.viewport(0, 0);
+            assert_eq!((width, height), (0.0, 0.0));
+            assert_eq!(scaling.to_target(10.0, 10.0, 0, 0), (0.0, 0.0));
+            assert_eq!(scaling.to_target(10.0, 10.0, 0, 720), (0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn backends_are_parsed_for_the_platform_they_run_on() {
+        assert_eq!(GpuBackend::parse_for("Vulkan", "windows"), Ok(GpuBackend::Vulkan));
+        assert_eq!(GpuBackend::parse_for("gl", "linux"), Ok(GpuBackend::Gl));
+        assert_eq!(GpuBackend::parse_for("null", "macos"), Ok(GpuBackend::Null));
+        assert_eq!(
+            GpuBackend::parse_for("gl", "macos"),
+            Err("the gl GPU backend isn't available on macos, expected one of: auto, metal, null".to_owned())
+        );
+        assert!(GpuBackend::parse_for("dx12", "linux").is_err());
+        assert!(GpuBackend::parse_for("opengl", "linux").is_err());
     }
 }
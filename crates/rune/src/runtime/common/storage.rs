@@ -1,6 +1,66 @@
+use std::str::FromStr;
+
 use vfs::{AltrootFS, VfsPath};
 
 pub enum Storage {
     Local(VfsPath, AltrootFS),
-    Cloud,
+    /// The game's build output, which the game can't change.
+    Assets(VfsPath, AltrootFS),
+    /// The game's directory in the platform data directory, which outlives updates to the game.
+    SaveData(VfsPath, AltrootFS),
+    /// A local mirror of a player's cloud storage, which is synced with the server in the
+    /// background. It's named by the player's id.
+    Cloud(String, VfsPath, AltrootFS),
+}
+
+impl Storage {
+    /// The root and filesystem of a storage device. Cloud storage is read and written through its
+    /// local mirror.
+    pub fn vfs(&self) -> (&VfsPath, &AltrootFS) {
+        match self {
+            Storage::Local(root, vfs)
+            | Storage::Assets(root, vfs)
+            | Storage::SaveData(root, vfs)
+            | Storage::Cloud(_, root, vfs) => (root, vfs),
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, Storage::Assets(..))
+    }
+}
+
+/// The server the cloud storage device syncs with, from the `[cloud]` table of rune.toml.
+#[derive(Clone, Debug)]
+pub struct CloudConfig {
+    /// The WebDAV collection the game's files are kept in, such as `https://saves.example.com/my-game/`.
+    /// Each player's files are kept in a collection under it, named by their player id.
+    pub url: String,
+    pub conflicts: CloudConflicts,
+}
+
+/// What happens when a file was changed both on this machine and on the server since they last synced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CloudConflicts {
+    /// The last machine to sync overwrites the server's file.
+    #[default]
+    LastWriterWins,
+    /// The server's file is kept, and this machine's file is moved aside to `<path>.conflict` for the
+    /// game to resolve.
+    VersionCheck,
+}
+
+impl FromStr for CloudConflicts {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "last-writer-wins" => Ok(CloudConflicts::LastWriterWins),
+            "version-check" => Ok(CloudConflicts::VersionCheck),
+            _ => Err(format!(
+                "unknown conflict strategy '{}', expected one of: last-writer-wins, version-check",
+                s
+            )),
+        }
+    }
 }
@@ -8,15 +8,26 @@ use pollster;
 mod common;
 #[cfg(target_arch = "wasm32")]
 mod web;
-#[cfg(any(target_os = "macos", target_os = "windows"))]
+#[cfg(not(target_arch = "wasm32"))]
 mod native;
 
 pub use common::*;
 #[cfg(target_arch = "wasm32")]
 pub use web::*;
-#[cfg(any(target_os = "macos", target_os = "windows"))]
+#[cfg(not(target_arch = "wasm32"))]
 pub use native::{
+    audio_decode::PendingAudioBuffer,
+    audio_devices::AudioDevices,
+    audio_input::AudioInput,
+    audio_stream::AudioStream,
+    audio_worklet::AudioWorklet,
+    debug,
+    gpu_backend::{Gpu, NullGpu, SHADER_CAPABILITIES},
+    pipeline_cache::PipelineCache,
+    replay::replay_gpu_trace,
     run::run,
+    run::run_with_options,
     run::test,
+    run::RunOptions,
     state::RuneRuntimeState
 };
@@ -1,6 +1,9 @@
+use std::collections::HashMap;
 use std::io::Cursor;
+use std::path::{Component, Path};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use cpal::traits::DeviceTrait;
 use wasmtime::component::Resource;
 use wasmtime::Result;
 use wasmtime_wasi::ResourceTable;
@@ -8,34 +11,136 @@ use web_audio_api::context::{AudioContext, AudioContextOptions, BaseAudioContext
 use web_audio_api::node::{AudioNode, AudioScheduledSourceNode, IIRFilterNode};
 
 use crate::rune::runtime::audio::*;
+use super::audio_output::AudioOutput;
+use super::audio_spatial::panner_output;
 use super::state::RuneRuntimeState;
 
 impl Host for RuneRuntimeState {
     async fn output(&mut self) -> Option<Resource<AudioDevice>> {
-        Some(Resource::new_own(0))
+        Some(self.table.push(self.audio_devices.default_output()).unwrap())
+    }
+
+    async fn outputs(&mut self) -> Vec<Resource<AudioDevice>> {
+        self.audio_devices
+            .outputs()
+            .into_iter()
+            .map(|device| self.table.push(device).unwrap())
+            .collect()
+    }
+
+    async fn inputs(&mut self) -> core::result::Result<Vec<Resource<AudioDevice>>, String> {
+        self.check_microphone_permission()?;
+        Ok(self
+            .audio_devices
+            .inputs()
+            .into_iter()
+            .map(|device| self.table.push(device).unwrap())
+            .collect())
+    }
+
+    async fn device_events(&mut self) -> Vec<AudioDeviceEvent> {
+        std::mem::take(&mut self.audio_devices.pending)
+    }
+
+    async fn create_offline_context(
+        &mut self,
+        number_of_channels: u32,
+        length: u32,
+        sample_rate: f32,
+    ) -> core::result::Result<Resource<OfflineAudioContext>, String> {
+        // web_audio_api panics on a context it can't render, so it's checked here first.
+        if !(1..=32).contains(&number_of_channels) {
+            return Err(format!(
+                "{} isn't between 1 and 32 channels",
+                number_of_channels
+            ));
+        }
+        if length == 0 {
+            return Err("An offline audio context needs a length of at least 1 frame".to_owned());
+        }
+        if !(3000. ..=768000.).contains(&sample_rate) {
+            return Err(format!(
+                "{} isn't a sample rate between 3000 and 768000",
+                sample_rate
+            ));
+        }
+        let audio_context = web_audio_api::context::OfflineAudioContext::new(
+            number_of_channels as usize,
+            length as usize,
+            sample_rate,
+        );
+        Ok(self.table.push(audio_context).unwrap())
     }
 }
 
 impl HostAudioDevice for RuneRuntimeState {
-    async fn name(&mut self, _audio_device: Resource<AudioDevice>) -> String {
-        match self.audio_state.device.name() {
-            Ok(name) => name,
-            Err(_) => "Unknown".to_owned(),
-        }
+    async fn id(&mut self, audio_device: Resource<AudioDevice>) -> String {
+        let audio_device = self.table.get(&audio_device).unwrap();
+        audio_device.id.clone()
+    }
+
+    async fn name(&mut self, audio_device: Resource<AudioDevice>) -> String {
+        let audio_device = self.table.get(&audio_device).unwrap();
+        audio_device.name.clone()
+    }
+
+    async fn default_sample_rate(&mut self, audio_device: Resource<AudioDevice>) -> Option<u32> {
+        let audio_device = self.table.get(&audio_device).unwrap();
+        audio_device.default_sample_rate
+    }
+
+    async fn is_default(&mut self, audio_device: Resource<AudioDevice>) -> bool {
+        let audio_device = self.table.get(&audio_device).unwrap();
+        audio_device.is_default
+    }
+
+    async fn is_input(&mut self, audio_device: Resource<AudioDevice>) -> bool {
+        let audio_device = self.table.get(&audio_device).unwrap();
+        audio_device.is_input
     }
 
     async fn create_context(
         &mut self,
-        _audio_device: Resource<AudioDevice>,
+        audio_device: Resource<AudioDevice>,
     ) -> Resource<AudioContext> {
+        let audio_device = self.table.get(&audio_device).unwrap();
+        let sink_id = match audio_device.is_input {
+            true => self.audio_devices.default_output().id,
+            false => self.audio_devices.available_sink_id(&audio_device.id),
+        };
+        let follows_default = sink_id == self.audio_devices.default_output().id;
         let audio_context = AudioContext::new(AudioContextOptions {
             sample_rate: Some(44100.),
+            sink_id,
             ..AudioContextOptions::default()
         });
-        self.table.push(audio_context).unwrap()
+        let mut output = AudioOutput::new(&audio_context);
+        output.follows_default = follows_default;
+        let audio_context = self.table.push(audio_context).unwrap();
+
+        if let Some(path) = &self.record_audio {
+            // Each context is recorded to its own file, numbered after the first.
+            let path = match self.audio_outputs.len() {
+                0 => path.clone(),
+                count => {
+                    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                    let mut name = format!("{}-{}", stem, count + 1);
+                    if let Some(extension) = path.extension() {
+                        name = format!("{}.{}", name, extension.to_string_lossy());
+                    }
+                    path.with_file_name(name)
+                }
+            };
+            if let Err(err) = output.start_recording(&path) {
+                super::debug::warn(&err);
+            }
+        }
+        self.audio_outputs.insert(audio_context.rep(), output);
+        audio_context
     }
 
-    async fn drop(&mut self, _rep: Resource<AudioDevice>) -> Result<()> {
+    async fn drop(&mut self, rep: Resource<AudioDevice>) -> Result<()> {
+        self.table.delete(rep)?;
         Ok(())
     }
 }
@@ -56,9 +161,25 @@ impl HostAudioContext for RuneRuntimeState {
         audio_context.sink_id()
     }
 
-    async fn set_sink_id(&mut self, audio_context: Resource<AudioContext>, sink_id: String) {
+    async fn set_sink_id(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        sink_id: String,
+    ) -> core::result::Result<(), String> {
+        if sink_id != "none" && !self.audio_devices.has_output(&sink_id) {
+            return Err(format!(
+                "There's no audio output device with the id {}",
+                sink_id
+            ));
+        }
+        // The game chose the device, so it stays there when the default changes.
+        if let Some(output) = self.audio_outputs.get_mut(&audio_context.rep()) {
+            output.follows_default = false;
+        }
         let audio_context = self.table.get(&audio_context).unwrap();
-        audio_context.set_sink_id_sync(sink_id).unwrap()
+        audio_context
+            .set_sink_id_sync(sink_id)
+            .map_err(|err| err.to_string())
     }
 
     async fn render_capacity(
@@ -80,23 +201,75 @@ impl HostAudioContext for RuneRuntimeState {
     }
 
     async fn close(&mut self, audio_context: Resource<AudioContext>) {
+        if let Some(output) = self.audio_outputs.get_mut(&audio_context.rep()) {
+            output.stop_recording();
+        }
         let audio_context = self.table.get(&audio_context).unwrap();
         audio_context.close_sync()
     }
 
+    async fn start_recording(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        path: Resource<crate::rune::runtime::storage::Path>,
+    ) -> core::result::Result<(), String> {
+        let path = self.paths.get(path.rep() as usize).unwrap().clone();
+        match self.audio_outputs.get_mut(&audio_context.rep()) {
+            // The player asked for that recording, so it's kept.
+            Some(output) if output.is_recording_for_cli() => {
+                Err("The audio is already being recorded for --record-audio".to_owned())
+            }
+            Some(output) => output.start_recording_to(path),
+            None => Err("The audio context is closed".to_owned()),
+        }
+    }
+
+    async fn stop_recording(&mut self, audio_context: Resource<AudioContext>) {
+        if let Some(output) = self.audio_outputs.get_mut(&audio_context.rep()) {
+            if !output.is_recording_for_cli() {
+                output.stop_recording();
+            }
+        }
+    }
+
+    async fn create_media_stream_source(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        input: Resource<AudioDevice>,
+    ) -> core::result::Result<Resource<MediaStreamAudioSourceNode>, String> {
+        RuneRuntimeState::create_media_stream_source(self, audio_context, input)
+    }
+
+    async fn create_stream_source(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        path: Resource<crate::rune::runtime::storage::Path>,
+    ) -> core::result::Result<Resource<AudioStreamSourceNode>, String> {
+        RuneRuntimeState::create_stream_source(self, audio_context, path)
+    }
+
+    async fn create_stream_source_from_data(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        data: Vec<u8>,
+    ) -> core::result::Result<Resource<AudioStreamSourceNode>, String> {
+        RuneRuntimeState::create_stream_source_from_data(self, audio_context, data)
+    }
+
     async fn decode_audio_data(
         &mut self,
         audio_context: Resource<AudioContext>,
         data: Vec<u8>,
-    ) -> Resource<AudioBuffer> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(
-                audio_context
-                    .decode_audio_data_sync(Cursor::new(data))
-                    .unwrap(),
-            )
-            .unwrap()
+    ) -> core::result::Result<Resource<AudioBuffer>, String> {
+        RuneRuntimeState::decode_audio_data(self, audio_context, data)
+    }
+
+    async fn start_decode_audio_data(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        data: Vec<u8>,
+    ) -> Resource<PendingAudioBuffer> {
+        RuneRuntimeState::start_decode_audio_data(self, audio_context, data)
     }
 
     async fn create_buffer(
@@ -106,60 +279,48 @@ impl HostAudioContext for RuneRuntimeState {
         length: u32,
         sample_rate: f32,
     ) -> Resource<AudioBuffer> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_buffer(
-                number_of_channels as usize,
-                length as usize,
-                sample_rate,
-            ))
-            .unwrap()
+        RuneRuntimeState::create_buffer(
+            self,
+            audio_context,
+            number_of_channels,
+            length,
+            sample_rate,
+        )
     }
 
     async fn create_biquad_filter(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<BiquadFilterNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_biquad_filter())
-            .unwrap()
+        RuneRuntimeState::create_biquad_filter(self, audio_context)
     }
 
     async fn create_buffer_source(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<AudioBufferSourceNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_buffer_source())
-            .unwrap()
+        RuneRuntimeState::create_buffer_source(self, audio_context)
     }
 
     async fn create_analyzer(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<AnalyzerNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table.push(audio_context.create_analyser()).unwrap()
+        RuneRuntimeState::create_analyzer(self, audio_context)
     }
 
     async fn create_constant_source(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<ConstantSourceNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_constant_source())
-            .unwrap()
+        RuneRuntimeState::create_constant_source(self, audio_context)
     }
 
     async fn create_convolver(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<ConvolverNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table.push(audio_context.create_convolver()).unwrap()
+        RuneRuntimeState::create_convolver(self, audio_context)
     }
 
     async fn create_channel_merger(
@@ -167,10 +328,7 @@ impl HostAudioContext for RuneRuntimeState {
         audio_context: Resource<AudioContext>,
         number_of_inputs: u32,
     ) -> Resource<ChannelMergerNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_channel_merger(number_of_inputs as usize))
-            .unwrap()
+        RuneRuntimeState::create_channel_merger(self, audio_context, number_of_inputs)
     }
 
     async fn create_channel_splitter(
@@ -178,10 +336,7 @@ impl HostAudioContext for RuneRuntimeState {
         audio_context: Resource<AudioContext>,
         number_of_outputs: u32,
     ) -> Resource<ChannelSplitterNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_channel_splitter(number_of_outputs as usize))
-            .unwrap()
+        RuneRuntimeState::create_channel_splitter(self, audio_context, number_of_outputs)
     }
 
     async fn create_delay(
@@ -189,25 +344,18 @@ impl HostAudioContext for RuneRuntimeState {
         audio_context: Resource<AudioContext>,
         max_delay_time: f32,
     ) -> Resource<DelayNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_delay(max_delay_time as f64))
-            .unwrap()
+        RuneRuntimeState::create_delay(self, audio_context, max_delay_time)
     }
 
     async fn create_dynamics_compressor(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<DynamicsCompressorNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_dynamics_compressor())
-            .unwrap()
+        RuneRuntimeState::create_dynamics_compressor(self, audio_context)
     }
 
     async fn create_gain(&mut self, audio_context: Resource<AudioContext>) -> Resource<GainNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table.push(audio_context.create_gain()).unwrap()
+        RuneRuntimeState::create_gain(self, audio_context)
     }
 
     async fn create_iir_filter(
@@ -216,66 +364,65 @@ impl HostAudioContext for RuneRuntimeState {
         feedforward: Vec<f32>,
         feedback: Vec<f32>,
     ) -> Resource<IIRFilterNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_iir_filter(
-                feedforward.iter().map(|&x| x as f64).collect(),
-                feedback.iter().map(|&x| x as f64).collect(),
-            ))
-            .unwrap()
+        RuneRuntimeState::create_iir_filter(self, audio_context, feedforward, feedback)
     }
 
     async fn create_oscillator(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<OscillatorNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table.push(audio_context.create_oscillator()).unwrap()
+        RuneRuntimeState::create_oscillator(self, audio_context)
     }
 
     async fn create_panner(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<PannerNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table.push(audio_context.create_panner()).unwrap()
+        RuneRuntimeState::create_panner(self, audio_context)
     }
 
     async fn create_periodic_wave(
         &mut self,
-        _audio_context: Resource<AudioContext>,
-        _options: PeriodicWaveOptions,
-    ) -> PeriodicWave {
-        todo!()
-        // let audio_context = self.table.get(&self_).unwrap();
-        // let periodic_wave = audio_context.create_periodic_wave(options.into());
-        // periodic_wave.into()
+        audio_context: Resource<AudioContext>,
+        options: PeriodicWaveOptions,
+    ) -> core::result::Result<Resource<PeriodicWave>, String> {
+        RuneRuntimeState::create_periodic_wave(self, audio_context, options)
     }
 
     async fn create_stereo_panner(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<StereoPannerNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table
-            .push(audio_context.create_stereo_panner())
-            .unwrap()
+        RuneRuntimeState::create_stereo_panner(self, audio_context)
     }
 
     async fn create_wave_shaper(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<WaveShaperNode> {
-        let audio_context = self.table.get(&audio_context).unwrap();
-        self.table.push(audio_context.create_wave_shaper()).unwrap()
+        RuneRuntimeState::create_wave_shaper(self, audio_context)
+    }
+
+    async fn create_audio_worklet(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        path: String,
+        options: AudioWorkletOptions,
+    ) -> core::result::Result<Resource<AudioWorkletNode>, String> {
+        RuneRuntimeState::create_audio_worklet(self, audio_context, path, options)
     }
 
     async fn destination(
         &mut self,
         audio_context: Resource<AudioContext>,
     ) -> Resource<AudioDestinationNode> {
+        let context_rep = audio_context.rep();
         let audio_context = self.table.get(&audio_context).unwrap();
-        self.table.push(audio_context.destination()).unwrap()
+        let destination = self.table.push(audio_context.destination()).unwrap();
+        if let Some(output) = self.audio_outputs.get_mut(&context_rep) {
+            output.destinations.push(destination.rep());
+        }
+        destination
     }
 
     async fn listener(&mut self, audio_context: Resource<AudioContext>) -> Resource<AudioListener> {
@@ -283,6 +430,16 @@ impl HostAudioContext for RuneRuntimeState {
         self.table.push(audio_context.listener()).unwrap()
     }
 
+    async fn set_listener_transform(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        position: Vec3,
+        forward: Vec3,
+        up: Vec3,
+    ) {
+        RuneRuntimeState::set_listener_transform(self, audio_context, position, forward, up)
+    }
+
     async fn sample_rate(&mut self, audio_context: Resource<AudioContext>) -> f32 {
         let audio_context = self.table.get(&audio_context).unwrap();
         audio_context.sample_rate() as f32
@@ -298,7 +455,12 @@ impl HostAudioContext for RuneRuntimeState {
         audio_context.current_time() as f32
     }
 
-    async fn drop(&mut self, _rep: Resource<AudioContext>) -> Result<()> {
+    async fn drop(&mut self, rep: Resource<AudioContext>) -> Result<()> {
+        if let Some(mut output) = self.audio_outputs.remove(&rep.rep()) {
+            output.stop_recording();
+        }
+        // Connections to the context's destination were kept under the context.
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -346,130 +508,353 @@ impl HostAudioBuffer for RuneRuntimeState {
     }
 }
 
-impl HostAudioParam for RuneRuntimeState {
-    async fn automation_rate(&mut self, audio_param: Resource<AudioParam>) -> AutomationRate {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.automation_rate().into()
+impl HostOfflineAudioContext for RuneRuntimeState {
+    async fn length(&mut self, audio_context: Resource<OfflineAudioContext>) -> u32 {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        audio_context.length() as u32
     }
 
-    async fn set_automation_rate(
+    async fn start_rendering(
         &mut self,
-        audio_param: Resource<AudioParam>,
-        automation_rate: AutomationRate,
-    ) {
-        let audio_param = self.table.get_mut(&audio_param).unwrap();
-        audio_param.set_automation_rate(automation_rate.into());
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> core::result::Result<Resource<AudioBuffer>, String> {
+        let audio_context = self.table.get_mut(&audio_context).unwrap();
+        if audio_context.state() != web_audio_api::context::AudioContextState::Suspended {
+            return Err("The offline audio context has already been rendered".to_owned());
+        }
+        let audio_buffer = audio_context.start_rendering_sync();
+        Ok(self.table.push(audio_buffer).unwrap())
     }
 
-    async fn default_value(&mut self, audio_param: Resource<AudioParam>) -> f32 {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.default_value()
+    async fn decode_audio_data(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+        data: Vec<u8>,
+    ) -> core::result::Result<Resource<AudioBuffer>, String> {
+        RuneRuntimeState::decode_audio_data(self, audio_context, data)
     }
 
-    async fn min_value(&mut self, audio_param: Resource<AudioParam>) -> f32 {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.min_value()
+    async fn start_decode_audio_data(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+        data: Vec<u8>,
+    ) -> Resource<PendingAudioBuffer> {
+        RuneRuntimeState::start_decode_audio_data(self, audio_context, data)
     }
 
-    async fn max_value(&mut self, audio_param: Resource<AudioParam>) -> f32 {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.max_value()
+    async fn create_buffer(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+        number_of_channels: u32,
+        length: u32,
+        sample_rate: f32,
+    ) -> Resource<AudioBuffer> {
+        RuneRuntimeState::create_buffer(
+            self,
+            audio_context,
+            number_of_channels,
+            length,
+            sample_rate,
+        )
     }
 
-    async fn value(&mut self, audio_param: Resource<AudioParam>) -> f32 {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.value()
+    async fn create_biquad_filter(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<BiquadFilterNode> {
+        RuneRuntimeState::create_biquad_filter(self, audio_context)
     }
 
-    async fn set_value(&mut self, audio_param: Resource<AudioParam>, value: f32) {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.set_value(value);
+    async fn create_buffer_source(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<AudioBufferSourceNode> {
+        RuneRuntimeState::create_buffer_source(self, audio_context)
     }
 
-    async fn set_value_at_time(
+    async fn create_analyzer(
         &mut self,
-        audio_param: Resource<AudioParam>,
-        value: f32,
-        end_time: f32,
-    ) {
-        let audio_param = self.table.get_mut(&audio_param).unwrap();
-        audio_param.set_value_at_time(value, end_time as f64);
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<AnalyzerNode> {
+        RuneRuntimeState::create_analyzer(self, audio_context)
     }
 
-    async fn set_value_curve_at_time(
+    async fn create_constant_source(
         &mut self,
-        audio_param: Resource<AudioParam>,
-        value: Vec<f32>,
-        start_time: f32,
-        duration: f32,
-    ) {
-        let audio_param = self.table.get_mut(&audio_param).unwrap();
-        audio_param.set_value_curve_at_time(&value, start_time as f64, duration as f64);
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<ConstantSourceNode> {
+        RuneRuntimeState::create_constant_source(self, audio_context)
     }
 
-    async fn linear_ramp_to_value_at_time(
+    async fn create_convolver(
         &mut self,
-        audio_param: Resource<AudioParam>,
-        value: f32,
-        end_time: f32,
-    ) {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.linear_ramp_to_value_at_time(value, end_time as f64);
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<ConvolverNode> {
+        RuneRuntimeState::create_convolver(self, audio_context)
     }
 
-    async fn exponential_ramp_to_value_at_time(
+    async fn create_channel_merger(
         &mut self,
-        audio_param: Resource<AudioParam>,
-        value: f32,
-        end_time: f32,
-    ) {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.exponential_ramp_to_value_at_time(value, end_time as f64);
+        audio_context: Resource<OfflineAudioContext>,
+        number_of_inputs: u32,
+    ) -> Resource<ChannelMergerNode> {
+        RuneRuntimeState::create_channel_merger(self, audio_context, number_of_inputs)
     }
 
-    async fn set_target_at_time(
+    async fn create_channel_splitter(
         &mut self,
-        audio_param: Resource<AudioParam>,
-        value: f32,
-        start_time: f32,
-        time_constant: f32,
-    ) {
-        let audio_param = self.table.get_mut(&audio_param).unwrap();
-        audio_param.set_target_at_time(value, start_time as f64, time_constant as f64);
+        audio_context: Resource<OfflineAudioContext>,
+        number_of_outputs: u32,
+    ) -> Resource<ChannelSplitterNode> {
+        RuneRuntimeState::create_channel_splitter(self, audio_context, number_of_outputs)
     }
 
-    async fn cancel_scheduled_values(
+    async fn create_delay(
         &mut self,
-        audio_param: Resource<AudioParam>,
-        cancel_time: f32,
-    ) {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.cancel_scheduled_values(cancel_time as f64);
+        audio_context: Resource<OfflineAudioContext>,
+        max_delay_time: f32,
+    ) -> Resource<DelayNode> {
+        RuneRuntimeState::create_delay(self, audio_context, max_delay_time)
     }
 
-    async fn cancel_and_hold_at_time(
+    async fn create_dynamics_compressor(
         &mut self,
-        audio_param: Resource<AudioParam>,
-        cancel_time: f32,
-    ) {
-        let audio_param = self.table.get(&audio_param).unwrap();
-        audio_param.cancel_and_hold_at_time(cancel_time as f64);
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<DynamicsCompressorNode> {
+        RuneRuntimeState::create_dynamics_compressor(self, audio_context)
     }
 
-    async fn drop(&mut self, _rep: Resource<AudioParam>) -> Result<()> {
-        Ok(())
+    async fn create_gain(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<GainNode> {
+        RuneRuntimeState::create_gain(self, audio_context)
     }
-}
 
-impl HostAudioRenderCapacity for RuneRuntimeState {
-    async fn drop(&mut self, _rep: Resource<AudioRenderCapacity>) -> Result<()> {
-        Ok(())
+    async fn create_iir_filter(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+        feedforward: Vec<f32>,
+        feedback: Vec<f32>,
+    ) -> Resource<IIRFilterNode> {
+        RuneRuntimeState::create_iir_filter(self, audio_context, feedforward, feedback)
     }
-}
 
-impl HostAnalyzerNode for RuneRuntimeState {
-    async fn fft_size(&mut self, node: Resource<AnalyzerNode>) -> u32 {
-        let audio_param = self.table.get(&node).unwrap();
+    async fn create_oscillator(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<OscillatorNode> {
+        RuneRuntimeState::create_oscillator(self, audio_context)
+    }
+
+    async fn create_panner(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<PannerNode> {
+        RuneRuntimeState::create_panner(self, audio_context)
+    }
+
+    async fn create_periodic_wave(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+        options: PeriodicWaveOptions,
+    ) -> core::result::Result<Resource<PeriodicWave>, String> {
+        RuneRuntimeState::create_periodic_wave(self, audio_context, options)
+    }
+
+    async fn create_stereo_panner(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<StereoPannerNode> {
+        RuneRuntimeState::create_stereo_panner(self, audio_context)
+    }
+
+    async fn create_wave_shaper(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<WaveShaperNode> {
+        RuneRuntimeState::create_wave_shaper(self, audio_context)
+    }
+
+    async fn create_audio_worklet(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+        path: String,
+        options: AudioWorkletOptions,
+    ) -> core::result::Result<Resource<AudioWorkletNode>, String> {
+        RuneRuntimeState::create_audio_worklet(self, audio_context, path, options)
+    }
+
+    async fn destination(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<AudioDestinationNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table.push(audio_context.destination()).unwrap()
+    }
+
+    async fn listener(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+    ) -> Resource<AudioListener> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table.push(audio_context.listener()).unwrap()
+    }
+
+    async fn set_listener_transform(
+        &mut self,
+        audio_context: Resource<OfflineAudioContext>,
+        position: Vec3,
+        forward: Vec3,
+        up: Vec3,
+    ) {
+        RuneRuntimeState::set_listener_transform(self, audio_context, position, forward, up)
+    }
+
+    async fn sample_rate(&mut self, audio_context: Resource<OfflineAudioContext>) -> f32 {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        audio_context.sample_rate() as f32
+    }
+
+    async fn state(&mut self, audio_context: Resource<OfflineAudioContext>) -> AudioContextState {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        audio_context.state().into()
+    }
+
+    async fn current_time(&mut self, audio_context: Resource<OfflineAudioContext>) -> f32 {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        audio_context.current_time() as f32
+    }
+
+    async fn drop(&mut self, _rep: Resource<OfflineAudioContext>) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl HostAudioParam for RuneRuntimeState {
+    async fn automation_rate(&mut self, audio_param: Resource<AudioParam>) -> AutomationRate {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.automation_rate().into()
+    }
+
+    async fn set_automation_rate(
+        &mut self,
+        audio_param: Resource<AudioParam>,
+        automation_rate: AutomationRate,
+    ) {
+        let audio_param = self.table.get_mut(&audio_param).unwrap();
+        audio_param.set_automation_rate(automation_rate.into());
+    }
+
+    async fn default_value(&mut self, audio_param: Resource<AudioParam>) -> f32 {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.default_value()
+    }
+
+    async fn min_value(&mut self, audio_param: Resource<AudioParam>) -> f32 {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.min_value()
+    }
+
+    async fn max_value(&mut self, audio_param: Resource<AudioParam>) -> f32 {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.max_value()
+    }
+
+    async fn value(&mut self, audio_param: Resource<AudioParam>) -> f32 {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.value()
+    }
+
+    async fn set_value(&mut self, audio_param: Resource<AudioParam>, value: f32) {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.set_value(value);
+    }
+
+    async fn set_value_at_time(
+        &mut self,
+        audio_param: Resource<AudioParam>,
+        value: f32,
+        end_time: f32,
+    ) {
+        let audio_param = self.table.get_mut(&audio_param).unwrap();
+        audio_param.set_value_at_time(value, end_time as f64);
+    }
+
+    async fn set_value_curve_at_time(
+        &mut self,
+        audio_param: Resource<AudioParam>,
+        value: Vec<f32>,
+        start_time: f32,
+        duration: f32,
+    ) {
+        let audio_param = self.table.get_mut(&audio_param).unwrap();
+        audio_param.set_value_curve_at_time(&value, start_time as f64, duration as f64);
+    }
+
+    async fn linear_ramp_to_value_at_time(
+        &mut self,
+        audio_param: Resource<AudioParam>,
+        value: f32,
+        end_time: f32,
+    ) {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.linear_ramp_to_value_at_time(value, end_time as f64);
+    }
+
+    async fn exponential_ramp_to_value_at_time(
+        &mut self,
+        audio_param: Resource<AudioParam>,
+        value: f32,
+        end_time: f32,
+    ) {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.exponential_ramp_to_value_at_time(value, end_time as f64);
+    }
+
+    async fn set_target_at_time(
+        &mut self,
+        audio_param: Resource<AudioParam>,
+        value: f32,
+        start_time: f32,
+        time_constant: f32,
+    ) {
+        let audio_param = self.table.get_mut(&audio_param).unwrap();
+        audio_param.set_target_at_time(value, start_time as f64, time_constant as f64);
+    }
+
+    async fn cancel_scheduled_values(
+        &mut self,
+        audio_param: Resource<AudioParam>,
+        cancel_time: f32,
+    ) {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.cancel_scheduled_values(cancel_time as f64);
+    }
+
+    async fn cancel_and_hold_at_time(
+        &mut self,
+        audio_param: Resource<AudioParam>,
+        cancel_time: f32,
+    ) {
+        let audio_param = self.table.get(&audio_param).unwrap();
+        audio_param.cancel_and_hold_at_time(cancel_time as f64);
+    }
+
+    async fn drop(&mut self, _rep: Resource<AudioParam>) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl HostAudioRenderCapacity for RuneRuntimeState {
+    async fn drop(&mut self, _rep: Resource<AudioRenderCapacity>) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl HostAnalyzerNode for RuneRuntimeState {
+    async fn fft_size(&mut self, node: Resource<AnalyzerNode>) -> u32 {
+        let audio_param = self.table.get(&node).unwrap();
         audio_param.fft_size() as u32
     }
 
@@ -545,12 +930,114 @@ impl HostAnalyzerNode for RuneRuntimeState {
         &mut self,
         node: Resource<AnalyzerNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<AnalyzerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<AnalyzerNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<AnalyzerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<AnalyzerNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<AnalyzerNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<AnalyzerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<AnalyzerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<AnalyzerNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -590,12 +1077,114 @@ impl HostBiquadFilterNode for RuneRuntimeState {
         &mut self,
         node: Resource<BiquadFilterNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<BiquadFilterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<BiquadFilterNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<BiquadFilterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<BiquadFilterNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<BiquadFilterNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<BiquadFilterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<BiquadFilterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<BiquadFilterNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -606,7 +1195,12 @@ impl HostAudioDestinationNode for RuneRuntimeState {
         node.max_channel_count() as u32
     }
 
-    async fn drop(&mut self, _rep: Resource<AudioDestinationNode>) -> Result<()> {
+    async fn drop(&mut self, rep: Resource<AudioDestinationNode>) -> Result<()> {
+        for output in self.audio_outputs.values_mut() {
+            output
+                .destinations
+                .retain(|&destination| destination != rep.rep());
+        }
         Ok(())
     }
 }
@@ -617,9 +1211,12 @@ impl HostAudioBufferSourceNode for RuneRuntimeState {
         node: Resource<AudioBufferSourceNode>,
         start: f32,
         offset: f32,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let (start, offset) = (audio_time(start)?, audio_time(offset)?);
+        self.start_playback(node.rep())?;
         let node = self.table.get_mut(&node).unwrap();
-        node.start_at_with_offset(start as f64, offset as f64);
+        node.start_at_with_offset(start, offset);
+        Ok(())
     }
 
     async fn start_at_with_offset_and_duration(
@@ -628,9 +1225,16 @@ impl HostAudioBufferSourceNode for RuneRuntimeState {
         start: f32,
         offset: f32,
         duration: f32,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let (start, offset, duration) = (
+            audio_time(start)?,
+            audio_time(offset)?,
+            audio_time(duration)?,
+        );
+        self.start_playback(node.rep())?;
         let node = self.table.get_mut(&node).unwrap();
-        node.start_at_with_offset_and_duration(start as f64, offset as f64, duration as f64);
+        node.start_at_with_offset_and_duration(start, offset, duration);
+        Ok(())
     }
 
     async fn buffer(
@@ -706,37 +1310,338 @@ impl HostAudioBufferSourceNode for RuneRuntimeState {
         &mut self,
         node: Resource<AudioBufferSourceNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
-    }
-
-    async fn start(&mut self, node: Resource<AudioBufferSourceNode>) {
-        let node = self.table.get_mut(&node).unwrap();
-        node.start();
-    }
-
-    async fn drop(&mut self, _rep: Resource<AudioBufferSourceNode>) -> Result<()> {
-        Ok(())
-    }
-}
-
-impl HostConstantSourceNode for RuneRuntimeState {
-    async fn offset(&mut self, node: Resource<ConstantSourceNode>) -> Resource<AudioParam> {
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<AudioBufferSourceNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<AudioBufferSourceNode>) {
+        self.forget_audio_connections(node.rep());
         let node = self.table.get(&node).unwrap();
-        self.table.push(node.offset().clone()).unwrap()
+        node.disconnect();
     }
 
-    async fn connect(
+    async fn disconnect_from(
         &mut self,
-        node: Resource<ConstantSourceNode>,
+        node: Resource<AudioBufferSourceNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<AudioBufferSourceNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
+    }
+
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<AudioBufferSourceNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<AudioBufferSourceNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn start(
+        &mut self,
+        node: Resource<AudioBufferSourceNode>,
+    ) -> core::result::Result<(), String> {
+        self.start_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.start();
+        Ok(())
+    }
+
+    async fn start_at(
+        &mut self,
+        node: Resource<AudioBufferSourceNode>,
+        when: f32,
+    ) -> core::result::Result<(), String> {
+        let when = audio_time(when)?;
+        self.start_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.start_at(when);
+        Ok(())
+    }
+
+    async fn stop(
+        &mut self,
+        node: Resource<AudioBufferSourceNode>,
+    ) -> core::result::Result<(), String> {
+        self.stop_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.stop();
+        Ok(())
+    }
+
+    async fn stop_at(
+        &mut self,
+        node: Resource<AudioBufferSourceNode>,
+        when: f32,
+    ) -> core::result::Result<(), String> {
+        let when = audio_time(when)?;
+        self.stop_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.stop_at(when);
+        Ok(())
+    }
+
+    async fn ended(&mut self, node: Resource<AudioBufferSourceNode>) -> bool {
+        self.audio_ended
+            .get(&node.rep())
+            .is_some_and(|ended| ended.load(Ordering::Acquire))
+    }
+
+    async fn drop(&mut self, rep: Resource<AudioBufferSourceNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
+        self.audio_ended.remove(&rep.rep());
+        Ok(())
+    }
+}
+
+impl HostConstantSourceNode for RuneRuntimeState {
+    async fn offset(&mut self, node: Resource<ConstantSourceNode>) -> Resource<AudioParam> {
+        let node = self.table.get(&node).unwrap();
+        self.table.push(node.offset().clone()).unwrap()
+    }
+
+    async fn start(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+    ) -> core::result::Result<(), String> {
+        self.start_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.start();
+        Ok(())
+    }
+
+    async fn start_at(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+        when: f32,
+    ) -> core::result::Result<(), String> {
+        let when = audio_time(when)?;
+        self.start_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.start_at(when);
+        Ok(())
+    }
+
+    async fn stop(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+    ) -> core::result::Result<(), String> {
+        self.stop_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.stop();
+        Ok(())
+    }
+
+    async fn stop_at(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+        when: f32,
+    ) -> core::result::Result<(), String> {
+        let when = audio_time(when)?;
+        self.stop_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.stop_at(when);
+        Ok(())
+    }
+
+    async fn ended(&mut self, node: Resource<ConstantSourceNode>) -> bool {
+        self.audio_ended
+            .get(&node.rep())
+            .is_some_and(|ended| ended.load(Ordering::Acquire))
+    }
+
+    async fn connect(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<ConstantSourceNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<ConstantSourceNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<ConstantSourceNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<ConstantSourceNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
+        self.audio_ended.remove(&rep.rep());
         Ok(())
     }
 }
@@ -770,12 +1675,114 @@ impl HostConvolverNode for RuneRuntimeState {
         &mut self,
         node: Resource<ConvolverNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<ConvolverNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<ConvolverNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<ConvolverNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<ConvolverNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<ConvolverNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<ConvolverNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<ConvolverNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<ConvolverNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -785,12 +1792,114 @@ impl HostChannelMergerNode for RuneRuntimeState {
         &mut self,
         node: Resource<ChannelMergerNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<ChannelMergerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<ChannelMergerNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<ChannelMergerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<ChannelMergerNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<ChannelMergerNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<ChannelMergerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<ChannelMergerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<ChannelMergerNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -800,12 +1909,114 @@ impl HostChannelSplitterNode for RuneRuntimeState {
         &mut self,
         node: Resource<ChannelSplitterNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<ChannelSplitterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<ChannelSplitterNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<ChannelSplitterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<ChannelSplitterNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<ChannelSplitterNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<ChannelSplitterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<ChannelSplitterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<ChannelSplitterNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -820,12 +2031,114 @@ impl HostDelayNode for RuneRuntimeState {
         &mut self,
         node: Resource<DelayNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<DelayNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<DelayNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<DelayNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<DelayNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<DelayNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<DelayNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<DelayNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<DelayNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -865,12 +2178,114 @@ impl HostDynamicsCompressorNode for RuneRuntimeState {
         &mut self,
         node: Resource<DynamicsCompressorNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<DynamicsCompressorNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<DynamicsCompressorNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<DynamicsCompressorNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<DynamicsCompressorNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<DynamicsCompressorNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<DynamicsCompressorNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<DynamicsCompressorNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<DynamicsCompressorNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -885,12 +2300,114 @@ impl HostGainNode for RuneRuntimeState {
         &mut self,
         node: Resource<GainNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<GainNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<GainNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<GainNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<GainNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<GainNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<GainNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<GainNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<GainNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -900,12 +2417,114 @@ impl HostIirFilterNode for RuneRuntimeState {
         &mut self,
         node: Resource<IIRFilterNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<IIRFilterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<IIRFilterNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<IIRFilterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<IIRFilterNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<IIRFilterNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<IIRFilterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<IIRFilterNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<IIRFilterNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -933,24 +2552,171 @@ impl HostOscillatorNode for RuneRuntimeState {
 
     async fn set_periodic_wave(
         &mut self,
-        _node: Resource<OscillatorNode>,
-        _periodic_wave: PeriodicWave,
+        node: Resource<OscillatorNode>,
+        periodic_wave: Resource<PeriodicWave>,
     ) {
-        // let node = self.table.get_mut(&self_).unwrap();
-        // node.set_periodic_wave(periodic_wave.into());
-        todo!()
+        let periodic_wave = { self.table.get(&periodic_wave).unwrap().clone() };
+        let node = self.table.get_mut(&node).unwrap();
+        node.set_periodic_wave(periodic_wave);
+    }
+
+    async fn start(&mut self, node: Resource<OscillatorNode>) -> core::result::Result<(), String> {
+        self.start_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.start();
+        Ok(())
+    }
+
+    async fn start_at(
+        &mut self,
+        node: Resource<OscillatorNode>,
+        when: f32,
+    ) -> core::result::Result<(), String> {
+        let when = audio_time(when)?;
+        self.start_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.start_at(when);
+        Ok(())
+    }
+
+    async fn stop(&mut self, node: Resource<OscillatorNode>) -> core::result::Result<(), String> {
+        self.stop_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.stop();
+        Ok(())
+    }
+
+    async fn stop_at(
+        &mut self,
+        node: Resource<OscillatorNode>,
+        when: f32,
+    ) -> core::result::Result<(), String> {
+        let when = audio_time(when)?;
+        self.stop_playback(node.rep())?;
+        let node = self.table.get_mut(&node).unwrap();
+        node.stop_at(when);
+        Ok(())
+    }
+
+    async fn ended(&mut self, node: Resource<OscillatorNode>) -> bool {
+        self.audio_ended
+            .get(&node.rep())
+            .is_some_and(|ended| ended.load(Ordering::Acquire))
     }
 
     async fn connect(
         &mut self,
         node: Resource<OscillatorNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<OscillatorNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<OscillatorNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<OscillatorNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<OscillatorNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<OscillatorNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<OscillatorNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<OscillatorNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<OscillatorNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
+        self.audio_ended.remove(&rep.rep());
         Ok(())
     }
 }
@@ -996,6 +2762,23 @@ impl HostPannerNode for RuneRuntimeState {
         node.set_orientation(x, y, z);
     }
 
+    async fn set_transform(
+        &mut self,
+        node: Resource<PannerNode>,
+        position: Vec3,
+        orientation: Vec3,
+    ) {
+        RuneRuntimeState::set_panner_transform(self, node, position, orientation)
+    }
+
+    async fn doppler_factor(&mut self, node: Resource<PannerNode>) -> f32 {
+        RuneRuntimeState::doppler_factor(self, node)
+    }
+
+    async fn set_doppler_factor(&mut self, node: Resource<PannerNode>, value: f32) {
+        RuneRuntimeState::set_doppler_factor(self, node, value)
+    }
+
     async fn distance_model(&mut self, node: Resource<PannerNode>) -> DistanceModelType {
         let node = self.table.get(&node).unwrap();
         node.distance_model().into()
@@ -1080,12 +2863,120 @@ impl HostPannerNode for RuneRuntimeState {
         &mut self,
         node: Resource<PannerNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
-        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+    ) -> core::result::Result<(), String> {
+        let source = panner_output(&self.table, &self.audio_dopplers, &node);
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<PannerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source = panner_output(&self.table, &self.audio_dopplers, &node);
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<PannerNode>) {
+        self.forget_audio_connections(node.rep());
+        panner_output(&self.table, &self.audio_dopplers, &node).disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<PannerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source = panner_output(&self.table, &self.audio_dopplers, &node);
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<PannerNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source = panner_output(&self.table, &self.audio_dopplers, &node);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
+    }
+
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<PannerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source = panner_output(&self.table, &self.audio_dopplers, &node);
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<PannerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source = panner_output(&self.table, &self.audio_dopplers, &node);
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<PannerNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
+        self.audio_dopplers.remove(&rep.rep());
+        Ok(())
     }
+}
 
-    async fn drop(&mut self, _rep: Resource<PannerNode>) -> Result<()> {
+impl HostPeriodicWave for RuneRuntimeState {
+    async fn drop(&mut self, _rep: Resource<PeriodicWave>) -> Result<()> {
         Ok(())
     }
 }
@@ -1100,12 +2991,114 @@ impl HostStereoPannerNode for RuneRuntimeState {
         &mut self,
         node: Resource<StereoPannerNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<StereoPannerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<StereoPannerNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<StereoPannerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<StereoPannerNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<StereoPannerNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<StereoPannerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<StereoPannerNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<StereoPannerNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -1135,12 +3128,114 @@ impl HostWaveShaperNode for RuneRuntimeState {
         &mut self,
         node: Resource<WaveShaperNode>,
         destination: crate::rune::runtime::audio::AudioNode,
-    ) {
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<WaveShaperNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<WaveShaperNode>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<WaveShaperNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<WaveShaperNode>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
         let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
-        audio_node_connect(&self.table, source, destination);
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
     }
 
-    async fn drop(&mut self, _rep: Resource<WaveShaperNode>) -> Result<()> {
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<WaveShaperNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<WaveShaperNode>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { self.table.get(&node).unwrap() };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<WaveShaperNode>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
         Ok(())
     }
 }
@@ -1196,29 +3291,844 @@ impl HostAudioListener for RuneRuntimeState {
     }
 }
 
-fn audio_node_connect(
+/// The connections the game has made, keyed by the table index of the source node, its output,
+/// the table index of the destination and its input, so disconnecting nodes that aren't
+/// connected can fail instead of panicking.
+pub type AudioConnections = HashMap<(u32, u32, u32, u32), crate::rune::runtime::audio::AudioNode>;
+
+/// Whether a source node has been started or stopped, since web_audio_api panics on starting one
+/// twice or stopping one that hasn't started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Playback {
+    Started,
+    Stopped,
+}
+
+impl RuneRuntimeState {
+    /// Records that a source node is starting, or says why it can't.
+    fn start_playback(&mut self, rep: u32) -> core::result::Result<(), String> {
+        if self.audio_playback.contains_key(&rep) {
+            return Err(
+                "The node has already been started, and can only be started once".to_owned(),
+            );
+        }
+        self.audio_playback.insert(rep, Playback::Started);
+        Ok(())
+    }
+
+    /// Records that a source node is stopping, or says why it can't.
+    fn stop_playback(&mut self, rep: u32) -> core::result::Result<(), String> {
+        match self.audio_playback.get(&rep) {
+            Some(Playback::Started) => {
+                self.audio_playback.insert(rep, Playback::Stopped);
+                Ok(())
+            }
+            Some(Playback::Stopped) => Err("The node has already been stopped".to_owned()),
+            None => Err("The node has to be started before it's stopped".to_owned()),
+        }
+    }
+
+    /// Forgets the connections from a node once it's disconnected from everything.
+    pub(super) fn forget_audio_connections(&mut self, rep: u32) {
+        self.audio_connections
+            .retain(|&(source, ..), _| source != rep);
+    }
+
+    /// Forgets a dropped node or context, so its table index can be reused.
+    pub(super) fn forget_audio_node(&mut self, rep: u32) {
+        self.audio_connections
+            .retain(|&(source, _, destination, _), _| source != rep && destination != rep);
+        self.audio_playback.remove(&rep);
+    }
+
+    /// Adds a source node to the table, remembering when it ends so the guest can ask.
+    fn push_scheduled_source<N>(&mut self, node: N) -> Resource<N>
+    where
+        N: AudioScheduledSourceNode + Send + 'static,
+    {
+        let ended = Arc::new(AtomicBool::new(false));
+        node.set_onended({
+            let ended = ended.clone();
+            move |_| ended.store(true, Ordering::Release)
+        });
+        let node = self.table.push(node).unwrap();
+        self.audio_ended.insert(node.rep(), ended);
+        node
+    }
+}
+
+/// Both kinds of context create their buffers and nodes the same way.
+impl RuneRuntimeState {
+    fn decode_audio_data<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        data: Vec<u8>,
+    ) -> core::result::Result<Resource<AudioBuffer>, String> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let audio_buffer = audio_context
+            .decode_audio_data_sync(Cursor::new(data))
+            .map_err(|err| format!("Couldn't decode the audio data: {}", err))?;
+        Ok(self.table.push(audio_buffer).unwrap())
+    }
+
+    fn create_buffer<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        number_of_channels: u32,
+        length: u32,
+        sample_rate: f32,
+    ) -> Resource<AudioBuffer> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table
+            .push(audio_context.create_buffer(
+                number_of_channels as usize,
+                length as usize,
+                sample_rate,
+            ))
+            .unwrap()
+    }
+
+    fn create_biquad_filter<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<BiquadFilterNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table
+            .push(audio_context.create_biquad_filter())
+            .unwrap()
+    }
+
+    fn create_buffer_source<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<AudioBufferSourceNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let node = audio_context.create_buffer_source();
+        self.push_scheduled_source(node)
+    }
+
+    fn create_analyzer<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<AnalyzerNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table.push(audio_context.create_analyser()).unwrap()
+    }
+
+    fn create_constant_source<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<ConstantSourceNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let node = audio_context.create_constant_source();
+        self.push_scheduled_source(node)
+    }
+
+    fn create_convolver<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<ConvolverNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table.push(audio_context.create_convolver()).unwrap()
+    }
+
+    fn create_channel_merger<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        number_of_inputs: u32,
+    ) -> Resource<ChannelMergerNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table
+            .push(audio_context.create_channel_merger(number_of_inputs as usize))
+            .unwrap()
+    }
+
+    fn create_channel_splitter<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        number_of_outputs: u32,
+    ) -> Resource<ChannelSplitterNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table
+            .push(audio_context.create_channel_splitter(number_of_outputs as usize))
+            .unwrap()
+    }
+
+    fn create_delay<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        max_delay_time: f32,
+    ) -> Resource<DelayNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table
+            .push(audio_context.create_delay(max_delay_time as f64))
+            .unwrap()
+    }
+
+    fn create_dynamics_compressor<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<DynamicsCompressorNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table
+            .push(audio_context.create_dynamics_compressor())
+            .unwrap()
+    }
+
+    fn create_gain<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<GainNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table.push(audio_context.create_gain()).unwrap()
+    }
+
+    fn create_iir_filter<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        feedforward: Vec<f32>,
+        feedback: Vec<f32>,
+    ) -> Resource<IIRFilterNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table
+            .push(audio_context.create_iir_filter(
+                feedforward.iter().map(|&x| x as f64).collect(),
+                feedback.iter().map(|&x| x as f64).collect(),
+            ))
+            .unwrap()
+    }
+
+    fn create_oscillator<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<OscillatorNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let node = audio_context.create_oscillator();
+        self.push_scheduled_source(node)
+    }
+
+    fn create_panner<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<PannerNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table.push(audio_context.create_panner()).unwrap()
+    }
+
+    fn create_periodic_wave<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        options: PeriodicWaveOptions,
+    ) -> core::result::Result<Resource<PeriodicWave>, String> {
+        let options = periodic_wave_options(options)?;
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let periodic_wave = audio_context.create_periodic_wave(options);
+        Ok(self.table.push(periodic_wave).unwrap())
+    }
+
+    fn create_stereo_panner<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<StereoPannerNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table
+            .push(audio_context.create_stereo_panner())
+            .unwrap()
+    }
+
+    fn create_wave_shaper<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+    ) -> Resource<WaveShaperNode> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        self.table.push(audio_context.create_wave_shaper()).unwrap()
+    }
+}
+
+/// Checks that a path the guest gave stays inside the directory it's relative to.
+pub(super) fn relative_path(path: &str) -> core::result::Result<&Path, String> {
+    let path = Path::new(path);
+    if path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        Ok(path)
+    } else {
+        Err(format!(
+            "{} isn't a relative path inside its directory",
+            path.display()
+        ))
+    }
+}
+
+/// Checks a time or duration in seconds first, since web_audio_api panics on negative and
+/// non-finite ones.
+fn audio_time(seconds: f32) -> core::result::Result<f64, String> {
+    if seconds.is_finite() && seconds >= 0. {
+        Ok(seconds as f64)
+    } else {
+        Err(format!(
+            "{} isn't a time in seconds, which can't be negative",
+            seconds
+        ))
+    }
+}
+
+/// Checks the coefficients of a periodic wave first, since web_audio_api panics on ones it can't use.
+fn periodic_wave_options(
+    options: PeriodicWaveOptions,
+) -> core::result::Result<web_audio_api::PeriodicWaveOptions, String> {
+    for coefficients in [&options.real, &options.imag].into_iter().flatten() {
+        if coefficients.len() < 2 {
+            return Err("A periodic wave needs at least 2 coefficients".to_owned());
+        }
+    }
+    if let (Some(real), Some(imag)) = (&options.real, &options.imag) {
+        if real.len() != imag.len() {
+            return Err(format!(
+                "The periodic wave has {} real and {} imaginary coefficients, which should match",
+                real.len(),
+                imag.len()
+            ));
+        }
+    }
+
+    Ok(web_audio_api::PeriodicWaveOptions {
+        real: options.real,
+        imag: options.imag,
+        disable_normalization: options.disable_normalization,
+    })
+}
+
+/// Connects a node and records the connection, checking first what web_audio_api would panic on.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn audio_node_connect(
+    table: &ResourceTable,
+    outputs: &HashMap<u32, AudioOutput>,
+    connections: &mut AudioConnections,
+    source_rep: u32,
+    source: &dyn AudioNode,
+    destination: crate::rune::runtime::audio::AudioNode,
+    output: u32,
+    input: u32,
+) -> core::result::Result<(), String> {
+    let node = audio_node(table, outputs, &destination);
+    if source.context() != node.context() {
+        return Err("The nodes belong to different audio contexts".to_owned());
+    }
+    if source.number_of_outputs() == 0 {
+        return Err("The node has no outputs to connect".to_owned());
+    }
+    if node.number_of_inputs() == 0 {
+        return Err("The destination node has no inputs to connect to".to_owned());
+    }
+    if output as usize >= source.number_of_outputs() {
+        return Err(format!(
+            "The node has {} outputs, so there's no output {}",
+            source.number_of_outputs(),
+            output
+        ));
+    }
+    if input as usize >= node.number_of_inputs() {
+        return Err(format!(
+            "The destination node has {} inputs, so there's no input {}",
+            node.number_of_inputs(),
+            input
+        ));
+    }
+    source.connect_from_output_to_input(node, output as usize, input as usize);
+    connections.insert(
+        (
+            source_rep,
+            output,
+            audio_node_key(outputs, &destination),
+            input,
+        ),
+        destination,
+    );
+    Ok(())
+}
+
+/// Disconnects a node from one destination, or only the connections from one of its outputs or
+/// to one of the destination's inputs, which web_audio_api panics on if they aren't connected.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn audio_node_disconnect(
     table: &ResourceTable,
+    outputs: &HashMap<u32, AudioOutput>,
+    connections: &mut AudioConnections,
+    source_rep: u32,
     source: &dyn AudioNode,
     destination: crate::rune::runtime::audio::AudioNode,
-) {
-    let audio_node: &dyn web_audio_api::node::AudioNode = {
-        match destination {
-            crate::rune::runtime::audio::AudioNode::Analyzer(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::BiquadFilter(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::BufferSource(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::Destination(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::ConstantSource(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::Convolver(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::ChannelMerger(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::ChannelSplitter(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::Delay(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::DynamicsCompressor(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::Gain(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::Oscillator(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::Panner(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::StereoPanner(d) => table.get(&d).unwrap(),
-            crate::rune::runtime::audio::AudioNode::WaveShaper(d) => table.get(&d).unwrap(),
+    output: Option<u32>,
+    input: Option<u32>,
+) -> core::result::Result<(), String> {
+    let destination_rep = audio_node_key(outputs, &destination);
+    let count = connections.len();
+    connections.retain(|&(from, from_output, to, to_input), _| {
+        from != source_rep
+            || to != destination_rep
+            || output.is_some_and(|output| output != from_output)
+            || input.is_some_and(|input| input != to_input)
+    });
+    if connections.len() == count {
+        return Err(match (output, input) {
+            (Some(output), Some(input)) => format!(
+                "The node's output {} isn't connected to input {} of that destination",
+                output, input
+            ),
+            (Some(output), None) => {
+                format!(
+                    "The node's output {} isn't connected to that destination",
+                    output
+                )
+            }
+            _ => "The node isn't connected to that destination".to_owned(),
+        });
+    }
+
+    let node = audio_node(table, outputs, &destination);
+    match (output, input) {
+        (Some(output), Some(input)) => {
+            source.disconnect_dest_from_output_to_input(node, output as usize, input as usize)
         }
-    };
-    source.connect(audio_node);
+        (Some(output), None) => source.disconnect_dest_from_output(node, output as usize),
+        (None, _) => source.disconnect_dest(node),
+    }
+    Ok(())
+}
+
+/// Disconnects everything from one of a node's outputs, which web_audio_api panics on if it
+/// doesn't have that output.
+pub(super) fn audio_node_disconnect_output(
+    connections: &mut AudioConnections,
+    source_rep: u32,
+    source: &dyn AudioNode,
+    output: u32,
+) -> core::result::Result<(), String> {
+    if output as usize >= source.number_of_outputs() {
+        return Err(format!(
+            "The node has {} outputs, so there's no output {}",
+            source.number_of_outputs(),
+            output
+        ));
+    }
+    connections.retain(|&(from, from_output, ..), _| from != source_rep || from_output != output);
+    source.disconnect_output(output as usize);
+    Ok(())
+}
+
+/// The table index a destination's connections are kept under. A context's destination can be
+/// asked for more than once, so connections to one that goes through the context's output are
+/// kept under the context.
+fn audio_node_key(
+    outputs: &HashMap<u32, AudioOutput>,
+    node: &crate::rune::runtime::audio::AudioNode,
+) -> u32 {
+    use crate::rune::runtime::audio::AudioNode as Node;
+    match node {
+        Node::Analyzer(d) => d.rep(),
+        Node::BiquadFilter(d) => d.rep(),
+        Node::BufferSource(d) => d.rep(),
+        Node::Destination(d) => outputs
+            .iter()
+            .find(|(_, output)| output.destinations.contains(&d.rep()))
+            .map_or(d.rep(), |(&context, _)| context),
+        Node::ConstantSource(d) => d.rep(),
+        Node::Convolver(d) => d.rep(),
+        Node::ChannelMerger(d) => d.rep(),
+        Node::ChannelSplitter(d) => d.rep(),
+        Node::Delay(d) => d.rep(),
+        Node::DynamicsCompressor(d) => d.rep(),
+        Node::Gain(d) => d.rep(),
+        Node::Oscillator(d) => d.rep(),
+        Node::Panner(d) => d.rep(),
+        Node::StereoPanner(d) => d.rep(),
+        Node::WaveShaper(d) => d.rep(),
+        Node::AudioWorklet(d) => d.rep(),
+        Node::MediaStreamSource(d) => d.rep(),
+        Node::StreamSource(d) => d.rep(),
+    }
+}
+
+/// The node a guest's audio node refers to.
+pub(super) fn audio_node<'a>(
+    table: &'a ResourceTable,
+    outputs: &'a HashMap<u32, AudioOutput>,
+    node: &crate::rune::runtime::audio::AudioNode,
+) -> &'a dyn AudioNode {
+    match node {
+        crate::rune::runtime::audio::AudioNode::Analyzer(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::BiquadFilter(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::BufferSource(d) => table.get(d).unwrap(),
+        // Audio reaches the destination through the context's output, so it can be recorded.
+        crate::rune::runtime::audio::AudioNode::Destination(d) => match outputs
+            .values()
+            .find(|output| output.destinations.contains(&d.rep()))
+        {
+            Some(output) => &output.tap,
+            None => table.get(d).unwrap(),
+        },
+        crate::rune::runtime::audio::AudioNode::ConstantSource(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::Convolver(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::ChannelMerger(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::ChannelSplitter(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::Delay(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::DynamicsCompressor(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::Gain(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::Oscillator(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::Panner(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::StereoPanner(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::WaveShaper(d) => table.get(d).unwrap(),
+        crate::rune::runtime::audio::AudioNode::AudioWorklet(d) => &table.get(d).unwrap().node,
+        crate::rune::runtime::audio::AudioNode::MediaStreamSource(d) => &table.get(d).unwrap().node,
+        crate::rune::runtime::audio::AudioNode::StreamSource(d) => &table.get(d).unwrap().node,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pollster::block_on;
+    use uuid::Uuid;
+    use winit::dpi::PhysicalSize;
+
+    use super::super::audio_devices::AudioDevices;
+    use super::super::gpu_backend::{Gpu, NullGpu};
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.;
+
+    /// A state with no GPU or audio devices, which is all offline contexts need.
+    fn state() -> RuneRuntimeState {
+        let mut gpu = NullGpu::new();
+        let (surface, adapter, device, queue) = (
+            gpu.create_id(),
+            gpu.create_id(),
+            gpu.create_id(),
+            gpu.create_id(),
+        );
+        RuneRuntimeState::new(
+            Uuid::nil(),
+            std::env::temp_dir(),
+            PhysicalSize::new(1, 1),
+            AudioDevices::silent(),
+            Gpu::Null(gpu),
+            surface,
+            adapter,
+            device,
+            queue,
+            None,
+            None,
+        )
+    }
+
+    /// A mono offline context a tenth of a second long.
+    fn offline_context(state: &mut RuneRuntimeState) -> Resource<OfflineAudioContext> {
+        block_on(Host::create_offline_context(state, 1, 4410, SAMPLE_RATE)).unwrap()
+    }
+
+    fn borrow<T: 'static>(resource: &Resource<T>) -> Resource<T> {
+        Resource::new_borrow(resource.rep())
+    }
+
+    #[test]
+    fn sources_start_and_stop_once() {
+        let mut state = state();
+        let context = offline_context(&mut state);
+        let oscillator = block_on(HostOfflineAudioContext::create_oscillator(
+            &mut state,
+            borrow(&context),
+        ));
+
+        assert_eq!(
+            block_on(HostOscillatorNode::stop(&mut state, borrow(&oscillator))),
+            Err("The node has to be started before it's stopped".to_owned())
+        );
+        assert_eq!(
+            block_on(HostOscillatorNode::start(&mut state, borrow(&oscillator))),
+            Ok(())
+        );
+        assert_eq!(
+            block_on(HostOscillatorNode::start_at(
+                &mut state,
+                borrow(&oscillator),
+                1.
+            )),
+            Err("The node has already been started, and can only be started once".to_owned())
+        );
+        assert_eq!(
+            block_on(HostOscillatorNode::stop(&mut state, borrow(&oscillator))),
+            Ok(())
+        );
+        assert_eq!(
+            block_on(HostOscillatorNode::stop_at(
+                &mut state,
+                borrow(&oscillator),
+                1.
+            )),
+            Err("The node has already been stopped".to_owned())
+        );
+        // A stopped node can't be started again either.
+        assert!(block_on(HostOscillatorNode::start(&mut state, borrow(&oscillator))).is_err());
+    }
+
+    #[test]
+    fn negative_times_are_rejected_without_starting() {
+        let mut state = state();
+        let context = offline_context(&mut state);
+        let source = block_on(HostOfflineAudioContext::create_constant_source(
+            &mut state,
+            borrow(&context),
+        ));
+
+        assert!(block_on(HostConstantSourceNode::start_at(
+            &mut state,
+            borrow(&source),
+            -1.
+        ))
+        .is_err());
+        assert!(block_on(HostConstantSourceNode::start_at(
+            &mut state,
+            borrow(&source),
+            f32::NAN
+        ))
+        .is_err());
+        assert_eq!(
+            block_on(HostConstantSourceNode::start(&mut state, borrow(&source))),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn sources_end_once_they_stop_playing() {
+        let mut state = state();
+        let context = offline_context(&mut state);
+        let destination = block_on(HostOfflineAudioContext::destination(
+            &mut state,
+            borrow(&context),
+        ));
+        let oscillator = block_on(HostOfflineAudioContext::create_oscillator(
+            &mut state,
+            borrow(&context),
+        ));
+        block_on(HostOscillatorNode::connect(
+            &mut state,
+            borrow(&oscillator),
+            crate::rune::runtime::audio::AudioNode::Destination(borrow(&destination)),
+        ))
+        .unwrap();
+        block_on(HostOscillatorNode::start(&mut state, borrow(&oscillator))).unwrap();
+        block_on(HostOscillatorNode::stop_at(
+            &mut state,
+            borrow(&oscillator),
+            0.01,
+        ))
+        .unwrap();
+        assert!(!block_on(HostOscillatorNode::ended(
+            &mut state,
+            borrow(&oscillator)
+        )));
+
+        block_on(HostOfflineAudioContext::start_rendering(
+            &mut state,
+            borrow(&context),
+        ))
+        .unwrap();
+        assert!(block_on(HostOscillatorNode::ended(
+            &mut state,
+            borrow(&oscillator)
+        )));
+    }
+
+    #[test]
+    fn outputs_are_connected_and_disconnected_separately() {
+        let mut state = state();
+        let context = offline_context(&mut state);
+        let splitter = block_on(HostOfflineAudioContext::create_channel_splitter(
+            &mut state,
+            borrow(&context),
+            2,
+        ));
+        let gain = block_on(HostOfflineAudioContext::create_gain(
+            &mut state,
+            borrow(&context),
+        ));
+        let to_gain =
+            || crate::rune::runtime::audio::AudioNode::Gain(Resource::new_borrow(gain.rep()));
+
+        assert!(
+            block_on(HostChannelSplitterNode::connect_from_output_to_input(
+                &mut state,
+                borrow(&splitter),
+                to_gain(),
+                2,
+                0
+            ))
+            .is_err()
+        );
+        assert!(
+            block_on(HostChannelSplitterNode::connect_from_output_to_input(
+                &mut state,
+                borrow(&splitter),
+                to_gain(),
+                0,
+                1
+            ))
+            .is_err()
+        );
+        block_on(HostChannelSplitterNode::connect_from_output_to_input(
+            &mut state,
+            borrow(&splitter),
+            to_gain(),
+            1,
+            0,
+        ))
+        .unwrap();
+
+        assert!(
+            block_on(HostChannelSplitterNode::disconnect_dest_from_output(
+                &mut state,
+                borrow(&splitter),
+                to_gain(),
+                0
+            ))
+            .is_err()
+        );
+        assert!(block_on(
+            HostChannelSplitterNode::disconnect_dest_from_output_to_input(
+                &mut state,
+                borrow(&splitter),
+                to_gain(),
+                0,
+                0
+            )
+        )
+        .is_err());
+        block_on(
+            HostChannelSplitterNode::disconnect_dest_from_output_to_input(
+                &mut state,
+                borrow(&splitter),
+                to_gain(),
+                1,
+                0,
+            ),
+        )
+        .unwrap();
+        assert!(block_on(HostChannelSplitterNode::disconnect_from(
+            &mut state,
+            borrow(&splitter),
+            to_gain()
+        ))
+        .is_err());
+
+        block_on(HostChannelSplitterNode::connect_from_output_to_input(
+            &mut state,
+            borrow(&splitter),
+            to_gain(),
+            0,
+            0,
+        ))
+        .unwrap();
+        block_on(HostChannelSplitterNode::connect_from_output_to_input(
+            &mut state,
+            borrow(&splitter),
+            to_gain(),
+            1,
+            0,
+        ))
+        .unwrap();
+        assert!(block_on(HostChannelSplitterNode::disconnect_output(
+            &mut state,
+            borrow(&splitter),
+            2
+        ))
+        .is_err());
+        block_on(HostChannelSplitterNode::disconnect_output(
+            &mut state,
+            borrow(&splitter),
+            0,
+        ))
+        .unwrap();
+        // Output 1 is still connected.
+        block_on(HostChannelSplitterNode::disconnect_dest_from_output(
+            &mut state,
+            borrow(&splitter),
+            to_gain(),
+            1,
+        ))
+        .unwrap();
+        assert!(block_on(HostChannelSplitterNode::disconnect_from(
+            &mut state,
+            borrow(&splitter),
+            to_gain()
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn offline_contexts_render_their_graph() {
+        let mut state = state();
+        let context = offline_context(&mut state);
+        let destination = block_on(HostOfflineAudioContext::destination(
+            &mut state,
+            borrow(&context),
+        ));
+        let oscillator = block_on(HostOfflineAudioContext::create_oscillator(
+            &mut state,
+            borrow(&context),
+        ));
+        let gain = block_on(HostOfflineAudioContext::create_gain(
+            &mut state,
+            borrow(&context),
+        ));
+        let gain_param = block_on(HostGainNode::gain(&mut state, borrow(&gain)));
+        block_on(HostAudioParam::set_value(&mut state, gain_param, 0.5));
+        block_on(HostOscillatorNode::connect(
+            &mut state,
+            borrow(&oscillator),
+            crate::rune::runtime::audio::AudioNode::Gain(borrow(&gain)),
+        ))
+        .unwrap();
+        block_on(HostGainNode::connect(
+            &mut state,
+            borrow(&gain),
+            crate::rune::runtime::audio::AudioNode::Destination(borrow(&destination)),
+        ))
+        .unwrap();
+        block_on(HostOscillatorNode::start(&mut state, borrow(&oscillator))).unwrap();
+
+        let rendered = block_on(HostOfflineAudioContext::start_rendering(
+            &mut state,
+            borrow(&context),
+        ))
+        .unwrap();
+        assert_eq!(
+            block_on(HostAudioBuffer::length(&mut state, borrow(&rendered))),
+            4410
+        );
+        assert_eq!(
+            block_on(HostAudioBuffer::number_of_channels(
+                &mut state,
+                borrow(&rendered)
+            )),
+            1
+        );
+        let samples = block_on(HostAudioBuffer::get_channel_data(
+            &mut state,
+            borrow(&rendered),
+            0,
+        ));
+        assert_eq!(samples.len(), 4410);
+        // A full-scale sine wave through a gain of a half peaks at a half.
+        let peak = samples
+            .iter()
+            .fold(0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((peak - 0.5).abs() < 0.01, "{}", peak);
+
+        // A context can only be rendered once.
+        assert!(block_on(HostOfflineAudioContext::start_rendering(
+            &mut state,
+            borrow(&context),
+        ))
+        .is_err());
+    }
 }
@@ -0,0 +1,154 @@
+use std::{
+    io::{Cursor, Read},
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use wasmtime::component::Resource;
+use wasmtime::Result;
+use web_audio_api::context::{BaseAudioContext, OfflineAudioContext};
+use web_audio_api::AudioBuffer;
+
+use crate::rune::runtime::audio::{
+    HostPendingAudioBuffer, PendingAudioBuffer as PendingAudioBufferResource,
+};
+
+use super::state::RuneRuntimeState;
+
+/// The most decodes that run at once, however many the game starts.
+const MAX_WORKERS: usize = 4;
+
+/// Worker threads that decode audio data for the game while it keeps running.
+pub struct AudioDecoder {
+    jobs: Sender<Job>,
+}
+
+struct Job {
+    data: Vec<u8>,
+    sample_rate: f32,
+    decode: Arc<Decode>,
+}
+
+/// An audio buffer that's being decoded on a worker thread.
+pub struct PendingAudioBuffer {
+    decode: Arc<Decode>,
+}
+
+struct Decode {
+    /// How many bytes of the data the decoder has read.
+    read: AtomicU64,
+    length: u64,
+    result: Mutex<Option<core::result::Result<AudioBuffer, String>>>,
+}
+
+impl AudioDecoder {
+    fn new() -> AudioDecoder {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .min(MAX_WORKERS);
+        for _ in 0..workers {
+            let receiver = receiver.clone();
+            thread::spawn(move || decode_jobs(&receiver));
+        }
+        AudioDecoder { jobs }
+    }
+}
+
+fn decode_jobs(jobs: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match jobs.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        // Decoding resamples to the sample rate of the context, which is all a context is needed for.
+        let context = OfflineAudioContext::new(1, 1, job.sample_rate);
+        let reader = ProgressReader {
+            data: Cursor::new(job.data),
+            decode: job.decode.clone(),
+        };
+        // A decoder that panics on malformed data mustn't take the worker with it, or leave the
+        // game waiting for a result that never comes.
+        let result =
+            panic::catch_unwind(AssertUnwindSafe(|| context.decode_audio_data_sync(reader)))
+                .unwrap_or_else(|_| Err("the decoder panicked".into()))
+                .map_err(|err| format!("Couldn't decode the audio data: {}", err));
+        *job.decode.result.lock().unwrap() = Some(result);
+    }
+}
+
+/// Counts how much of the data the decoder has read, so the game can show its progress.
+struct ProgressReader {
+    data: Cursor<Vec<u8>>,
+    decode: Arc<Decode>,
+}
+
+impl Read for ProgressReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.data.read(buf)?;
+        self.decode
+            .read
+            .store(self.data.position(), Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl RuneRuntimeState {
+    /// Queues audio data to be decoded at a context's sample rate on a worker thread.
+    pub(super) fn start_decode_audio_data<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        data: Vec<u8>,
+    ) -> Resource<PendingAudioBufferResource> {
+        let sample_rate = self.table.get(&audio_context).unwrap().sample_rate();
+        let decode = Arc::new(Decode {
+            read: AtomicU64::new(0),
+            length: data.len() as u64,
+            result: Mutex::new(None),
+        });
+        let audio_decoder = self.audio_decoder.get_or_insert_with(AudioDecoder::new);
+        let _ = audio_decoder.jobs.send(Job {
+            data,
+            sample_rate,
+            decode: decode.clone(),
+        });
+        self.table.push(PendingAudioBuffer { decode }).unwrap()
+    }
+}
+
+impl HostPendingAudioBuffer for RuneRuntimeState {
+    async fn progress(&mut self, pending: Resource<PendingAudioBuffer>) -> f32 {
+        let pending = self.table.get(&pending).unwrap();
+        let decode = &pending.decode;
+        if decode.result.lock().unwrap().is_some() || decode.length == 0 {
+            return 1.;
+        }
+        (decode.read.load(Ordering::Relaxed) as f64 / decode.length as f64).min(1.) as f32
+    }
+
+    async fn finished(&mut self, pending: Resource<PendingAudioBuffer>) -> bool {
+        let pending = self.table.get(&pending).unwrap();
+        pending.decode.result.lock().unwrap().is_some()
+    }
+
+    async fn get(
+        &mut self,
+        pending: Resource<PendingAudioBuffer>,
+    ) -> Option<core::result::Result<Resource<AudioBuffer>, String>> {
+        let pending = self.table.get(&pending).unwrap();
+        let result = pending.decode.result.lock().unwrap().clone()?;
+        Some(result.map(|audio_buffer| self.table.push(audio_buffer).unwrap()))
+    }
+
+    async fn drop(&mut self, rep: Resource<PendingAudioBuffer>) -> Result<()> {
+        self.table.delete(rep)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use wasmtime::component::Resource;
+use web_audio_api::context::{AudioContext, AudioContextState, BaseAudioContext};
+use web_audio_api::media_devices::{enumerate_devices_sync, MediaDeviceInfo, MediaDeviceInfoKind};
+
+use crate::rune::runtime::audio::AudioDeviceEvent;
+use crate::runtime::audio::AudioDevice;
+
+use super::debug;
+use super::state::RuneRuntimeState;
+
+/// How often the devices are checked for changes, since cpal doesn't report them.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// How many device events are kept for a game that never asks for them.
+const MAX_PENDING_EVENTS: usize = 64;
+/// The web_audio_api sink id of the silent sink.
+const NULL_SINK_ID: &str = "none";
+
+/// Keeps track of the audio devices coming and going.
+pub struct AudioDevices {
+    events: Receiver<AudioDeviceEvent>,
+    /// Events the game hasn't asked for yet.
+    pub pending: Vec<AudioDeviceEvent>,
+    /// The devices as of the last check, so asking for them never waits on the audio host.
+    devices: Arc<Mutex<DeviceLists>>,
+}
+
+#[derive(Default)]
+struct DeviceLists {
+    outputs: Vec<AudioDevice>,
+    inputs: Vec<AudioDevice>,
+}
+
+impl AudioDevices {
+    /// Lists the devices and keeps checking them on a background thread, since listing them
+    /// can be slow.
+    pub fn watch() -> AudioDevices {
+        let (sender, events) = mpsc::channel();
+        let devices = Arc::new(Mutex::new(list_devices()));
+        let watched = devices.clone();
+        thread::spawn(move || loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = list_devices();
+            let changes = {
+                let mut devices = watched.lock().unwrap();
+                let changes = output_changes(&devices.outputs, &current.outputs);
+                *devices = current;
+                changes
+            };
+
+            for change in changes {
+                if sender.send(change).is_err() {
+                    return;
+                }
+            }
+        });
+
+        AudioDevices {
+            events,
+            pending: Vec::new(),
+            devices,
+        }
+    }
+
+    /// Only offers the silent sink, without asking cpal for devices, for running where there's
+    /// no audio hardware.
+    pub fn silent() -> AudioDevices {
+        let (_, events) = mpsc::channel();
+        AudioDevices {
+            events,
+            pending: Vec::new(),
+            devices: Arc::default(),
+        }
+    }
+
+    /// The audio output devices.
+    pub fn outputs(&self) -> Vec<AudioDevice> {
+        self.devices.lock().unwrap().outputs.clone()
+    }
+
+    /// The audio input devices.
+    pub fn inputs(&self) -> Vec<AudioDevice> {
+        self.devices.lock().unwrap().inputs.clone()
+    }
+
+    /// Whether an output device with this id is connected.
+    pub fn has_output(&self, id: &str) -> bool {
+        self.devices
+            .lock()
+            .unwrap()
+            .outputs
+            .iter()
+            .any(|device| device.id == id)
+    }
+
+    /// Whether an input device with this id is connected.
+    pub fn has_input(&self, id: &str) -> bool {
+        self.devices
+            .lock()
+            .unwrap()
+            .inputs
+            .iter()
+            .any(|device| device.id == id)
+    }
+
+    /// The system's default output device, or the silent sink when there isn't one.
+    pub fn default_output(&self) -> AudioDevice {
+        let devices = self.devices.lock().unwrap();
+        devices
+            .outputs
+            .iter()
+            .find(|device| device.is_default)
+            .or(devices.outputs.first())
+            .cloned()
+            .unwrap_or_else(AudioDevice::null)
+    }
+
+    /// Returns the sink id to play to for a device, falling back to the default device, or the
+    /// silent sink, when it's gone.
+    pub fn available_sink_id(&self, id: &str) -> String {
+        if id == NULL_SINK_ID || self.has_output(id) {
+            return id.to_owned();
+        }
+        let fallback = self.default_output();
+        debug::warn(&format!(
+            "The audio device {} isn't connected, so {} is used instead",
+            id, fallback.name
+        ));
+        fallback.id
+    }
+}
+
+/// The events for the output devices added, removed and made the default since the last check.
+fn output_changes(previous: &[AudioDevice], current: &[AudioDevice]) -> Vec<AudioDeviceEvent> {
+    let mut changes = Vec::new();
+    for device in current {
+        if !previous.iter().any(|previous| previous.id == device.id) {
+            changes.push(AudioDeviceEvent::Added(device.id.clone()));
+        }
+    }
+    for device in previous {
+        if !current.iter().any(|next| next.id == device.id) {
+            changes.push(AudioDeviceEvent::Removed(device.id.clone()));
+        }
+    }
+    let default_id = |devices: &[AudioDevice]| {
+        devices
+            .iter()
+            .find(|device| device.is_default)
+            .map(|device| device.id.clone())
+    };
+    if let Some(id) = default_id(current).filter(|id| Some(id) != default_id(previous).as_ref()) {
+        changes.push(AudioDeviceEvent::DefaultChanged(id));
+    }
+    changes
+}
+
+impl AudioDevice {
+    /// The silent sink, used when there's no output device.
+    pub fn null() -> AudioDevice {
+        AudioDevice {
+            id: NULL_SINK_ID.to_owned(),
+            name: "No output".to_owned(),
+            default_sample_rate: None,
+            is_default: true,
+            is_input: false,
+        }
+    }
+}
+
+/// Lists the audio devices, with web_audio_api's ids and cpal's names and default sample rates.
+fn list_devices() -> DeviceLists {
+    let host = cpal::default_host();
+    let infos = enumerate_devices_sync();
+    DeviceLists {
+        outputs: devices(
+            &infos,
+            MediaDeviceInfoKind::AudioOutput,
+            host.default_output_device(),
+            host.output_devices().map(Iterator::collect).unwrap_or_default(),
+        ),
+        inputs: devices(
+            &infos,
+            MediaDeviceInfoKind::AudioInput,
+            host.default_input_device(),
+            host.input_devices().map(Iterator::collect).unwrap_or_default(),
+        ),
+    }
+}
+
+/// Pairs web_audio_api's devices of a kind with cpal's, which it lists in the same order, so
+/// each id gets its own device's sample rate even when several devices share a name.
+fn devices(
+    infos: &[MediaDeviceInfo],
+    kind: MediaDeviceInfoKind,
+    default: Option<cpal::Device>,
+    cpal_devices: Vec<cpal::Device>,
+) -> Vec<AudioDevice> {
+    let is_input = kind == MediaDeviceInfoKind::AudioInput;
+    let default_name = default.and_then(|device| device.name().ok());
+    let mut has_default = false;
+    infos
+        .iter()
+        .filter(|info| info.kind() == kind)
+        .zip(cpal_devices)
+        .map(|(info, device)| {
+            // A device that came or went between the two lists is paired with the wrong one,
+            // until the next check lists them again.
+            let paired = device.name().ok().as_deref() == Some(info.label());
+            let config = match is_input {
+                true => device.default_input_config(),
+                false => device.default_output_config(),
+            };
+            // cpal has no ids, so the default is the first device with its name, which is the
+            // one web_audio_api numbers first.
+            let is_default = !has_default && Some(info.label()) == default_name.as_deref();
+            has_default |= is_default;
+            AudioDevice {
+                id: info.device_id().to_owned(),
+                name: info.label().to_owned(),
+                default_sample_rate: config
+                    .ok()
+                    .filter(|_| paired)
+                    .map(|config| config.sample_rate().0),
+                is_default,
+                is_input,
+            }
+        })
+        .collect()
+}
+
+impl RuneRuntimeState {
+    /// Collects device changes for the game. Contexts whose device was disconnected move to the
+    /// default device, and contexts following the default move with it.
+    pub fn update_audio_devices(&mut self) {
+        let events = self.audio_devices.events.try_iter().collect::<Vec<_>>();
+        for event in events {
+            for (&rep, output) in self.audio_outputs.iter_mut() {
+                let audio_context = self
+                    .table
+                    .get(&Resource::<AudioContext>::new_borrow(rep))
+                    .unwrap();
+                if audio_context.state() == AudioContextState::Closed {
+                    continue;
+                }
+                let sink_id = match &event {
+                    AudioDeviceEvent::Removed(id) if audio_context.sink_id() == *id => {
+                        output.follows_default = true;
+                        self.audio_devices.available_sink_id(id)
+                    }
+                    AudioDeviceEvent::DefaultChanged(id)
+                        if output.follows_default && audio_context.sink_id() != *id =>
+                    {
+                        id.clone()
+                    }
+                    _ => continue,
+                };
+                if let Err(err) = audio_context.set_sink_id_sync(sink_id) {
+                    debug::warn(&format!(
+                        "Couldn't move the audio to another device: {}",
+                        err
+                    ));
+                }
+            }
+            self.audio_devices.pending.push(event);
+        }
+
+        let pending = &mut self.audio_devices.pending;
+        if pending.len() > MAX_PENDING_EVENTS {
+            pending.drain(..pending.len() - MAX_PENDING_EVENTS);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use wasmtime::component::Resource;
+use wasmtime::Result;
+use web_audio_api::context::{AudioContext, BaseAudioContext};
+use web_audio_api::media_devices::{
+    get_user_media_sync, MediaStreamConstraints, MediaTrackConstraints,
+};
+use web_audio_api::media_streams::MediaStream;
+use web_audio_api::node::{AudioNode, MediaStreamAudioSourceNode};
+
+use crate::rune::runtime::audio::{
+    HostMediaStreamAudioSourceNode,
+    MediaStreamAudioSourceNode as MediaStreamAudioSourceNodeResource,
+};
+use crate::runtime::audio::AudioDevice;
+
+use super::audio::{audio_node_connect, audio_node_disconnect, audio_node_disconnect_output};
+use super::state::RuneRuntimeState;
+
+/// A node that plays what an audio input, such as a microphone, hears.
+pub struct AudioInput {
+    pub node: MediaStreamAudioSourceNode,
+    stream: MediaStream,
+}
+
+impl Drop for AudioInput {
+    /// Stops listening as soon as the game is done with the node, rather than when the audio
+    /// render thread gets round to dropping it.
+    fn drop(&mut self) {
+        for track in self.stream.get_tracks() {
+            track.close();
+        }
+    }
+}
+
+impl RuneRuntimeState {
+    /// Checks that rune.toml lets the game listen to audio inputs.
+    pub(super) fn check_microphone_permission(&self) -> core::result::Result<(), String> {
+        if self.microphone {
+            Ok(())
+        } else {
+            Err("The game doesn't have permission to use audio inputs. Add `microphone = \"<why the game listens>\"` to [permissions] in rune.toml.".to_owned())
+        }
+    }
+
+    /// Opens an input device and feeds it into a context's node graph.
+    pub(super) fn create_media_stream_source(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        input: Resource<AudioDevice>,
+    ) -> core::result::Result<Resource<MediaStreamAudioSourceNodeResource>, String> {
+        self.check_microphone_permission()?;
+        let input = self.table.get(&input).unwrap();
+        if !input.is_input {
+            return Err(format!("{} isn't an audio input", input.name));
+        }
+        // web_audio_api panics when it can't open the device, so it's checked here first.
+        if !self.audio_devices.has_input(&input.id) {
+            return Err(format!("The audio input {} isn't connected", input.name));
+        }
+
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let mut constraints = MediaTrackConstraints::default();
+        constraints.device_id = Some(input.id.clone());
+        constraints.sample_rate = Some(audio_context.sample_rate());
+        let stream = get_user_media_sync(MediaStreamConstraints::AudioWithConstraints(constraints));
+        let node = audio_context.create_media_stream_source(&stream);
+
+        Ok(self.table.push(AudioInput { node, stream }).unwrap())
+    }
+}
+
+impl HostMediaStreamAudioSourceNode for RuneRuntimeState {
+    async fn connect(
+        &mut self,
+        node: Resource<AudioInput>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<AudioInput>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<AudioInput>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<AudioInput>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<AudioInput>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
+    }
+
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<AudioInput>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<AudioInput>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<AudioInput>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
+        self.table.delete(rep)?;
+        Ok(())
+    }
+}
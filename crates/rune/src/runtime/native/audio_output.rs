@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+use vfs::VfsPath;
+use web_audio_api::context::{AudioContext, BaseAudioContext};
+use web_audio_api::node::{AudioNode, AudioNodeOptions, ChannelCountMode, ChannelInterpretation};
+use web_audio_api::worklet::{
+    AudioParamValues, AudioWorkletGlobalScope, AudioWorkletNode, AudioWorkletNodeOptions,
+    AudioWorkletProcessor,
+};
+
+use super::debug;
+use super::state::RuneRuntimeState;
+
+/// The node a context's audio passes through on its way to the destination, so the runtime can
+/// record exactly what was played.
+pub struct AudioOutput {
+    /// Whatever the guest connects to the destination is connected here instead.
+    pub tap: AudioWorkletNode,
+    /// Table indices of the destination nodes handed to the guest for this context.
+    pub destinations: Vec<u32>,
+    /// Whether the context plays to whichever device is the default, rather than one the game
+    /// chose, so it moves when the default changes.
+    pub follows_default: bool,
+    shared: Arc<TapShared>,
+    /// The end of the tap's ring buffer that's read from, which the writer thread has while
+    /// recording.
+    samples: Option<Consumer<f32>>,
+    recording: Option<Recording>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+/// What the tap and the runtime share, which is only atomics so the tap never waits on a lock.
+struct TapShared {
+    recording: AtomicBool,
+    /// Whether the writer fell so far behind that the tap had to leave quanta out.
+    overflowed: AtomicBool,
+}
+
+struct Recording {
+    /// The file being written, which is a temporary one for recordings saved to storage.
+    path: PathBuf,
+    /// The storage path the game asked for the recording to be saved to, which the finished file
+    /// is copied to. Recordings started by `--record-audio` have none.
+    save_to: Option<VfsPath>,
+    stop: Arc<AtomicBool>,
+    writer: JoinHandle<(Consumer<f32>, hound::Result<()>)>,
+}
+
+/// How long the writer thread sleeps between draining the ring buffer.
+const WRITE_INTERVAL: Duration = Duration::from_millis(10);
+
+impl AudioOutput {
+    /// Creates the tap and connects it to the context's destination.
+    pub fn new(context: &AudioContext) -> AudioOutput {
+        let destination = context.destination();
+        let channels = destination.channel_count();
+        let shared = Arc::new(TapShared {
+            recording: AtomicBool::new(false),
+            overflowed: AtomicBool::new(false),
+        });
+        // Made once for the life of the tap, so the audio thread never allocates or frees it. A
+        // second of audio leaves the writer thread plenty of room.
+        let (producer, consumer) =
+            RingBuffer::new(context.sample_rate() as usize * channels.max(1));
+        let tap = AudioWorkletNode::new::<Tap>(
+            context,
+            AudioWorkletNodeOptions {
+                number_of_inputs: 1,
+                number_of_outputs: 1,
+                output_channel_count: vec![channels],
+                parameter_data: HashMap::new(),
+                processor_options: TapOptions {
+                    samples: producer,
+                    shared: shared.clone(),
+                },
+                audio_node_options: AudioNodeOptions {
+                    channel_count: channels,
+                    channel_count_mode: ChannelCountMode::Explicit,
+                    channel_interpretation: ChannelInterpretation::Speakers,
+                },
+            },
+        );
+        tap.connect(&destination);
+
+        AudioOutput {
+            tap,
+            destinations: Vec::new(),
+            follows_default: false,
+            shared,
+            samples: Some(consumer),
+            recording: None,
+            channels: channels as u16,
+            sample_rate: context.sample_rate() as u32,
+        }
+    }
+
+    /// Writes everything the context plays from the next render quantum on to a 32-bit float WAV
+    /// file, replacing any recording in progress.
+    pub fn start_recording(&mut self, path: &Path) -> Result<(), String> {
+        self.record(path, None)
+    }
+
+    /// Records like `start_recording`, saving the finished file to a path in one of the game's
+    /// storage devices. Storage files can't be seeked while writing, which finishing a WAV file
+    /// needs, so the recording is written to a temporary file first.
+    pub fn start_recording_to(&mut self, path: VfsPath) -> Result<(), String> {
+        let temporary =
+            std::env::temp_dir().join(format!("rune-recording-{}.wav", uuid::Uuid::new_v4()));
+        self.record(&temporary, Some(path))
+    }
+
+    /// Whether a recording started by `--record-audio` is in progress, which the game can't stop
+    /// or replace.
+    pub fn is_recording_for_cli(&self) -> bool {
+        self.recording
+            .as_ref()
+            .is_some_and(|recording| recording.save_to.is_none())
+    }
+
+    fn record(&mut self, path: &Path, save_to: Option<VfsPath>) -> Result<(), String> {
+        self.stop_recording();
+        if self.samples.is_none() {
+            return Err("Audio can't be recorded again after a recording failed".to_owned());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Couldn't create {}: {}", parent.display(), err))?;
+        }
+        let spec = hound::WavSpec {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(path, spec)
+            .map_err(|err| format!("Couldn't create {}: {}", path.display(), err))?;
+
+        let mut samples = self.samples.take().unwrap();
+        // Quanta left over from the last recording aren't part of this one.
+        if let Ok(stale) = samples.read_chunk(samples.slots()) {
+            stale.commit_all();
+        }
+        self.shared.overflowed.store(false, Ordering::Relaxed);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let result = (|| {
+                    loop {
+                        // Checked before draining, so everything written before the stop is kept.
+                        let stopping = stop.load(Ordering::Acquire);
+                        let chunk = samples.read_chunk(samples.slots()).unwrap();
+                        let (first, second) = chunk.as_slices();
+                        for sample in first.iter().chain(second) {
+                            writer.write_sample(*sample)?;
+                        }
+                        chunk.commit_all();
+                        if stopping {
+                            break;
+                        }
+                        thread::sleep(WRITE_INTERVAL);
+                    }
+                    writer.finalize()
+                })();
+                (samples, result)
+            }
+        });
+
+        self.shared.recording.store(true, Ordering::Release);
+        self.recording = Some(Recording {
+            path: path.to_path_buf(),
+            save_to,
+            stop,
+            writer,
+        });
+        Ok(())
+    }
+
+    /// Stops the recording, if there is one, and waits for its file to be finished.
+    pub fn stop_recording(&mut self) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        self.shared.recording.store(false, Ordering::Release);
+        recording.stop.store(true, Ordering::Release);
+        let name = match &recording.save_to {
+            Some(path) => path.as_str().to_owned(),
+            None => recording.path.display().to_string(),
+        };
+        let result = match recording.writer.join() {
+            Ok((samples, result)) => {
+                self.samples = Some(samples);
+                result.map_err(|err| err.to_string())
+            }
+            Err(_) => Err("its writer thread panicked".to_owned()),
+        };
+        if self.shared.overflowed.load(Ordering::Relaxed) {
+            debug::warn(&format!(
+                "The audio recording {} skips some audio, since it couldn't be written quickly enough",
+                name
+            ));
+        }
+        let result = match &recording.save_to {
+            Some(save_to) => {
+                let saved = result.and_then(|()| {
+                    copy_to_storage(&recording.path, save_to).map_err(|err| err.to_string())
+                });
+                let _ = fs::remove_file(&recording.path);
+                saved
+            }
+            None => result,
+        };
+        match result {
+            Ok(()) => debug::log(&format!("Saved audio recording to {}", name)),
+            Err(err) => debug::warn(&format!(
+                "Couldn't write the audio recording {}: {}",
+                name, err
+            )),
+        }
+    }
+}
+
+/// Copies a finished recording into storage, replacing any file already there.
+fn copy_to_storage(path: &Path, save_to: &VfsPath) -> vfs::VfsResult<()> {
+    save_to.parent().create_dir_all()?;
+    let mut file = save_to.create_file()?;
+    io::copy(&mut fs::File::open(path)?, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+impl RuneRuntimeState {
+    /// Finishes every audio recording, so the files are complete when the game exits.
+    pub fn stop_audio_recordings(&mut self) {
+        for output in self.audio_outputs.values_mut() {
+            output.stop_recording();
+        }
+    }
+}
+
+/// Passes its input through unchanged, copying it into the ring buffer while recording.
+struct Tap {
+    samples: Producer<f32>,
+    shared: Arc<TapShared>,
+}
+
+struct TapOptions {
+    samples: Producer<f32>,
+    shared: Arc<TapShared>,
+}
+
+impl AudioWorkletProcessor for Tap {
+    type ProcessorOptions = TapOptions;
+
+    fn constructor(options: Self::ProcessorOptions) -> Self {
+        Tap {
+            samples: options.samples,
+            shared: options.shared,
+        }
+    }
+
+    fn process<'a, 'b>(
+        &mut self,
+        inputs: &'b [&'a [&'a [f32]]],
+        outputs: &'b mut [&'a mut [&'a mut [f32]]],
+        _params: AudioParamValues<'b>,
+        _scope: &'b AudioWorkletGlobalScope,
+    ) -> bool {
+        let input = inputs[0];
+        let output = &mut *outputs[0];
+        for (channel, output_channel) in output.iter_mut().enumerate() {
+            // Silent inputs arrive as a single channel.
+            match input
+                .get(channel)
+                .or(input.first().filter(|_| input.len() == 1))
+            {
+                Some(input_channel) => output_channel.copy_from_slice(input_channel),
+                None => output_channel.fill(0.),
+            }
+        }
+
+        if self.shared.recording.load(Ordering::Acquire) {
+            let frames = output.first().map_or(0, |channel| channel.len());
+            // A whole quantum is committed at once, so the writer never sees part of a frame.
+            match self.samples.write_chunk_uninit(frames * output.len()) {
+                Ok(chunk) => {
+                    let output = &*output;
+                    chunk.fill_from_iter(
+                        (0..frames)
+                            .flat_map(|frame| output.iter().map(move |channel| channel[frame])),
+                    );
+                }
+                Err(_) => self.shared.overflowed.store(true, Ordering::Relaxed),
+            }
+        }
+
+        true
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use wasmtime::component::Resource;
+use wasmtime_wasi::ResourceTable;
+use web_audio_api::context::BaseAudioContext;
+use web_audio_api::node::{AudioNode, DelayNode, DelayOptions, PannerNode};
+
+use crate::rune::runtime::audio::Vec3;
+
+use super::audio::audio_node;
+use super::state::RuneRuntimeState;
+
+/// The speed of sound in air, in meters per second, taking a unit of distance to be a meter.
+const SPEED_OF_SOUND: f32 = 343.;
+/// The longest delay the doppler effect adds, which is around 340 meters away.
+const MAX_DOPPLER_DELAY: f64 = 1.;
+/// How quickly the delay follows a move, which smooths out games that move things once a frame.
+const DOPPLER_TIME_CONSTANT: f64 = 0.05;
+
+/// A panner's doppler effect, which delays its output by how long the sound takes to reach the
+/// listener. As the distance changes, the changing delay shifts the pitch like a real doppler
+/// effect, so no velocities are needed.
+pub struct Doppler {
+    pub delay: DelayNode,
+    factor: f32,
+}
+
+impl RuneRuntimeState {
+    /// Moves and turns a context's listener, then updates the doppler effect of its panners.
+    pub(super) fn set_listener_transform<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        position: Vec3,
+        forward: Vec3,
+        up: Vec3,
+    ) {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let listener = audio_context.listener();
+        listener.position_x().set_value(position.x);
+        listener.position_y().set_value(position.y);
+        listener.position_z().set_value(position.z);
+        listener.forward_x().set_value(forward.x);
+        listener.forward_y().set_value(forward.y);
+        listener.forward_z().set_value(forward.z);
+        listener.up_x().set_value(up.x);
+        listener.up_y().set_value(up.y);
+        listener.up_z().set_value(up.z);
+
+        for (&rep, doppler) in &self.audio_dopplers {
+            let panner = self
+                .table
+                .get(&Resource::<PannerNode>::new_borrow(rep))
+                .unwrap();
+            if panner.context() == audio_context.base() {
+                let panner_position = Vec3 {
+                    x: panner.position_x().value(),
+                    y: panner.position_y().value(),
+                    z: panner.position_z().value(),
+                };
+                doppler.update(panner_position, position);
+            }
+        }
+    }
+
+    /// Moves and turns a panner, updating its doppler effect.
+    pub(super) fn set_panner_transform(
+        &mut self,
+        panner: Resource<PannerNode>,
+        position: Vec3,
+        orientation: Vec3,
+    ) {
+        let rep = panner.rep();
+        let panner = self.table.get_mut(&panner).unwrap();
+        panner.set_position(position.x, position.y, position.z);
+        panner.set_orientation(orientation.x, orientation.y, orientation.z);
+
+        if let Some(doppler) = self.audio_dopplers.get(&rep) {
+            let listener = panner.context().listener();
+            let listener_position = Vec3 {
+                x: listener.position_x().value(),
+                y: listener.position_y().value(),
+                z: listener.position_z().value(),
+            };
+            doppler.update(position, listener_position);
+        }
+    }
+
+    pub(super) fn doppler_factor(&self, panner: Resource<PannerNode>) -> f32 {
+        self.audio_dopplers
+            .get(&panner.rep())
+            .map_or(0., |doppler| doppler.factor)
+    }
+
+    /// Turns a panner's doppler effect on, off or up. Turning it on moves the nodes the panner is
+    /// connected to behind its delay, and turning it off moves them back.
+    pub(super) fn set_doppler_factor(&mut self, panner: Resource<PannerNode>, factor: f32) {
+        let rep = panner.rep();
+        let factor = factor.max(0.);
+        if let Some(doppler) = self.audio_dopplers.get_mut(&rep) {
+            if factor > 0. {
+                doppler.factor = factor;
+                return;
+            }
+        } else if factor == 0. {
+            return;
+        }
+
+        let panner = self.table.get(&panner).unwrap();
+        // Panners and delays have one output, so only the destination's input matters.
+        let destinations = self
+            .audio_connections
+            .iter()
+            .filter(|((source, ..), _)| *source == rep)
+            .map(|(&(.., input), destination)| {
+                (
+                    audio_node(&self.table, &self.audio_outputs, destination),
+                    input as usize,
+                )
+            });
+        if factor == 0. {
+            let doppler = self.audio_dopplers.remove(&rep).unwrap();
+            doppler.delay.disconnect();
+            panner.disconnect();
+            for (destination, input) in destinations {
+                panner.connect_from_output_to_input(destination, 0, input);
+            }
+        } else {
+            let delay = DelayNode::new(
+                panner.context(),
+                DelayOptions {
+                    max_delay_time: MAX_DOPPLER_DELAY,
+                    ..DelayOptions::default()
+                },
+            );
+            panner.disconnect();
+            panner.connect(&delay);
+            for (destination, input) in destinations {
+                delay.connect_from_output_to_input(destination, 0, input);
+            }
+            self.audio_dopplers.insert(rep, Doppler { delay, factor });
+        }
+    }
+}
+
+/// The node a panner's output comes from, which is its doppler delay when it has one.
+pub(super) fn panner_output<'a>(
+    table: &'a ResourceTable,
+    dopplers: &'a HashMap<u32, Doppler>,
+    panner: &Resource<PannerNode>,
+) -> &'a dyn AudioNode {
+    match dopplers.get(&panner.rep()) {
+        Some(doppler) => &doppler.delay,
+        None => table.get(panner).unwrap(),
+    }
+}
+
+impl Doppler {
+    fn update(&self, position: Vec3, listener_position: Vec3) {
+        let distance = ((position.x - listener_position.x).powi(2)
+            + (position.y - listener_position.y).powi(2)
+            + (position.z - listener_position.z).powi(2))
+        .sqrt();
+        let delay_time = (distance * self.factor / SPEED_OF_SOUND) as f64;
+        let now = self.delay.context().current_time();
+        self.delay.delay_time().set_target_at_time(
+            delay_time.min(MAX_DOPPLER_DELAY) as f32,
+            now,
+            DOPPLER_TIME_CONSTANT,
+        );
+    }
+}
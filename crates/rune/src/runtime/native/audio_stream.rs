@@ -0,0 +1,771 @@
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use vfs::SeekAndRead;
+use wasmtime::component::Resource;
+use wasmtime::Result;
+use web_audio_api::context::AudioContext;
+use web_audio_api::node::{AudioNode, AudioNodeOptions};
+use web_audio_api::worklet::{
+    AudioParamValues, AudioWorkletGlobalScope, AudioWorkletNode, AudioWorkletNodeOptions,
+    AudioWorkletProcessor,
+};
+
+use crate::rune::runtime::audio::{
+    AudioStreamSourceNode as AudioStreamSourceNodeResource, HostAudioStreamSourceNode,
+};
+use crate::rune::runtime::storage::Path;
+
+use super::audio::{audio_node_connect, audio_node_disconnect, audio_node_disconnect_output};
+use super::state::RuneRuntimeState;
+
+/// How many decoded packets are kept ready ahead of playback, which is around a second of audio
+/// for most formats.
+const AHEAD_PACKETS: usize = 64;
+/// How long the decoder waits before trying again when it's far enough ahead.
+const AHEAD_WAIT: Duration = Duration::from_millis(5);
+/// How many played packets can be on their way back to the decoder. This is more than can ever be
+/// in flight, so the render thread never has to free one itself.
+const RECYCLED_PACKETS: usize = AHEAD_PACKETS + 4;
+
+/// A node that plays an encoded audio file, decoding it a little ahead of playback on a background
+/// thread, so a long piece of music never needs to be in memory all at once.
+pub struct AudioStream {
+    pub node: AudioWorkletNode,
+    shared: Arc<Shared>,
+    /// The frame to seek to, and the generation the decoded packets belong to from then on.
+    seeks: Sender<(u64, u64)>,
+    sample_rate: f64,
+    /// The length of the file in frames, if it says.
+    length: Option<u64>,
+}
+
+/// What the game, the decoder and the audio render thread all see of a stream.
+struct Shared {
+    paused: AtomicBool,
+    ended: AtomicBool,
+    looping: AtomicBool,
+    /// In frames of the file.
+    loop_start: AtomicU64,
+    /// In frames of the file, where zero loops at the end of the file.
+    loop_end: AtomicU64,
+    /// The frame of the file being played.
+    position: AtomicU64,
+    /// Counts the game's seeks, so audio decoded before one is never played after it.
+    generation: AtomicU64,
+    error: Mutex<Option<String>>,
+}
+
+/// A decoded packet, or the end of the file when it has no samples.
+struct Packet {
+    generation: u64,
+    /// The frame of the file the packet starts at.
+    start: u64,
+    /// Interleaved samples.
+    samples: Vec<f32>,
+}
+
+impl RuneRuntimeState {
+    /// Streams a file from a storage device.
+    pub(super) fn create_stream_source(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        path: Resource<Path>,
+    ) -> core::result::Result<Resource<AudioStreamSourceNodeResource>, String> {
+        let path = self.paths.get(path.rep() as usize).unwrap();
+        let file = path
+            .open_file()
+            .map_err(|err| format!("Couldn't open {}: {}", path.as_str(), err))?;
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension() {
+            hint.with_extension(&extension);
+        }
+
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let stream = AudioStream::new(audio_context, Box::new(VfsSource::new(file)), hint)?;
+        Ok(self.table.push(stream).unwrap())
+    }
+
+    /// Streams a file the game has already read, which stays encoded in memory.
+    pub(super) fn create_stream_source_from_data(
+        &mut self,
+        audio_context: Resource<AudioContext>,
+        data: Vec<u8>,
+    ) -> core::result::Result<Resource<AudioStreamSourceNodeResource>, String> {
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let stream = AudioStream::new(audio_context, Box::new(Cursor::new(data)), Hint::new())?;
+        Ok(self.table.push(stream).unwrap())
+    }
+}
+
+impl AudioStream {
+    fn new(
+        context: &AudioContext,
+        source: Box<dyn MediaSource>,
+        hint: Hint,
+    ) -> core::result::Result<AudioStream, String> {
+        let source = MediaSourceStream::new(source, Default::default());
+        let probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|err| format!("Couldn't read the audio file: {}", err))?;
+        let format = probed.format;
+        let track = format
+            .default_track()
+            .ok_or("The audio file has no audio in it")?;
+        let params = &track.codec_params;
+        let (Some(sample_rate), Some(channels)) = (params.sample_rate, params.channels) else {
+            return Err("The audio file doesn't say how it should be played".to_owned());
+        };
+        let channels = channels.count();
+        let length = params.n_frames;
+        let track_id = track.id;
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .map_err(|err| format!("Couldn't decode the audio file: {}", err))?;
+
+        let shared = Arc::new(Shared {
+            paused: AtomicBool::new(true),
+            ended: AtomicBool::new(false),
+            looping: AtomicBool::new(false),
+            loop_start: AtomicU64::new(0),
+            loop_end: AtomicU64::new(0),
+            position: AtomicU64::new(0),
+            generation: AtomicU64::new(0),
+            error: Mutex::new(None),
+        });
+        let (packets, receiver) = mpsc::sync_channel(AHEAD_PACKETS);
+        let (seeks, seek_receiver) = mpsc::channel();
+        let (recycled, recycled_receiver) = mpsc::sync_channel(RECYCLED_PACKETS);
+        thread::spawn({
+            let mut stream_decoder = StreamDecoder {
+                format,
+                decoder,
+                track_id,
+                channels,
+                shared: shared.clone(),
+                packets,
+                seeks: seek_receiver,
+                recycled: recycled_receiver,
+                generation: 0,
+                skip_to: 0,
+            };
+            move || stream_decoder.run()
+        });
+
+        let node = AudioWorkletNode::new::<StreamProcessor>(
+            context,
+            AudioWorkletNodeOptions {
+                number_of_inputs: 0,
+                number_of_outputs: 1,
+                output_channel_count: vec![channels],
+                parameter_data: HashMap::new(),
+                processor_options: ProcessorOptions {
+                    packets: receiver,
+                    recycled,
+                    shared: shared.clone(),
+                    channels,
+                    sample_rate: sample_rate as f64,
+                },
+                audio_node_options: AudioNodeOptions::default(),
+            },
+        );
+
+        Ok(AudioStream {
+            node,
+            shared,
+            seeks,
+            sample_rate: sample_rate as f64,
+            length,
+        })
+    }
+
+    fn seconds(&self, frames: u64) -> f32 {
+        (frames as f64 / self.sample_rate) as f32
+    }
+
+    fn frames(&self, seconds: f32) -> u64 {
+        (seconds.max(0.) as f64 * self.sample_rate).round() as u64
+    }
+}
+
+struct StreamDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    channels: usize,
+    shared: Arc<Shared>,
+    packets: SyncSender<Packet>,
+    seeks: Receiver<(u64, u64)>,
+    /// Sample buffers of played packets, which are reused so the render thread never frees them.
+    recycled: Receiver<Vec<f32>>,
+    generation: u64,
+    /// Decoded frames before this one are dropped, since seeks land on the packet before.
+    skip_to: u64,
+}
+
+impl StreamDecoder {
+    /// Decodes until the node is dropped, staying a little ahead of playback.
+    fn run(&mut self) {
+        let mut pending = None;
+        loop {
+            // Only the latest of several quick seeks matters.
+            let mut seek = None;
+            loop {
+                match self.seeks.try_recv() {
+                    Ok(next) => seek = Some(next),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+            if let Some((frame, generation)) = seek {
+                self.generation = generation;
+                pending = None;
+                if let Err(err) = self.seek(frame) {
+                    return self.fail(err);
+                }
+            }
+
+            let packet = match pending.take() {
+                Some(packet) => packet,
+                None => match self.next_packet() {
+                    Ok(Some(packet)) => packet,
+                    Ok(None) => continue,
+                    Err(err) => return self.fail(err),
+                },
+            };
+            let end = packet.samples.is_empty();
+            match self.packets.try_send(packet) {
+                Ok(()) => {}
+                Err(TrySendError::Full(packet)) => {
+                    pending = Some(packet);
+                    thread::sleep(AHEAD_WAIT);
+                }
+                Err(TrySendError::Disconnected(_)) => return,
+            }
+
+            // Nothing more is decoded until the game seeks somewhere else.
+            if end && pending.is_none() {
+                match self.seeks.recv() {
+                    Ok((frame, generation)) => {
+                        self.generation = generation;
+                        if let Err(err) = self.seek(frame) {
+                            return self.fail(err);
+                        }
+                    }
+                    Err(_) => return,
+                }
+            }
+        }
+    }
+
+    /// Decodes the next packet of the track, looping as the game asked. Returns None for packets
+    /// that are skipped.
+    fn next_packet(&mut self) -> core::result::Result<Option<Packet>, String> {
+        let looping = self.shared.looping.load(Ordering::Acquire);
+        let loop_start = self.shared.loop_start.load(Ordering::Acquire);
+        let loop_end = self.shared.loop_end.load(Ordering::Acquire);
+
+        let packet = match self.format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                if looping {
+                    self.seek(loop_start)?;
+                    return Ok(None);
+                }
+                return Ok(Some(Packet {
+                    generation: self.generation,
+                    start: 0,
+                    samples: self.samples(),
+                }));
+            }
+            Err(err) => return Err(format!("Couldn't read the audio file: {}", err)),
+        };
+        if packet.track_id() != self.track_id {
+            return Ok(None);
+        }
+        let decoded = match self.decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt packet is skipped, like a browser would.
+            Err(SymphoniaError::DecodeError(_)) => return Ok(None),
+            Err(err) => return Err(format!("Couldn't decode the audio file: {}", err)),
+        };
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+        buffer.copy_interleaved_ref(decoded);
+
+        let mut start = packet.ts();
+        let mut samples = self.samples();
+        samples.extend_from_slice(buffer.samples());
+        if start < self.skip_to {
+            let skip = ((self.skip_to - start) as usize * self.channels).min(samples.len());
+            samples.drain(..skip);
+            start = self.skip_to;
+        }
+        let end = start + (samples.len() / self.channels) as u64;
+        if looping && loop_end > loop_start && end >= loop_end {
+            samples.truncate(loop_end.saturating_sub(start) as usize * self.channels);
+            self.seek(loop_start)?;
+        }
+        if samples.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Packet {
+            generation: self.generation,
+            start,
+            samples,
+        }))
+    }
+
+    /// An empty buffer for a packet's samples, reusing one that has been played if there is one.
+    fn samples(&self) -> Vec<f32> {
+        match self.recycled.try_recv() {
+            Ok(mut samples) => {
+                samples.clear();
+                samples
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn seek(&mut self, frame: u64) -> core::result::Result<(), String> {
+        let seeked = self
+            .format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::TimeStamp {
+                    ts: frame,
+                    track_id: self.track_id,
+                },
+            )
+            .map_err(|err| format!("Couldn't seek in the audio file: {}", err))?;
+        self.decoder.reset();
+        self.skip_to = seeked.required_ts;
+        Ok(())
+    }
+
+    fn fail(&self, err: String) {
+        *self.shared.error.lock().unwrap() = Some(err);
+        self.shared.ended.store(true, Ordering::Release);
+    }
+}
+
+struct ProcessorOptions {
+    packets: Receiver<Packet>,
+    recycled: SyncSender<Vec<f32>>,
+    shared: Arc<Shared>,
+    channels: usize,
+    sample_rate: f64,
+}
+
+/// Plays the decoded packets on the audio render thread, resampling them to the context's rate.
+struct StreamProcessor {
+    options: ProcessorOptions,
+    generation: u64,
+    packet: Option<Packet>,
+    /// The last frame of the packet before the current one, which playback interpolates from
+    /// while the position is less than a frame into the current packet.
+    previous: Vec<f32>,
+    /// Whether the decoder has reached the end of the file.
+    ended: bool,
+    /// The position in the current packet, in frames of the file. It's negative between the
+    /// previous packet's last frame and the current one's first.
+    offset: f64,
+}
+
+impl StreamProcessor {
+    fn packet_frames(&self) -> usize {
+        self.packet
+            .as_ref()
+            .map_or(0, |packet| packet.samples.len() / self.options.channels)
+    }
+
+    /// Moves on to the next packet of the current generation, or None when playback has caught
+    /// up with the decoder.
+    fn next_packet(&mut self) -> Option<Packet> {
+        while let Ok(packet) = self.options.packets.try_recv() {
+            if packet.generation != self.generation {
+                self.recycle(packet);
+                continue;
+            }
+            if packet.samples.is_empty() {
+                self.recycle(packet);
+                self.ended = true;
+                self.options.shared.ended.store(true, Ordering::Release);
+                return None;
+            }
+            return Some(packet);
+        }
+        None
+    }
+
+    /// Sends a packet's buffer back to the decoder, so it isn't freed on the render thread.
+    fn recycle(&self, packet: Packet) {
+        let _ = self.options.recycled.try_send(packet.samples);
+    }
+
+    /// Makes the next packet the current one, remembering the last frame of this one.
+    fn advance(&mut self, packet: Packet) {
+        let frames = self.packet_frames();
+        let channels = self.options.channels;
+        match self.packet.replace(packet) {
+            Some(previous) => {
+                self.previous
+                    .copy_from_slice(&previous.samples[(frames - 1) * channels..]);
+                self.recycle(previous);
+            }
+            None => self.previous.fill(0.),
+        }
+        self.offset -= frames as f64;
+    }
+
+    /// A sample of the current packet, where frame -1 is the previous packet's last frame.
+    fn sample(&self, frame: isize, channel: usize) -> f32 {
+        match frame {
+            -1 => self.previous[channel],
+            frame => {
+                self.packet.as_ref().unwrap().samples
+                    [frame as usize * self.options.channels + channel]
+            }
+        }
+    }
+}
+
+impl AudioWorkletProcessor for StreamProcessor {
+    type ProcessorOptions = ProcessorOptions;
+
+    fn constructor(options: Self::ProcessorOptions) -> Self {
+        let previous = vec![0.; options.channels];
+        StreamProcessor {
+            options,
+            generation: 0,
+            packet: None,
+            previous,
+            ended: false,
+            offset: 0.,
+        }
+    }
+
+    fn process<'a, 'b>(
+        &mut self,
+        _inputs: &'b [&'a [&'a [f32]]],
+        outputs: &'b mut [&'a mut [&'a mut [f32]]],
+        _params: AudioParamValues<'b>,
+        scope: &'b AudioWorkletGlobalScope,
+    ) -> bool {
+        let output = &mut *outputs[0];
+        for channel in output.iter_mut() {
+            channel.fill(0.);
+        }
+
+        let shared = self.options.shared.clone();
+        let generation = shared.generation.load(Ordering::Acquire);
+        if generation != self.generation {
+            self.generation = generation;
+            if let Some(packet) = self.packet.take() {
+                self.recycle(packet);
+            }
+            self.ended = false;
+            self.offset = 0.;
+        }
+        if shared.paused.load(Ordering::Acquire) {
+            return true;
+        }
+
+        let step = self.options.sample_rate / scope.sample_rate as f64;
+        let frames = output.first().map_or(0, |channel| channel.len());
+        for frame in 0..frames {
+            // Interpolating needs the frame after the position too, so the next packet is taken
+            // as soon as the position is past the current one's last frame.
+            while !self.ended && self.offset + 1. >= self.packet_frames() as f64 {
+                let Some(packet) = self.next_packet() else {
+                    break;
+                };
+                self.advance(packet);
+            }
+            let packet_frames = self.packet_frames();
+            if self.packet.is_none() || self.offset >= packet_frames as f64 {
+                // The file has ended.
+                break;
+            }
+            if !self.ended && self.offset + 1. >= packet_frames as f64 {
+                // The decoder has fallen behind.
+                break;
+            }
+
+            let index = self.offset.floor() as isize;
+            // At the end of the file the last frame has nothing after it.
+            let next = (index + 1).min(packet_frames as isize - 1);
+            let fraction = (self.offset - index as f64) as f32;
+            for (channel, output_channel) in output.iter_mut().enumerate() {
+                let current = self.sample(index, channel);
+                let following = self.sample(next, channel);
+                output_channel[frame] = current + (following - current) * fraction;
+            }
+            self.offset += step;
+        }
+
+        if let Some(packet) = &self.packet {
+            let position = packet.start + self.offset.max(0.) as u64;
+            shared.position.store(position, Ordering::Release);
+        }
+        true
+    }
+}
+
+/// Lets symphonia read a file from a storage device, which it needs to be shareable between
+/// threads.
+struct VfsSource {
+    file: Mutex<Box<dyn SeekAndRead + Send>>,
+    len: Option<u64>,
+}
+
+impl VfsSource {
+    fn new(mut file: Box<dyn SeekAndRead + Send>) -> VfsSource {
+        let len = file
+            .seek(SeekFrom::End(0))
+            .and_then(|len| file.seek(SeekFrom::Start(0)).map(|_| len))
+            .ok();
+        VfsSource {
+            file: Mutex::new(file),
+            len,
+        }
+    }
+}
+
+impl Read for VfsSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.get_mut().unwrap().read(buf)
+    }
+}
+
+impl Seek for VfsSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.get_mut().unwrap().seek(pos)
+    }
+}
+
+impl MediaSource for VfsSource {
+    fn is_seekable(&self) -> bool {
+        self.len.is_some()
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.len
+    }
+}
+
+impl HostAudioStreamSourceNode for RuneRuntimeState {
+    async fn play(&mut self, node: Resource<AudioStream>) {
+        let node = self.table.get(&node).unwrap();
+        node.shared.paused.store(false, Ordering::Release);
+    }
+
+    async fn pause(&mut self, node: Resource<AudioStream>) {
+        let node = self.table.get(&node).unwrap();
+        node.shared.paused.store(true, Ordering::Release);
+    }
+
+    async fn paused(&mut self, node: Resource<AudioStream>) -> bool {
+        let node = self.table.get(&node).unwrap();
+        node.shared.paused.load(Ordering::Acquire)
+    }
+
+    async fn ended(&mut self, node: Resource<AudioStream>) -> bool {
+        let node = self.table.get(&node).unwrap();
+        node.shared.ended.load(Ordering::Acquire)
+    }
+
+    async fn error(&mut self, node: Resource<AudioStream>) -> Option<String> {
+        let node = self.table.get(&node).unwrap();
+        node.shared.error.lock().unwrap().clone()
+    }
+
+    async fn duration(&mut self, node: Resource<AudioStream>) -> Option<f32> {
+        let node = self.table.get(&node).unwrap();
+        node.length.map(|length| node.seconds(length))
+    }
+
+    async fn position(&mut self, node: Resource<AudioStream>) -> f32 {
+        let node = self.table.get(&node).unwrap();
+        node.seconds(node.shared.position.load(Ordering::Acquire))
+    }
+
+    async fn seek(&mut self, node: Resource<AudioStream>, position: f32) {
+        let node = self.table.get(&node).unwrap();
+        let frame = node.frames(position);
+        let generation = node.shared.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        node.shared.position.store(frame, Ordering::Release);
+        node.shared.ended.store(false, Ordering::Release);
+        let _ = node.seeks.send((frame, generation));
+    }
+
+    async fn loop_(&mut self, node: Resource<AudioStream>) -> bool {
+        let node = self.table.get(&node).unwrap();
+        node.shared.looping.load(Ordering::Acquire)
+    }
+
+    async fn set_loop(&mut self, node: Resource<AudioStream>, value: bool) {
+        let node = self.table.get(&node).unwrap();
+        node.shared.looping.store(value, Ordering::Release);
+    }
+
+    async fn loop_start(&mut self, node: Resource<AudioStream>) -> f32 {
+        let node = self.table.get(&node).unwrap();
+        node.seconds(node.shared.loop_start.load(Ordering::Acquire))
+    }
+
+    async fn set_loop_start(&mut self, node: Resource<AudioStream>, value: f32) {
+        let node = self.table.get(&node).unwrap();
+        let frame = node.frames(value);
+        node.shared.loop_start.store(frame, Ordering::Release);
+    }
+
+    async fn loop_end(&mut self, node: Resource<AudioStream>) -> f32 {
+        let node = self.table.get(&node).unwrap();
+        node.seconds(node.shared.loop_end.load(Ordering::Acquire))
+    }
+
+    async fn set_loop_end(&mut self, node: Resource<AudioStream>, value: f32) {
+        let node = self.table.get(&node).unwrap();
+        let frame = node.frames(value);
+        node.shared.loop_end.store(frame, Ordering::Release);
+    }
+
+    async fn connect(
+        &mut self,
+        node: Resource<AudioStream>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<AudioStream>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<AudioStream>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<AudioStream>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<AudioStream>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
+    }
+
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<AudioStream>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<AudioStream>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<AudioStream>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
+        // Dropping the node stops its decoder thread too.
+        self.table.delete(rep)?;
+        Ok(())
+    }
+}
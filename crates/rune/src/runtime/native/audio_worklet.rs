@@ -0,0 +1,596 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use wasmtime::component::{Component, Linker, Resource};
+use wasmtime::{Config, Engine, Result, Store, StoreLimits, StoreLimitsBuilder, Trap};
+use wasmtime_wasi::{ResourceTable, WasiCtx, WasiCtxBuilder, WasiView};
+use web_audio_api::context::BaseAudioContext;
+use web_audio_api::node::{AudioNode, AudioNodeOptions, ChannelCountMode, ChannelInterpretation};
+use web_audio_api::worklet::{
+    AudioParamValues, AudioWorkletGlobalScope, AudioWorkletNode, AudioWorkletNodeOptions,
+    AudioWorkletProcessor,
+};
+use web_audio_api::{AudioParamDescriptor, AutomationRate};
+
+use crate::rune::runtime::audio::{
+    AudioParam, AudioWorkletNode as AudioWorkletNodeResource, AudioWorkletOptions,
+    HostAudioWorkletNode,
+};
+
+use super::audio::{
+    audio_node_connect, audio_node_disconnect, audio_node_disconnect_output, relative_path,
+};
+use super::state::RuneRuntimeState;
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "rune:runtime/audio-worklet",
+        path: "wit/runtime",
+    });
+}
+
+/// How often the worklet engine's epoch advances, which is the resolution of the time budget.
+const EPOCH_TICK: Duration = Duration::from_millis(1);
+/// How many epochs a call to process may run into before it's stopped. A render quantum at
+/// 44.1kHz is 2.9ms, so a processor taking longer than this would glitch anyway.
+const PROCESS_DEADLINE: u64 = 2;
+/// How many epochs instantiating a processor and running its init may take.
+const INIT_DEADLINE: u64 = 1000;
+const MEMORY_LIMIT: usize = 32 << 20;
+/// web_audio_api declares the parameters of a processor type up front, so every node gets this
+/// many and the game's parameters are mapped onto them in order.
+const MAX_PARAMETERS: usize = 16;
+/// How many messages can wait in each direction before more are dropped.
+const MESSAGE_CAPACITY: usize = 256;
+/// The frames in a render quantum, which the buffers handed to the guest are sized for.
+const RENDER_QUANTUM_SIZE: usize = 128;
+
+/// The engine audio worklets run in, and the components compiled for it so far.
+pub struct AudioWorklets {
+    engine: Engine,
+    linker: Linker<WorkletState>,
+    components: HashMap<PathBuf, Component>,
+}
+
+impl AudioWorklets {
+    fn new() -> Result<AudioWorklets> {
+        let mut config = Config::new();
+        config.wasm_component_model(true);
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config)?;
+
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker_sync(&mut linker)?;
+        bindings::AudioWorklet::add_to_linker(&mut linker, |state: &mut WorkletState| state)?;
+
+        // The engine lives as long as the game, so the ticker does too.
+        thread::spawn({
+            let engine = engine.clone();
+            move || loop {
+                thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        });
+
+        Ok(AudioWorklets {
+            engine,
+            linker,
+            components: HashMap::new(),
+        })
+    }
+}
+
+/// A node whose audio is processed by a guest audio-worklet component.
+pub struct AudioWorklet {
+    pub node: AudioWorkletNode,
+    /// The name of the web_audio_api parameter each of the game's parameters is mapped onto.
+    parameters: HashMap<String, String>,
+    to_processor: SyncSender<Vec<u8>>,
+    from_processor: Receiver<Vec<u8>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl AudioWorklet {
+    fn new(
+        context: &impl BaseAudioContext,
+        processor: ProcessorOptions,
+        options: AudioWorkletOptions,
+        to_processor: SyncSender<Vec<u8>>,
+        from_processor: Receiver<Vec<u8>>,
+    ) -> AudioWorklet {
+        let error = processor.error.clone();
+        let node = AudioWorkletNode::new::<GuestProcessor>(
+            context,
+            AudioWorkletNodeOptions {
+                number_of_inputs: options.number_of_inputs as usize,
+                number_of_outputs: options.number_of_outputs as usize,
+                output_channel_count: options
+                    .output_channel_count
+                    .iter()
+                    .map(|&count| count as usize)
+                    .collect(),
+                parameter_data: options
+                    .parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(index, parameter)| (index.to_string(), parameter.default_value as f64))
+                    .collect(),
+                processor_options: processor,
+                audio_node_options: AudioNodeOptions {
+                    channel_count: 2,
+                    channel_count_mode: ChannelCountMode::Max,
+                    channel_interpretation: ChannelInterpretation::Speakers,
+                },
+            },
+        );
+
+        let mut parameters = HashMap::new();
+        for (index, parameter) in options.parameters.into_iter().enumerate() {
+            node.parameters()[&index.to_string()]
+                .set_automation_rate(parameter.automation_rate.into());
+            parameters.insert(parameter.name, index.to_string());
+        }
+
+        AudioWorklet {
+            node,
+            parameters,
+            to_processor,
+            from_processor,
+            error,
+        }
+    }
+}
+
+impl RuneRuntimeState {
+    /// Creates an audio worklet node in a context, instantiating its component and running its
+    /// init here rather than on the audio render thread.
+    pub(super) fn create_audio_worklet<C: BaseAudioContext + 'static>(
+        &mut self,
+        audio_context: Resource<C>,
+        path: String,
+        options: AudioWorkletOptions,
+    ) -> core::result::Result<Resource<AudioWorkletNodeResource>, String> {
+        check_options(&options)?;
+        let path = self.input_path.join(relative_path(&path)?);
+        let sample_rate = self.table.get(&audio_context).unwrap().sample_rate();
+
+        if self.audio_worklets.is_none() {
+            let audio_worklets = AudioWorklets::new()
+                .map_err(|err| format!("Couldn't start the audio worklet engine: {:#}", err))?;
+            self.audio_worklets = Some(audio_worklets);
+        }
+        let audio_worklets = self.audio_worklets.as_mut().unwrap();
+        let component = match audio_worklets.components.get(&path) {
+            Some(component) => component.clone(),
+            None => {
+                let component = Component::from_file(&audio_worklets.engine, &path)
+                    .map_err(|err| format!("Couldn't load {}: {:#}", path.display(), err))?;
+                audio_worklets
+                    .components
+                    .insert(path.clone(), component.clone());
+                component
+            }
+        };
+
+        let (to_processor, processor_messages) = mpsc::sync_channel(MESSAGE_CAPACITY);
+        let (game_messages, from_processor) = mpsc::sync_channel(MESSAGE_CAPACITY);
+        let mut store = Store::new(
+            &audio_worklets.engine,
+            WorkletState {
+                table: ResourceTable::new(),
+                // Writing to the console from the audio render thread could block it.
+                wasi_ctx: WasiCtxBuilder::new().build(),
+                limits: StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build(),
+                messages: game_messages,
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_epoch_deadline(INIT_DEADLINE);
+
+        let worklet =
+            bindings::AudioWorklet::instantiate(&mut store, &component, &audio_worklets.linker)
+                .map_err(|err| format!("Couldn't instantiate {}: {:#}", path.display(), err))?;
+        worklet
+            .rune_runtime_audio_processor()
+            .call_init(&mut store, sample_rate, &options.processor_options)
+            .map_err(|err| format!("{} trapped in init: {:#}", path.display(), err))?
+            .map_err(|err| format!("{} failed to init: {}", path.display(), err))?;
+
+        let processor = ProcessorOptions {
+            store,
+            worklet,
+            messages: processor_messages,
+            parameters: (0..options.parameters.len())
+                .map(|index| index.to_string())
+                .collect(),
+            ranges: options
+                .parameters
+                .iter()
+                .map(|parameter| (parameter.min_value, parameter.max_value))
+                .collect(),
+            error: Arc::new(Mutex::new(None)),
+            // Nodes mix their inputs to stereo unless more channels are connected.
+            inputs: vec![
+                vec![Vec::with_capacity(RENDER_QUANTUM_SIZE); 2];
+                options.number_of_inputs as usize
+            ],
+            parameter_values: vec![
+                Vec::with_capacity(RENDER_QUANTUM_SIZE);
+                options.parameters.len()
+            ],
+        };
+        let audio_context = self.table.get(&audio_context).unwrap();
+        let node = AudioWorklet::new(
+            audio_context,
+            processor,
+            options,
+            to_processor,
+            from_processor,
+        );
+        Ok(self.table.push(node).unwrap())
+    }
+}
+
+/// Checks the options first, since web_audio_api panics on a node it can't create.
+fn check_options(options: &AudioWorkletOptions) -> core::result::Result<(), String> {
+    if options.number_of_inputs == 0 && options.number_of_outputs == 0 {
+        return Err("An audio worklet node needs at least one input or output".to_owned());
+    }
+    if !options.output_channel_count.is_empty()
+        && options.output_channel_count.len() != options.number_of_outputs as usize
+    {
+        return Err(format!(
+            "There are {} output channel counts for {} outputs",
+            options.output_channel_count.len(),
+            options.number_of_outputs
+        ));
+    }
+    if let Some(count) = options
+        .output_channel_count
+        .iter()
+        .find(|count| !(1..=32).contains(*count))
+    {
+        return Err(format!("{} isn't between 1 and 32 channels", count));
+    }
+    if options.parameters.len() > MAX_PARAMETERS {
+        return Err(format!(
+            "An audio worklet node can have up to {} parameters, not {}",
+            MAX_PARAMETERS,
+            options.parameters.len()
+        ));
+    }
+    for (index, parameter) in options.parameters.iter().enumerate() {
+        if options.parameters[..index]
+            .iter()
+            .any(|other| other.name == parameter.name)
+        {
+            return Err(format!("There are two parameters named {}", parameter.name));
+        }
+        if parameter.min_value > parameter.max_value {
+            return Err(format!(
+                "The parameter {} has a min-value above its max-value",
+                parameter.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl HostAudioWorkletNode for RuneRuntimeState {
+    async fn parameter(
+        &mut self,
+        node: Resource<AudioWorklet>,
+        name: String,
+    ) -> Option<Resource<AudioParam>> {
+        let node = self.table.get(&node).unwrap();
+        let parameter = node.parameters.get(&name)?;
+        let parameter = node.node.parameters()[parameter].clone();
+        Some(self.table.push(parameter).unwrap())
+    }
+
+    async fn post_message(&mut self, node: Resource<AudioWorklet>, data: Vec<u8>) {
+        let node = self.table.get(&node).unwrap();
+        let _ = node.to_processor.try_send(data);
+    }
+
+    async fn receive_messages(&mut self, node: Resource<AudioWorklet>) -> Vec<Vec<u8>> {
+        let node = self.table.get(&node).unwrap();
+        node.from_processor.try_iter().collect()
+    }
+
+    async fn error(&mut self, node: Resource<AudioWorklet>) -> Option<String> {
+        let node = self.table.get(&node).unwrap();
+        node.error.lock().unwrap().clone()
+    }
+
+    async fn connect(
+        &mut self,
+        node: Resource<AudioWorklet>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            0,
+            0,
+        )
+    }
+
+    async fn connect_from_output_to_input(
+        &mut self,
+        node: Resource<AudioWorklet>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_connect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            output,
+            input,
+        )
+    }
+
+    async fn disconnect(&mut self, node: Resource<AudioWorklet>) {
+        self.forget_audio_connections(node.rep());
+        let node = self.table.get(&node).unwrap();
+        node.node.disconnect();
+    }
+
+    async fn disconnect_from(
+        &mut self,
+        node: Resource<AudioWorklet>,
+        destination: crate::rune::runtime::audio::AudioNode,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            None,
+            None,
+        )
+    }
+
+    async fn disconnect_output(
+        &mut self,
+        node: Resource<AudioWorklet>,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect_output(&mut self.audio_connections, node.rep(), source, output)
+    }
+
+    async fn disconnect_dest_from_output(
+        &mut self,
+        node: Resource<AudioWorklet>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            None,
+        )
+    }
+
+    async fn disconnect_dest_from_output_to_input(
+        &mut self,
+        node: Resource<AudioWorklet>,
+        destination: crate::rune::runtime::audio::AudioNode,
+        output: u32,
+        input: u32,
+    ) -> core::result::Result<(), String> {
+        let source: &dyn web_audio_api::node::AudioNode = { &self.table.get(&node).unwrap().node };
+        audio_node_disconnect(
+            &self.table,
+            &self.audio_outputs,
+            &mut self.audio_connections,
+            node.rep(),
+            source,
+            destination,
+            Some(output),
+            Some(input),
+        )
+    }
+
+    async fn drop(&mut self, rep: Resource<AudioWorklet>) -> Result<()> {
+        self.forget_audio_node(rep.rep());
+        Ok(())
+    }
+}
+
+struct WorkletState {
+    table: ResourceTable,
+    wasi_ctx: WasiCtx,
+    limits: StoreLimits,
+    messages: SyncSender<Vec<u8>>,
+}
+
+impl WasiView for WorkletState {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi_ctx
+    }
+}
+
+impl bindings::rune::runtime::audio_processor_port::Host for WorkletState {
+    fn post_message(&mut self, data: Vec<u8>) {
+        // A game that stops reading messages mustn't hold up the audio render thread.
+        let _ = self.messages.try_send(data);
+    }
+}
+
+/// Everything a processor needs, moved onto the audio render thread when the node is created.
+struct ProcessorOptions {
+    store: Store<WorkletState>,
+    worklet: bindings::AudioWorklet,
+    messages: Receiver<Vec<u8>>,
+    /// The web_audio_api parameter names in use, in the game's order.
+    parameters: Vec<String>,
+    ranges: Vec<(f32, f32)>,
+    error: Arc<Mutex<Option<String>>>,
+    /// The buffers the inputs and parameter values are copied into for the guest, reused
+    /// between render quanta so the audio render thread doesn't allocate.
+    inputs: Vec<Vec<Vec<f32>>>,
+    parameter_values: Vec<Vec<f32>>,
+}
+
+/// Runs the guest's process for each render quantum, until it's stopped for going over its
+/// limits or trapping.
+struct GuestProcessor {
+    options: ProcessorOptions,
+    stopped: bool,
+}
+
+impl GuestProcessor {
+    fn run(
+        &mut self,
+        inputs: &[&[&[f32]]],
+        params: &AudioParamValues,
+        frames: usize,
+    ) -> Result<bindings::exports::rune::runtime::audio_processor::ProcessResult> {
+        let options = &mut self.options;
+        options.store.set_epoch_deadline(PROCESS_DEADLINE);
+        let processor = options.worklet.rune_runtime_audio_processor();
+
+        for message in options.messages.try_iter() {
+            processor.call_message(&mut options.store, &message)?;
+        }
+
+        // The number of channels only changes when connections do, so this only allocates then.
+        options.inputs.resize_with(inputs.len(), Vec::new);
+        for (buffer, input) in options.inputs.iter_mut().zip(inputs) {
+            buffer.resize_with(input.len(), || Vec::with_capacity(RENDER_QUANTUM_SIZE));
+            for (buffer, channel) in buffer.iter_mut().zip(input.iter()) {
+                buffer.clear();
+                buffer.extend_from_slice(channel);
+            }
+        }
+        for ((buffer, name), &(min, max)) in options
+            .parameter_values
+            .iter_mut()
+            .zip(&options.parameters)
+            .zip(&options.ranges)
+        {
+            buffer.clear();
+            buffer.extend(params.get(name).iter().map(|value| value.clamp(min, max)));
+        }
+        processor.call_process(
+            &mut options.store,
+            &options.inputs,
+            &options.parameter_values,
+            frames as u32,
+        )
+    }
+}
+
+impl AudioWorkletProcessor for GuestProcessor {
+    type ProcessorOptions = ProcessorOptions;
+
+    fn constructor(options: Self::ProcessorOptions) -> Self {
+        GuestProcessor {
+            options,
+            stopped: false,
+        }
+    }
+
+    fn parameter_descriptors() -> Vec<AudioParamDescriptor>
+    where
+        Self: Sized,
+    {
+        // The game's min-value and max-value are applied when the values are handed over.
+        (0..MAX_PARAMETERS)
+            .map(|index| AudioParamDescriptor {
+                name: index.to_string(),
+                automation_rate: AutomationRate::A,
+                default_value: 0.,
+                min_value: f32::MIN,
+                max_value: f32::MAX,
+            })
+            .collect()
+    }
+
+    fn process<'a, 'b>(
+        &mut self,
+        inputs: &'b [&'a [&'a [f32]]],
+        outputs: &'b mut [&'a mut [&'a mut [f32]]],
+        params: AudioParamValues<'b>,
+        _scope: &'b AudioWorkletGlobalScope,
+    ) -> bool {
+        for channel in outputs.iter_mut().flat_map(|output| output.iter_mut()) {
+            channel.fill(0.);
+        }
+        if self.stopped {
+            return false;
+        }
+
+        let frames = outputs
+            .iter()
+            .flat_map(|output| output.first())
+            .map(|channel| channel.len())
+            .chain(
+                inputs
+                    .iter()
+                    .flat_map(|input| input.first())
+                    .map(|channel| channel.len()),
+            )
+            .next()
+            .unwrap_or(128);
+        match self.run(inputs, &params, frames) {
+            Ok(result) => {
+                for (output, guest_output) in outputs.iter_mut().zip(result.outputs) {
+                    for (channel, guest_channel) in output.iter_mut().zip(guest_output) {
+                        let length = channel.len().min(guest_channel.len());
+                        channel[..length].copy_from_slice(&guest_channel[..length]);
+                    }
+                }
+                result.keep_alive
+            }
+            Err(err) => {
+                let error = match err.downcast_ref::<Trap>() {
+                    Some(Trap::Interrupt) => format!(
+                        "The audio processor went over its time budget of {}ms",
+                        PROCESS_DEADLINE * EPOCH_TICK.as_millis() as u64
+                    ),
+                    _ => format!("The audio processor trapped: {:#}", err),
+                };
+                if let Ok(mut slot) = self.options.error.try_lock() {
+                    *slot = Some(error);
+                }
+                self.stopped = true;
+                false
+            }
+        }
+    }
+}
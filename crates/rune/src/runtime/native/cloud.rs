@@ -0,0 +1,805 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{header, Method, StatusCode, Url};
+
+use crate::runtime::storage::{CloudConfig, CloudConflicts};
+
+use super::debug;
+
+/// How often the server is checked for files changed by other machines.
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a request can take before the server is taken to be offline.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const DAV: &str = "DAV:";
+
+/// Mirrors the game's cloud storage to a directory on this machine and syncs the two on a
+/// background thread. The game only touches the mirror, so cloud storage keeps working offline
+/// and its changes are sent once the server can be reached again.
+pub struct CloudSync {
+    /// The directory the game's files are mirrored to.
+    pub root: PathBuf,
+    shared: Arc<Shared>,
+    wake: Sender<()>,
+}
+
+struct Shared {
+    files: Mutex<Files>,
+    online: AtomicBool,
+    /// Sent as a bearer token with every request. It can change while syncing, when the game
+    /// signs the player in again.
+    token: Mutex<Option<String>>,
+}
+
+/// What's known about each file that's been synced or changed, keyed by its path in the storage
+/// device.
+struct Files {
+    entries: HashMap<String, FileState>,
+    /// Where the entries are kept between runs, so changes made offline are sent next time.
+    state_path: PathBuf,
+}
+
+#[derive(Clone, Default)]
+struct FileState {
+    /// The server's version of the file when it was last synced, or None if it never was.
+    etag: Option<String>,
+    /// Whether the file has changed here since it was last synced.
+    dirty: bool,
+    /// Whether the change was removing the file.
+    deleted: bool,
+    /// Counts changes, so a file written again while it's being uploaded stays dirty.
+    revision: u64,
+}
+
+/// What the server did with a change.
+enum Sent {
+    Synced(Option<String>),
+    Removed,
+    Conflict,
+    Failed(StatusCode),
+}
+
+impl CloudSync {
+    /// Starts syncing a player's collection on the server in `config` with a mirror kept under
+    /// `dir`.
+    pub fn start(
+        config: CloudConfig,
+        player: &str,
+        token: Option<String>,
+        dir: &Path,
+    ) -> Option<CloudSync> {
+        if player.is_empty() || player == "." || player == ".." {
+            debug::warn(&format!(
+                "{:?} isn't a player id that cloud storage can use",
+                player
+            ));
+            return None;
+        }
+        // Player ids can have any characters, so the collection and mirror are named by an
+        // encoding that's safe in both urls and file names.
+        let player = percent_encode(player).replace('/', "%2F");
+
+        let mut base = match Url::parse(&config.url) {
+            Ok(base) => base,
+            Err(err) => {
+                debug::warn(&format!(
+                    "Invalid cloud.url {} in rune.toml: {}",
+                    config.url, err
+                ));
+                return None;
+            }
+        };
+        // Files are joined onto the url, which only keeps its last segment if it ends with a slash.
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        let base = base.join(&format!("{}/", player)).unwrap();
+
+        let dir = dir.join(&player);
+        let root = dir.join("files");
+        if let Err(err) = fs::create_dir_all(&root) {
+            debug::warn(&format!(
+                "Couldn't create the cloud storage directory {}: {}",
+                root.display(),
+                err
+            ));
+            return None;
+        }
+
+        let state_path = dir.join("state");
+        let shared = Arc::new(Shared {
+            files: Mutex::new(Files {
+                entries: Files::load(&state_path),
+                state_path,
+            }),
+            online: AtomicBool::new(false),
+            token: Mutex::new(token),
+        });
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build().ok()?;
+        let syncer = Syncer {
+            client,
+            base,
+            conflicts: config.conflicts,
+            root: root.clone(),
+            download_path: dir.join("download"),
+            shared: shared.clone(),
+        };
+        let (wake, woken) = mpsc::channel();
+        thread::spawn(move || syncer.run(&woken));
+
+        Some(CloudSync { root, shared, wake })
+    }
+
+    /// Records that the game wrote or removed a file, and wakes the sync thread to send it.
+    pub fn changed(&self, path: &str, deleted: bool) {
+        let mut files = self.shared.files.lock().unwrap();
+        let file = files.entries.entry(key(path)).or_default();
+        file.dirty = true;
+        file.deleted = deleted;
+        file.revision += 1;
+        files.save();
+        let _ = self.wake.send(());
+    }
+
+    /// Replaces the token sent with requests, and wakes the sync thread in case the old one was
+    /// why it couldn't sync.
+    pub fn set_token(&self, token: Option<String>) {
+        let mut current = self.shared.token.lock().unwrap();
+        if *current != token {
+            *current = token;
+            let _ = self.wake.send(());
+        }
+    }
+
+    /// Whether the last sync reached the server.
+    pub fn online(&self) -> bool {
+        self.shared.online.load(Ordering::Relaxed)
+    }
+
+    /// How many changes haven't been sent to the server yet.
+    pub fn pending(&self) -> u32 {
+        let files = self.shared.files.lock().unwrap();
+        files.entries.values().filter(|file| file.dirty).count() as u32
+    }
+
+    /// The files whose changes here lost to the server's, for as long as their `.conflict` copy
+    /// is still around.
+    pub fn conflicts(&self) -> Vec<String> {
+        let files = self.shared.files.lock().unwrap();
+        files
+            .entries
+            .keys()
+            .filter(|path| self.root.join(format!("{}.conflict", path)).exists())
+            .cloned()
+            .collect()
+    }
+}
+
+impl Files {
+    /// Reads the entries saved by a previous run, one `<state>\t<etag>\t<path>` line each.
+    fn load(state_path: &Path) -> HashMap<String, FileState> {
+        let state = match fs::read_to_string(state_path) {
+            Ok(state) => state,
+            Err(_) => return HashMap::new(),
+        };
+        state
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let (state, etag, path) = (fields.next()?, fields.next()?, fields.next()?);
+                let file = FileState {
+                    etag: (!etag.is_empty()).then(|| etag.to_owned()),
+                    dirty: state != "synced",
+                    deleted: state == "removed",
+                    revision: 0,
+                };
+                Some((path.to_owned(), file))
+            })
+            .collect()
+    }
+
+    fn save(&self) {
+        let mut state = String::new();
+        for (path, file) in &self.entries {
+            let file_state = match (file.dirty, file.deleted) {
+                (false, _) => "synced",
+                (true, false) => "changed",
+                (true, true) => "removed",
+            };
+            let etag = file.etag.as_deref().unwrap_or("");
+            state.push_str(&format!("{}\t{}\t{}\n", file_state, etag, path));
+        }
+        if let Err(err) = fs::write(&self.state_path, state) {
+            debug::warn(&format!(
+                "Couldn't save the cloud storage sync state: {}",
+                err
+            ));
+        }
+    }
+}
+
+/// The sync thread's half, which talks to a WebDAV server.
+struct Syncer {
+    client: Client,
+    base: Url,
+    conflicts: CloudConflicts,
+    root: PathBuf,
+    /// Downloads are written here first, so the game never reads half a file.
+    download_path: PathBuf,
+    shared: Arc<Shared>,
+}
+
+impl Syncer {
+    fn run(&self, woken: &Receiver<()>) {
+        let mut warned = false;
+        loop {
+            match self.sync() {
+                Ok(()) => {
+                    self.shared.online.store(true, Ordering::Relaxed);
+                    warned = false;
+                }
+                Err(err) => {
+                    self.shared.online.store(false, Ordering::Relaxed);
+                    if !warned {
+                        debug::warn(&format!("Couldn't reach the cloud storage server, so changes will be sent once it's back: {}", err));
+                        warned = true;
+                    }
+                }
+            }
+
+            match woken.recv_timeout(SYNC_INTERVAL) {
+                Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            // Sends a burst of writes together.
+            while woken.try_recv().is_ok() {}
+        }
+    }
+
+    fn sync(&self) -> Result<(), reqwest::Error> {
+        self.send_changes()?;
+        self.fetch_changes()?;
+        self.shared.files.lock().unwrap().save();
+        Ok(())
+    }
+
+    /// Uploads the files changed here and removes the ones removed here.
+    fn send_changes(&self) -> Result<(), reqwest::Error> {
+        let changes: Vec<(String, FileState)> = {
+            let files = self.shared.files.lock().unwrap();
+            files
+                .entries
+                .iter()
+                .filter(|(_, file)| file.dirty)
+                .map(|(path, file)| (path.clone(), file.clone()))
+                .collect()
+        };
+
+        for (path, sent_file) in changes {
+            let sent = if sent_file.deleted {
+                self.remove(&path, &sent_file)?
+            } else {
+                self.upload(&path, &sent_file)?
+            };
+
+            let mut files = self.shared.files.lock().unwrap();
+            let file = match files.entries.get_mut(&path) {
+                Some(file) => file,
+                None => continue,
+            };
+            let unchanged = file.revision == sent_file.revision;
+            match sent {
+                Sent::Synced(etag) => {
+                    file.etag = etag;
+                    if unchanged {
+                        file.dirty = false;
+                    }
+                }
+                Sent::Removed if unchanged => {
+                    files.entries.remove(&path);
+                }
+                Sent::Removed => file.etag = None,
+                Sent::Conflict => {
+                    file.etag = None;
+                    // A newer change is sent again, and kept aside instead if it conflicts too.
+                    if unchanged {
+                        // The server's file is downloaded over this one by the next fetch.
+                        file.dirty = false;
+                        file.deleted = false;
+                        if !sent_file.deleted {
+                            self.keep_conflict(&path);
+                        }
+                    }
+                }
+                Sent::Failed(status) => debug::warn(&format!(
+                    "The cloud storage server couldn't sync {}: {}",
+                    path, status
+                )),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn upload(&self, path: &str, file: &FileState) -> Result<Sent, reqwest::Error> {
+        let data = match fs::read(self.root.join(path)) {
+            Ok(data) => data,
+            Err(_) => return self.remove(path, file),
+        };
+
+        let mut response = self.put(path, &data, file)?;
+        // WebDAV servers don't create missing parent directories themselves.
+        if response.status() == StatusCode::CONFLICT {
+            self.create_parents(path)?;
+            response = self.put(path, &data, file)?;
+        }
+
+        Ok(match response.status() {
+            status if status.is_success() => Sent::Synced(etag(&response)),
+            StatusCode::PRECONDITION_FAILED => Sent::Conflict,
+            status => Sent::Failed(status),
+        })
+    }
+
+    fn put(&self, path: &str, data: &[u8], file: &FileState) -> Result<Response, reqwest::Error> {
+        let mut request = self.request(Method::PUT, path).body(data.to_vec());
+        if self.conflicts == CloudConflicts::VersionCheck {
+            // Only replaces the version this machine last saw, or only creates the file if it
+            // never saw one.
+            request = match &file.etag {
+                Some(etag) => request.header(header::IF_MATCH, etag),
+                None => request.header(header::IF_NONE_MATCH, "*"),
+            };
+        }
+        request.send()
+    }
+
+    fn remove(&self, path: &str, file: &FileState) -> Result<Sent, reqwest::Error> {
+        let etag = match &file.etag {
+            Some(etag) => etag,
+            // It never reached the server.
+            None => return Ok(Sent::Removed),
+        };
+
+        let mut request = self.request(Method::DELETE, path);
+        if self.conflicts == CloudConflicts::VersionCheck {
+            request = request.header(header::IF_MATCH, etag);
+        }
+        let response = request.send()?;
+
+        Ok(match response.status() {
+            status if status.is_success() => Sent::Removed,
+            StatusCode::NOT_FOUND => Sent::Removed,
+            StatusCode::PRECONDITION_FAILED => Sent::Conflict,
+            status => Sent::Failed(status),
+        })
+    }
+
+    /// Creates each directory above a file on the server, including the game's own.
+    fn create_parents(&self, path: &str) -> Result<(), reqwest::Error> {
+        let url = self.url(path);
+        let segments: Vec<&str> = url.path_segments().into_iter().flatten().collect();
+        let mut parent = url.clone();
+        for end in 1..segments.len() {
+            parent.set_path(&format!("/{}/", segments[..end].join("/")));
+            // Directories that already exist fail, which is fine.
+            self.authorize(self.client.request(mkcol(), parent.clone()))
+                .send()?;
+        }
+        Ok(())
+    }
+
+    /// Keeps the game's losing version of a file next to the server's, so nothing is lost.
+    fn keep_conflict(&self, path: &str) {
+        let conflict = format!("{}.conflict", path);
+        if fs::rename(self.root.join(path), self.root.join(&conflict)).is_ok() {
+            debug::warn(&format!(
+                "{} was changed on another machine too, so this machine's version was moved to {}",
+                path, conflict
+            ));
+        }
+    }
+
+    /// Downloads the files changed on the server and removes the ones removed there.
+    fn fetch_changes(&self) -> Result<(), reqwest::Error> {
+        let remote = match self.list()? {
+            Some(remote) => remote,
+            None => {
+                // Removing everything that seems to be missing from a listing that can't be read
+                // would throw the saves away.
+                debug::warn("Couldn't read the cloud storage server's file listing");
+                return Ok(());
+            }
+        };
+
+        let downloads: Vec<(String, String)> = {
+            let files = self.shared.files.lock().unwrap();
+            remote
+                .iter()
+                .filter(|(path, etag)| match files.entries.get(*path) {
+                    Some(file) => !file.dirty && file.etag.as_ref() != Some(etag),
+                    None => true,
+                })
+                .map(|(path, etag)| (path.clone(), etag.clone()))
+                .collect()
+        };
+
+        for (path, listed_etag) in downloads {
+            let response = self.request(Method::GET, &path).send()?;
+            // It was removed since the listing.
+            if !response.status().is_success() {
+                continue;
+            }
+            let etag = etag(&response).unwrap_or(listed_etag);
+            let data = response.bytes()?;
+
+            let mut files = self.shared.files.lock().unwrap();
+            let file = files.entries.entry(path.clone()).or_default();
+            // The game changed it while it was downloading.
+            if file.dirty {
+                continue;
+            }
+            match self.write(&path, &data) {
+                Ok(()) => file.etag = Some(etag),
+                Err(err) => {
+                    debug::warn(&format!("Couldn't write {} from the cloud: {}", path, err))
+                }
+            }
+        }
+
+        let mut files = self.shared.files.lock().unwrap();
+        files.entries.retain(|path, file| {
+            if file.dirty || remote.contains_key(path) {
+                return true;
+            }
+            // Files that were never synced were never here, but synced ones were removed elsewhere.
+            if file.etag.is_some() {
+                let _ = fs::remove_file(self.root.join(path));
+            }
+            false
+        });
+
+        Ok(())
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        let full_path = self.root.join(path);
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.download_path, data)?;
+        fs::rename(&self.download_path, full_path)
+    }
+
+    /// Lists every file on the server with its etag, or None if the listing can't be read.
+    fn list(&self) -> Result<Option<HashMap<String, String>>, reqwest::Error> {
+        let response = self
+            .authorize(self.client.request(propfind(), self.base.clone()))
+            .header("Depth", "infinity")
+            .send()?;
+        // Nothing has been saved yet.
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Some(HashMap::new()));
+        }
+        let body = response.error_for_status()?.text()?;
+        Ok(parse_multistatus(&body, &percent_decode(self.base.path())))
+    }
+
+    fn url(&self, path: &str) -> Url {
+        self.base.join(&percent_encode(path)).unwrap()
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.authorize(self.client.request(method, self.url(path)))
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &*self.shared.token.lock().unwrap() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+/// Reads the files out of a PROPFIND response, keyed by their path under `base_path`.
+fn parse_multistatus(xml: &str, base_path: &str) -> Option<HashMap<String, String>> {
+    let document = roxmltree::Document::parse(xml).ok()?;
+    let mut files = HashMap::new();
+    for response in document
+        .descendants()
+        .filter(|node| node.has_tag_name((DAV, "response")))
+    {
+        if response
+            .descendants()
+            .any(|node| node.has_tag_name((DAV, "collection")))
+        {
+            continue;
+        }
+
+        let href = response
+            .descendants()
+            .find(|node| node.has_tag_name((DAV, "href")))?
+            .text()?
+            .trim();
+        // Hrefs can be full urls or just paths.
+        let href = match Url::parse(href) {
+            Ok(url) => percent_decode(url.path()),
+            Err(_) => percent_decode(href),
+        };
+        let path = match href.strip_prefix(base_path) {
+            Some(path) => path.to_owned(),
+            None => continue,
+        };
+        let etag = response
+            .descendants()
+            .find(|node| node.has_tag_name((DAV, "getetag")))
+            .and_then(|node| node.text())
+            .unwrap_or("")
+            .trim()
+            .to_owned();
+        files.insert(path, etag);
+    }
+    Some(files)
+}
+
+fn etag(response: &Response) -> Option<String> {
+    let etag = response.headers().get(header::ETAG)?;
+    etag.to_str().ok().map(str::to_owned)
+}
+
+fn propfind() -> Method {
+    Method::from_bytes(b"PROPFIND").unwrap()
+}
+
+fn mkcol() -> Method {
+    Method::from_bytes(b"MKCOL").unwrap()
+}
+
+/// The key of a storage device path, which is relative to the game's collection on the server.
+fn key(path: &str) -> String {
+    path.trim_start_matches('/').to_owned()
+}
+
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = encoded
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    use rune_cloud_server::CloudServer;
+
+    use super::*;
+
+    /// A directory for one test, which is removed afterwards.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            let dir = std::env::temp_dir().join(format!("rune-cloud-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn percent_encode_keeps_slashes() {
+        assert_eq!(percent_encode("saves/slot 1.json"), "saves/slot%201.json");
+        assert_eq!(percent_encode("héros~_-.txt"), "h%C3%A9ros~_-.txt");
+        assert_eq!(percent_encode("100%?#"), "100%25%3F%23");
+    }
+
+    #[test]
+    fn percent_decode_round_trips() {
+        assert_eq!(percent_decode("saves/slot%201.json"), "saves/slot 1.json");
+        assert_eq!(percent_decode("h%C3%A9ros"), "héros");
+        assert_eq!(percent_decode("a%2fb"), "a/b");
+        for path in ["a/b c/d%e.txt", "100%", "~tilde_-.", "中文/セーブ"] {
+            assert_eq!(percent_decode(&percent_encode(path)), path);
+        }
+    }
+
+    #[test]
+    fn percent_decode_keeps_broken_escapes() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("%%41"), "%A");
+    }
+
+    #[test]
+    fn parse_multistatus_lists_files_under_the_base() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response><d:href>/game/player/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat></d:response>
+  <d:response><d:href>/game/player/save.json</d:href><d:propstat><d:prop><d:resourcetype/><d:getetag> "abc" </d:getetag></d:prop></d:propstat></d:response>
+  <d:response><d:href>https://saves.example.com/game/player/slot%201/save.json</d:href><d:propstat><d:prop><d:getetag>"def"</d:getetag></d:prop></d:propstat></d:response>
+  <d:response><d:href>/game/player/no-etag</d:href></d:response>
+  <d:response><d:href>/game/other/save.json</d:href><d:propstat><d:prop><d:getetag>"ghi"</d:getetag></d:prop></d:propstat></d:response>
+</d:multistatus>"#;
+        let files = parse_multistatus(xml, "/game/player/").unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files["save.json"], "\"abc\"");
+        assert_eq!(files["slot 1/save.json"], "\"def\"");
+        assert_eq!(files["no-etag"], "");
+    }
+
+    #[test]
+    fn parse_multistatus_rejects_broken_listings() {
+        assert!(parse_multistatus("<d:multistatus xmlns:d=\"DAV:\">", "/").is_none());
+        // A file without an href can't be placed, so the listing can't be trusted to be complete.
+        let xml = r#"<d:multistatus xmlns:d="DAV:"><d:response><d:propstat/></d:response></d:multistatus>"#;
+        assert!(parse_multistatus(xml, "/").is_none());
+    }
+
+    #[test]
+    fn files_are_kept_between_runs() {
+        let dir = TempDir::new();
+        let state_path = dir.0.join("state");
+        let mut files = Files {
+            entries: HashMap::new(),
+            state_path: state_path.clone(),
+        };
+        let file = |etag: Option<&str>, dirty, deleted| FileState {
+            etag: etag.map(str::to_owned),
+            dirty,
+            deleted,
+            revision: 7,
+        };
+        files
+            .entries
+            .insert("synced.json".to_owned(), file(Some("\"1\""), false, false));
+        files.entries.insert(
+            "slot 1/changed\twith a tab.json".to_owned(),
+            file(Some("\"2\""), true, false),
+        );
+        files
+            .entries
+            .insert("removed.json".to_owned(), file(Some("\"3\""), true, true));
+        files
+            .entries
+            .insert("new.json".to_owned(), file(None, true, false));
+        files.save();
+
+        let loaded = Files::load(&state_path);
+        assert_eq!(loaded.len(), 4);
+        for (path, file) in &files.entries {
+            let loaded = &loaded[path];
+            assert_eq!(loaded.etag, file.etag, "{}", path);
+            assert_eq!(loaded.dirty, file.dirty, "{}", path);
+            assert_eq!(loaded.deleted, file.deleted, "{}", path);
+        }
+    }
+
+    #[test]
+    fn missing_or_broken_state_loads_nothing() {
+        let dir = TempDir::new();
+        let state_path = dir.0.join("state");
+        assert!(Files::load(&state_path).is_empty());
+        fs::write(&state_path, "synced\n\nnot a state line\n").unwrap();
+        assert!(Files::load(&state_path).is_empty());
+    }
+
+    /// Writes a file the way the storage device does.
+    fn write(cloud: &CloudSync, path: &str, data: &str) {
+        fs::write(cloud.root.join(path), data).unwrap();
+        cloud.changed(&format!("/{}", path), false);
+    }
+
+    fn read(path: PathBuf) -> String {
+        fs::read_to_string(path).unwrap_or_default()
+    }
+
+    fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !done() {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "Timed out waiting until {}",
+                what
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn sends_offline_changes_and_keeps_conflicts_aside() {
+        let dir = TempDir::new();
+        // Nothing listens on the port until the server starts.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = CloudConfig {
+            url: format!("http://127.0.0.1:{}/game", port),
+            conflicts: CloudConflicts::VersionCheck,
+        };
+
+        let first =
+            CloudSync::start(config.clone(), "player one", None, &dir.0.join("first")).unwrap();
+        write(&first, "save.json", "offline");
+        thread::sleep(Duration::from_millis(200));
+        assert!(!first.online());
+        assert_eq!(first.pending(), 1);
+
+        let server =
+            CloudServer::new(&format!("127.0.0.1:{}", port), dir.0.join("server"), None).unwrap();
+        thread::spawn(move || server.run());
+        // Another change wakes the sync thread, rather than waiting for the next interval.
+        first.changed("/save.json", false);
+        wait_until("the offline change is sent", || {
+            first.online() && first.pending() == 0
+        });
+        let on_server = dir.0.join("server/game/player one/save.json");
+        assert_eq!(read(on_server.clone()), "offline");
+
+        let second = CloudSync::start(config, "player one", None, &dir.0.join("second")).unwrap();
+        wait_until("the second machine downloads the file", || {
+            read(second.root.join("save.json")) == "offline"
+        });
+        write(&second, "save.json", "second machine");
+        wait_until("the second machine sends its change", || {
+            second.pending() == 0
+        });
+        assert_eq!(read(on_server.clone()), "second machine");
+
+        // The first machine's change was made to a version that's been replaced since.
+        write(&first, "save.json", "first machine");
+        wait_until("the first machine's change conflicts", || {
+            first.conflicts() == ["save.json"]
+                && read(first.root.join("save.json")) == "second machine"
+        });
+        assert_eq!(read(first.root.join("save.json.conflict")), "first machine");
+        assert_eq!(read(on_server), "second machine");
+        assert_eq!(first.pending(), 0);
+    }
+}
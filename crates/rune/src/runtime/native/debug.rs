@@ -3,28 +3,44 @@ use std::io::Write;
 use super::state::RuneRuntimeState;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
+pub(crate) fn log(msg: &str) {
+    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    stdout
+        .set_color(ColorSpec::new().set_fg(Some(Color::Rgb(127, 127, 127))))
+        .unwrap();
+    writeln!(&mut stdout, "{}", msg).unwrap();
+}
+
+pub(crate) fn warn(msg: &str) {
+    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    stdout
+        .set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))
+        .unwrap();
+    writeln!(&mut stdout, "{}", msg).unwrap();
+}
+
+pub(crate) fn error(msg: &str) {
+    let mut stderr = StandardStream::stderr(ColorChoice::Always);
+    stderr
+        .set_color(ColorSpec::new().set_fg(Some(Color::Red)))
+        .unwrap();
+    writeln!(&mut stderr, "{}", msg).unwrap();
+}
+
 impl crate::rune::runtime::debug::Host for RuneRuntimeState {
     async fn log(&mut self, msg: String) {
-        let mut stdout = StandardStream::stdout(ColorChoice::Always);
-        stdout
-            .set_color(ColorSpec::new().set_fg(Some(Color::Rgb(127, 127, 127))))
-            .unwrap();
-        writeln!(&mut stdout, "{}", msg).unwrap();
+        log(&msg);
     }
 
     async fn warn(&mut self, msg: String) {
-        let mut stdout = StandardStream::stdout(ColorChoice::Always);
-        stdout
-            .set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))
-            .unwrap();
-        writeln!(&mut stdout, "{}", msg).unwrap();
+        warn(&msg);
     }
 
     async fn error(&mut self, msg: String) {
-        let mut stderr = StandardStream::stderr(ColorChoice::Always);
-        stderr
-            .set_color(ColorSpec::new().set_fg(Some(Color::Red)))
-            .unwrap();
-        writeln!(&mut stderr, "{}", msg).unwrap();
+        error(&msg);
+    }
+
+    async fn gpu_resources(&mut self) -> Vec<crate::debug::GpuResource> {
+        self.gpu_state.live_resources()
     }
 }
@@ -1,30 +1,616 @@
-use std::{borrow::Cow, collections::HashMap, num::NonZeroU64};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    error::Error,
+    num::NonZeroU64,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use wasmtime::{component::Resource, Result};
 use wgpu_core::{
     binding_model::{BindGroupLayoutDescriptor, PipelineLayoutDescriptor},
-    command::PassChannel,
+    command::{PassChannel, PassTimestampWrites},
+    device::{DeviceError, HostMap},
+    id::{Id, Marker},
     pipeline::VertexBufferLayout,
     resource::{BufferMapOperation, TextureViewDescriptor},
 };
 use wgpu_types::{
     BindGroupLayoutEntry, Color, ColorTargetState, ColorWrites, DepthBiasState, DepthStencilState,
-    DynamicOffset, Face, FrontFace, ImageCopyTexture, ImageDataLayout, ImageSubresourceRange,
-    MultisampleState, Origin3d, PolygonMode, PrimitiveState, PrimitiveTopology, StencilFaceState,
-    StencilState, VertexAttribute,
+    DynamicOffset, Extent3d, Face, FrontFace, ImageCopyTexture, ImageDataLayout,
+    ImageSubresourceRange, MultisampleState, Origin3d, PolygonMode, PrimitiveState,
+    PrimitiveTopology, StencilFaceState, StencilState, TextureFormat, VertexAttribute,
 };
 
 use crate::{
     rune::runtime::gpu::*,
-    runtime::gpu::{Buffer, QuerySet, Texture},
-    wgpu_id,
+    runtime::gpu::{
+        Buffer, ErrorScope, GpuProfiler, ProfiledPass, ProfilerFrame, QuerySet, RenderScaling,
+        Texture, TrackedResource, PROFILER_FRAMES, PROFILER_MAX_PASSES,
+    },
 };
+use crate::debug::GpuResourceKind;
 
+use super::debug;
+use super::gpu_backend::{check, GpuCallError, NullGpuError, SHADER_CAPABILITIES};
 use super::state::RuneRuntimeState;
+use super::texture_decoder::{self, DecodeOptions, DecodedImage};
 
 use super::utilities::{convert_bind_group_entry, vec_to_color};
 
-impl RuneRuntimeState {}
+impl RuneRuntimeState {
+    /// Unwraps the result of a wgpu-core create call, reporting any error to the error scopes.
+    /// wgpu-core hands back an invalid id on failure, which the guest receives like WebGPU would.
+    pub(super) fn gpu_id<I: Marker, E: Into<GpuCallError>>(
+        &mut self,
+        label: Option<&str>,
+        (id, error): (Id<I>, Option<E>),
+    ) -> Id<I> {
+        if let Some(error) = error {
+            self.report_gpu_error(label, &*error.into());
+        }
+        id
+    }
+
+    /// Unwraps the result of a wgpu-core call, reporting any error to the error scopes.
+    pub(super) fn gpu_result<T, E: Into<GpuCallError>>(
+        &mut self,
+        label: Option<&str>,
+        result: core::result::Result<T, E>,
+    ) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.report_gpu_error(label, &*error.into());
+                None
+            }
+        }
+    }
+
+    /// Captures the error in the innermost matching error scope, or logs it as uncaptured.
+    pub(super) fn report_gpu_error(&mut self, label: Option<&str>, error: &(dyn Error + 'static)) {
+        let filter = gpu_error_filter(error);
+        let mut message = error.to_string();
+        let mut source = error.source();
+        while let Some(inner) = source {
+            message.push_str(&format!(": {}", inner));
+            source = inner.source();
+        }
+
+        let scope = self
+            .gpu_state
+            .error_scopes
+            .iter_mut()
+            .rev()
+            .find(|scope| scope.filter == filter);
+
+        match scope {
+            // Only the first error in a scope is kept, matching WebGPU.
+            Some(scope) => {
+                if scope.error.is_none() {
+                    scope.error = Some(match filter {
+                        GpuErrorFilter::Validation => GpuError::Validation(message),
+                        GpuErrorFilter::OutOfMemory => GpuError::OutOfMemory(message),
+                        GpuErrorFilter::Internal => GpuError::Internal(message),
+                    });
+                }
+            }
+            None => {
+                self.gpu_state.uncaptured_errors += 1;
+                match label {
+                    Some(label) => debug::error(&format!("Uncaptured GPU error in '{}': {}", label, message)),
+                    None => debug::error(&format!("Uncaptured GPU error: {}", message)),
+                }
+            }
+        }
+    }
+
+    /// Records a resource handed to the guest so it shows up in the live-resource report until dropped.
+    fn track_gpu_resource<T: 'static>(
+        &mut self,
+        resource: Resource<T>,
+        kind: GpuResourceKind,
+        label: Option<String>,
+        size: u64,
+    ) -> Resource<T> {
+        self.gpu_state.resources.insert(
+            resource.rep(),
+            TrackedResource {
+                kind,
+                label,
+                size,
+                created_frame: self.gpu_state.frame,
+            },
+        );
+        resource
+    }
+
+    /// The label a guest resource was created with, for reporting errors that involve it.
+    pub(super) fn gpu_label<T: 'static>(&self, resource: &Resource<T>) -> Option<String> {
+        match self.gpu_state.resources.get(&resource.rep()) {
+            Some(tracked) => tracked.label.clone(),
+            None => self.gpu_state.labels.get(&resource.rep()).cloned(),
+        }
+    }
+
+    /// Keeps what the guest can ask about a texture, and hands it to the guest.
+    fn push_texture(
+        &mut self,
+        device: &Resource<GpuDevice>,
+        texture_id: wgpu_core::id::TextureId,
+        texture_descriptor: &wgpu_core::resource::TextureDescriptor,
+    ) -> Resource<GpuTexture> {
+        let texture = Texture {
+            height: texture_descriptor.size.height,
+            width: texture_descriptor.size.width,
+            depth_or_array_layers: texture_descriptor.size.depth_or_array_layers,
+            dimension: texture_descriptor.dimension,
+            format: texture_descriptor.format,
+            mip_level_count: texture_descriptor.mip_level_count,
+            sample_count: texture_descriptor.sample_count,
+            usage: texture_descriptor.usage,
+        };
+        let size = texture.estimated_size();
+        self.gpu_state.textures.insert(texture_id, texture);
+
+        let texture = self.table.push_child(texture_id, device).unwrap();
+        let label = texture_descriptor.label.as_deref().map(str::to_owned);
+        self.track_gpu_resource(texture, GpuResourceKind::Texture, label, size)
+    }
+
+    /// Writes each mip level of a decoded image into a texture, the first at `mip_level` and
+    /// `origin`.
+    fn write_decoded_image(
+        &mut self,
+        texture_id: wgpu_core::id::TextureId,
+        label: Option<&str>,
+        mip_level: u32,
+        origin: Origin3d,
+        image: &DecodedImage,
+    ) {
+        let (block_width, block_height) = image.format.block_dimensions();
+        let block_size = image.format.block_copy_size(None).unwrap_or(4);
+
+        for (mip, data) in image.mips.iter().enumerate() {
+            let mip = mip as u32;
+            let size = image.size.mip_level_size(mip, wgpu_types::TextureDimension::D2);
+            let origin = Origin3d {
+                x: origin.x >> mip,
+                y: origin.y >> mip,
+                z: origin.z,
+            };
+            let queue_id = self.queue;
+            let result = self.gpu.call(
+                || {
+                    format!(
+                        "write-texture {{ texture: {:?}, mip_level: {}, origin: {:?}, size: {:?} }}",
+                        texture_id,
+                        mip_level + mip,
+                        origin,
+                        size
+                    )
+                },
+                |global| {
+                    global.queue_write_texture(
+                        queue_id,
+                        &ImageCopyTexture {
+                            texture: texture_id,
+                            mip_level: mip_level + mip,
+                            origin,
+                            aspect: wgpu_types::TextureAspect::All,
+                        },
+                        data,
+                        &ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: Some(size.width.div_ceil(block_width) * block_size),
+                            rows_per_image: Some(size.height.div_ceil(block_height)),
+                        },
+                        // Compressed levels smaller than a block are still copied a whole block at a time.
+                        &size.physical_size(image.format),
+                    )
+                },
+            );
+            self.gpu_result(label, result);
+        }
+    }
+
+    /// Turns the GPU profiler on or off, creating its query sets and buffers the first time.
+    pub fn set_gpu_profiling(&mut self, enabled: bool) {
+        if let Some(profiler) = self.gpu_state.profiler.as_mut() {
+            profiler.enabled = enabled;
+            return;
+        }
+        if !enabled {
+            return;
+        }
+        let Some(global) = self.gpu.wgpu() else {
+            debug::warn("GPU profiling isn't available with the null GPU backend");
+            return;
+        };
+        if !global
+            .device_features(self.device)
+            .contains(wgpu_types::Features::TIMESTAMP_QUERY)
+        {
+            debug::warn("GPU profiling needs timestamp queries, which this GPU doesn't support");
+            return;
+        }
+
+        let query_count = PROFILER_MAX_PASSES * 2;
+        let size = query_count as u64 * wgpu_types::QUERY_SIZE as u64;
+        let frames = (0..PROFILER_FRAMES)
+            .map(|_| ProfilerFrame {
+                query_set: self.gpu_id(
+                    Some("profiler"),
+                    global.device_create_query_set(
+                        self.device,
+                        &wgpu_core::resource::QuerySetDescriptor {
+                            label: Some("profiler".into()),
+                            ty: wgpu_types::QueryType::Timestamp,
+                            count: query_count,
+                        },
+                        None,
+                    ),
+                ),
+                resolve_buffer: self.gpu_id(
+                    Some("profiler"),
+                    global.device_create_buffer(
+                        self.device,
+                        &wgpu_core::resource::BufferDescriptor {
+                            label: Some("profiler resolve".into()),
+                            size,
+                            usage: wgpu_types::BufferUsages::QUERY_RESOLVE
+                                | wgpu_types::BufferUsages::COPY_SRC,
+                            mapped_at_creation: false,
+                        },
+                        None,
+                    ),
+                ),
+                readback_buffer: self.gpu_id(
+                    Some("profiler"),
+                    global.device_create_buffer(
+                        self.device,
+                        &wgpu_core::resource::BufferDescriptor {
+                            label: Some("profiler readback".into()),
+                            size,
+                            usage: wgpu_types::BufferUsages::MAP_READ
+                                | wgpu_types::BufferUsages::COPY_DST,
+                            mapped_at_creation: false,
+                        },
+                        None,
+                    ),
+                ),
+                passes: Vec::new(),
+                mapped: Arc::new(AtomicBool::new(false)),
+                pending: false,
+            })
+            .collect();
+
+        self.gpu_state.profiler = Some(GpuProfiler {
+            enabled: true,
+            frames,
+            current: 0,
+            active_passes: HashMap::new(),
+            timestamp_period: global.queue_get_timestamp_period(self.queue),
+            last_timings: Vec::new(),
+            stats: Vec::new(),
+        });
+    }
+
+    /// Reserves a pair of timestamp queries for a pass that's about to begin.
+    fn profiler_begin_pass(&mut self, kind: &str, label: Option<String>) -> Option<(usize, PassTimestampWrites)> {
+        let profiler = self.gpu_state.profiler.as_mut().filter(|p| p.enabled)?;
+        let frame = &mut profiler.frames[profiler.current];
+        // The frame's queries are still being read back, so this frame goes untimed.
+        if frame.pending || frame.passes.len() as u32 >= PROFILER_MAX_PASSES {
+            return None;
+        }
+
+        let index = frame.passes.len();
+        frame.passes.push(ProfiledPass {
+            named: label.is_some(),
+            name: label.unwrap_or_else(|| format!("{} pass {}", kind, index)),
+        });
+
+        Some((
+            index,
+            PassTimestampWrites {
+                query_set: frame.query_set,
+                beginning_of_pass_write_index: Some(index as u32 * 2),
+                end_of_pass_write_index: Some(index as u32 * 2 + 1),
+            },
+        ))
+    }
+
+    /// Names a profiled pass after the first debug group pushed in it.
+    fn profiler_name_pass(&mut self, pass: u32, group_label: &str) {
+        let Some(profiler) = self.gpu_state.profiler.as_mut() else {
+            return;
+        };
+        let Some(&index) = profiler.active_passes.get(&pass) else {
+            return;
+        };
+
+        let profiled = &mut profiler.frames[profiler.current].passes[index];
+        if !profiled.named {
+            profiled.name = group_label.to_owned();
+            profiled.named = true;
+        }
+    }
+
+    /// Collects timings from earlier frames whose readback has finished, then resolves this
+    /// frame's timestamps and starts reading them back. Called once a frame, after the guest renders.
+    pub fn end_gpu_profiler_frame(&mut self) {
+        let Some(mut profiler) = self.gpu_state.profiler.take() else {
+            return;
+        };
+        // The profiler is only ever turned on with wgpu.
+        let global = self.gpu.wgpu().unwrap();
+
+        let result = global.device_poll(self.device, wgpu_types::Maintain::Poll);
+        self.gpu_result(Some("profiler"), result);
+
+        for i in 0..profiler.frames.len() {
+            let frame = &profiler.frames[i];
+            if !frame.pending || !frame.mapped.load(Ordering::Acquire) {
+                continue;
+            }
+
+            let readback_buffer = frame.readback_buffer;
+            let size = (frame.passes.len() * 2) as u64 * wgpu_types::QUERY_SIZE as u64;
+            let result = global.buffer_get_mapped_range(readback_buffer, 0, Some(size));
+            if let Some((mapped_range, _)) = self.gpu_result(Some("profiler"), result) {
+                let bytes = unsafe { std::slice::from_raw_parts(mapped_range.as_ptr(), size as usize) };
+                let period = profiler.timestamp_period as f64;
+                let timings = frame
+                    .passes
+                    .iter()
+                    .zip(bytes.chunks_exact(wgpu_types::QUERY_SIZE as usize * 2))
+                    .map(|(pass, timestamps)| {
+                        let begin = u64::from_le_bytes(timestamps[..8].try_into().unwrap());
+                        let end = u64::from_le_bytes(timestamps[8..].try_into().unwrap());
+                        (pass.name.clone(), end.saturating_sub(begin) as f64 * period / 1_000_000.0)
+                    })
+                    .collect();
+                profiler.record_timings(timings);
+            }
+            global.buffer_unmap(readback_buffer).ok();
+
+            let frame = &mut profiler.frames[i];
+            frame.passes.clear();
+            frame.mapped.store(false, Ordering::Release);
+            frame.pending = false;
+        }
+
+        let current = profiler.current;
+        let frame = &profiler.frames[current];
+        if !frame.pending && !frame.passes.is_empty() {
+            let query_count = frame.passes.len() as u32 * 2;
+            let size = query_count as u64 * wgpu_types::QUERY_SIZE as u64;
+            let (query_set, resolve_buffer, readback_buffer) =
+                (frame.query_set, frame.resolve_buffer, frame.readback_buffer);
+            let mapped = frame.mapped.clone();
+
+            let command_encoder_id = self.gpu_id(
+                Some("profiler"),
+                global.device_create_command_encoder(
+                    self.device,
+                    &wgpu_types::CommandEncoderDescriptor {
+                        label: Some("profiler".into()),
+                    },
+                    None,
+                ),
+            );
+            let result = global.command_encoder_resolve_query_set(
+                command_encoder_id,
+                query_set,
+                0,
+                query_count,
+                resolve_buffer,
+                0,
+            );
+            self.gpu_result(Some("profiler"), result);
+            let result = global.command_encoder_copy_buffer_to_buffer(
+                command_encoder_id,
+                resolve_buffer,
+                0,
+                readback_buffer,
+                0,
+                size,
+            );
+            self.gpu_result(Some("profiler"), result);
+            let command_buffer_id = self.gpu_id(
+                Some("profiler"),
+                global.command_encoder_finish(
+                    command_encoder_id,
+                    &wgpu_types::CommandBufferDescriptor { label: None },
+                ),
+            );
+            let result = global
+                .queue_submit(self.queue, &[command_buffer_id])
+                .map_err(|(_, error)| error);
+            self.gpu_result(Some("profiler"), result);
+
+            let result = global.buffer_map_async(
+                readback_buffer,
+                0,
+                Some(size),
+                BufferMapOperation {
+                    host: HostMap::Read,
+                    callback: Some(Box::new(move |_| mapped.store(true, Ordering::Release))),
+                },
+            );
+            let frame = &mut profiler.frames[current];
+            match self.gpu_result(Some("profiler"), result) {
+                Some(_) => {
+                    frame.pending = true;
+                    profiler.current = (current + 1) % PROFILER_FRAMES;
+                }
+                None => frame.passes.clear(),
+            }
+        }
+
+        profiler.active_passes.clear();
+        self.gpu_state.profiler = Some(profiler);
+    }
+
+    /// Reads back the current surface texture as tightly packed RGBA8 pixels.
+    /// This blocks until the GPU has finished all submitted work.
+    pub fn capture_surface_texture(&mut self) -> Option<GpuFrameCapture> {
+        // There are no pixels to read back without a GPU.
+        let global = self.gpu.wgpu()?;
+
+        let texture_id = self.gpu_state.surface_texture?;
+        // With render scaling the frame is the offscreen target, at the game's own resolution.
+        let (width, height, format) = match self.gpu_state.textures.get(&texture_id) {
+            Some(texture) => (texture.width, texture.height, texture.format),
+            None => (
+                self.surface_config.width,
+                self.surface_config.height,
+                self.surface_config.format,
+            ),
+        };
+
+        match format {
+            TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::Bgra8UnormSrgb => {}
+            _ => {
+                debug::warn(&format!("Frame capture is not supported for surface format {:?}", format));
+                return None;
+            }
+        }
+
+        let unpadded_bytes_per_row = width * 4;
+        let align = wgpu_types::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
+        let size = padded_bytes_per_row as u64 * height as u64;
+
+        let buffer_id = self.gpu_id(
+            Some("frame capture"),
+            global.device_create_buffer(
+                self.device,
+                &wgpu_core::resource::BufferDescriptor {
+                    label: Some("frame capture".into()),
+                    size,
+                    usage: wgpu_types::BufferUsages::MAP_READ | wgpu_types::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+                None,
+            ),
+        );
+
+        let command_encoder_id = self.gpu_id(
+            Some("frame capture"),
+            global.device_create_command_encoder(
+                self.device,
+                &wgpu_types::CommandEncoderDescriptor {
+                    label: Some("frame capture".into()),
+                },
+                None,
+            ),
+        );
+
+        let result = global.command_encoder_copy_texture_to_buffer(
+            command_encoder_id,
+            &ImageCopyTexture {
+                texture: texture_id,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: wgpu_types::TextureAspect::All,
+            },
+            &wgpu_core::command::ImageCopyBuffer {
+                buffer: buffer_id,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            &Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.gpu_result(Some("frame capture"), result);
+
+        let command_buffer_id = self.gpu_id(
+            Some("frame capture"),
+            global.command_encoder_finish(
+                command_encoder_id,
+                &wgpu_types::CommandBufferDescriptor { label: None },
+            ),
+        );
+
+        let result = global
+            .queue_submit(self.queue, &[command_buffer_id])
+            .map_err(|(_, error)| error);
+        self.gpu_result(Some("frame capture"), result);
+
+        let result = global.buffer_map_async(
+            buffer_id,
+            0,
+            Some(size),
+            BufferMapOperation {
+                host: HostMap::Read,
+                callback: None,
+            },
+        );
+        let mapped = self.gpu_result(Some("frame capture"), result).is_some();
+
+        let result = global.device_poll(self.device, wgpu_types::Maintain::Wait);
+        self.gpu_result(Some("frame capture"), result);
+
+        let mut data = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        if mapped {
+            let result = global.buffer_get_mapped_range(buffer_id, 0, Some(size));
+            if let Some((mapped_range, _)) = self.gpu_result(Some("frame capture"), result) {
+                let bytes = unsafe { std::slice::from_raw_parts(mapped_range.as_ptr(), size as usize) };
+                for row in bytes.chunks(padded_bytes_per_row as usize) {
+                    data.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+                }
+            }
+            global.buffer_unmap(buffer_id).ok();
+        }
+        global.buffer_drop(buffer_id);
+
+        if data.is_empty() {
+            return None;
+        }
+
+        if matches!(format, TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb) {
+            for pixel in data.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        Some(GpuFrameCapture {
+            width,
+            height,
+            data,
+        })
+    }
+}
+
+/// Classifies a wgpu-core error by walking its source chain, the same way wgpu does.
+fn gpu_error_filter(error: &(dyn Error + 'static)) -> GpuErrorFilter {
+    let mut source = Some(error);
+    while let Some(inner) = source {
+        match inner.downcast_ref::<DeviceError>() {
+            Some(DeviceError::OutOfMemory) => return GpuErrorFilter::OutOfMemory,
+            Some(DeviceError::Lost | DeviceError::ResourceCreationFailed) => {
+                return GpuErrorFilter::Internal
+            }
+            _ => {}
+        }
+        source = inner.source();
+    }
+    GpuErrorFilter::Validation
+}
 
 impl Host for RuneRuntimeState {
     async fn surface(&mut self) -> Resource<GpuSurface> {
@@ -43,18 +629,131 @@ impl Host for RuneRuntimeState {
         // Ok(self.table.push(adapter_id).unwrap())
         Resource::new_own(self.adapter_resource_id)
     }
+
+    async fn capture_frame(&mut self) -> Option<GpuFrameCapture> {
+        self.capture_surface_texture()
+    }
+
+    async fn recorded_commands(&mut self) -> Vec<String> {
+        self.gpu.take_commands()
+    }
+
+    async fn set_profiling(&mut self, enabled: bool) {
+        self.set_gpu_profiling(enabled);
+    }
+
+    async fn profiler_timings(&mut self) -> Vec<GpuPassTiming> {
+        match self.gpu_state.profiler.as_ref() {
+            Some(profiler) => profiler
+                .last_timings
+                .iter()
+                .map(|(name, milliseconds)| GpuPassTiming {
+                    name: name.clone(),
+                    milliseconds: *milliseconds,
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
 }
 
 impl HostGpuSurface for RuneRuntimeState {
-    async fn current_texture(&mut self, _surface: Resource<GpuSurface>) -> Resource<GpuTexture> {
-        let surface_output = self
-            .instance
-            .surface_get_current_texture(self.surface, None)
-            .unwrap();
+    async fn current_texture(
+        &mut self,
+        _surface: Resource<GpuSurface>,
+    ) -> Result<Resource<GpuTexture>, GpuSurfaceError> {
+        if self.gpu_state.render_scaling.is_some() {
+            let texture_id = self.render_scaling_target();
+            self.gpu_state.surface_texture = Some(texture_id);
+            self.gpu_state.present_surface = true;
+            return Ok(self.table.push(texture_id).unwrap());
+        }
 
+        let texture_id = self.acquire_surface_texture()?;
+        self.gpu_state.surface_texture = Some(texture_id);
         self.gpu_state.present_surface = true;
 
-        self.table.push(surface_output.texture_id.unwrap()).unwrap()
+        Ok(self.table.push(texture_id).unwrap())
+    }
+
+    async fn get_capabilities(
+        &mut self,
+        _surface: Resource<GpuSurface>,
+        adapter: Resource<GpuAdapter>,
+    ) -> GpuSurfaceCapabilities {
+        let adapter_id = *self.table.get(&adapter).unwrap();
+        let capabilities = self.gpu.surface_capabilities(self.surface, adapter_id);
+
+        GpuSurfaceCapabilities {
+            formats: capabilities.formats.into_iter().map(|f| f.into()).collect(),
+            present_modes: capabilities.present_modes.into_iter().map(|m| m.into()).collect(),
+            alpha_modes: capabilities.alpha_modes.into_iter().map(|m| m.into()).collect(),
+            usages: capabilities.usages.into(),
+        }
+    }
+
+    async fn configure(
+        &mut self,
+        _surface: Resource<GpuSurface>,
+        configuration: GpuSurfaceConfiguration,
+    ) -> () {
+        let capabilities = self.gpu.surface_capabilities(self.surface, self.adapter);
+
+        let usage: wgpu_types::TextureUsages = configuration.usage.into();
+        let surface_config = wgpu_types::SurfaceConfiguration {
+            // Frame capture copies out of the surface texture, so keep COPY_SRC where the surface allows it.
+            usage: usage
+                | wgpu_types::TextureUsages::RENDER_ATTACHMENT
+                | (capabilities.usages & wgpu_types::TextureUsages::COPY_SRC),
+            format: configuration.format.into(),
+            width: self.surface_config.width,
+            height: self.surface_config.height,
+            present_mode: configuration.present_mode.into(),
+            alpha_mode: configuration.alpha_mode.into(),
+            view_formats: configuration
+                .view_formats
+                .into_iter()
+                .map(|f| f.into())
+                .collect(),
+            desired_maximum_frame_latency: configuration.desired_maximum_frame_latency,
+        };
+
+        let (surface_id, device_id) = (self.surface, self.device);
+        let result = self.gpu.call(
+            || format!("configure {:?}", surface_config),
+            |global| global.surface_configure(surface_id, device_id, &surface_config).map_or(Ok(()), Err),
+        );
+        if self.gpu_result(Some("surface"), result).is_some() {
+            self.surface_config = surface_config;
+        }
+        ()
+    }
+
+    async fn get_configuration(&mut self, _surface: Resource<GpuSurface>) -> GpuSurfaceConfiguration {
+        GpuSurfaceConfiguration {
+            format: self.surface_config.format.into(),
+            usage: self.surface_config.usage.into(),
+            present_mode: self.surface_config.present_mode.into(),
+            alpha_mode: self.surface_config.alpha_mode.into(),
+            view_formats: self
+                .surface_config
+                .view_formats
+                .iter()
+                .map(|f| (*f).into())
+                .collect(),
+            desired_maximum_frame_latency: self.surface_config.desired_maximum_frame_latency,
+        }
+    }
+
+    async fn set_render_scaling(&mut self, _surface: Resource<GpuSurface>, scaling: Option<GpuRenderScaling>) {
+        self.set_scaling(scaling.map(|scaling| RenderScaling {
+            width: scaling.width,
+            height: scaling.height,
+            filter: scaling.filter.into(),
+            mode: scaling.mode,
+            target: None,
+            sampler: None,
+        }));
     }
 
     async fn drop(&mut self, _rep: Resource<GpuSurface>) -> Result<()> {
@@ -64,13 +763,13 @@ impl HostGpuSurface for RuneRuntimeState {
 
 impl HostGpuQuerySet for RuneRuntimeState {
     async fn type_(&mut self, query_set: Resource<GpuQuerySet>) -> GpuQueryType {
-        let query_set_id = self.table.get(&query_set).unwrap();
+        let query_set_id = *self.table.get(&query_set).unwrap();
         let query_set = self.gpu_state.query_sets.get(&query_set_id).unwrap();
         query_set.type_.into()
     }
 
     async fn count(&mut self, query_set: Resource<GpuQuerySet>) -> GpuSizeU32Out {
-        let query_set_id = self.table.get(&query_set).unwrap();
+        let query_set_id = *self.table.get(&query_set).unwrap();
         let query_set = self.gpu_state.query_sets.get(&query_set_id).unwrap();
         query_set.count
     }
@@ -78,8 +777,10 @@ impl HostGpuQuerySet for RuneRuntimeState {
     async fn destroy(&mut self, _self_: Resource<GpuQuerySet>) {}
 
     async fn drop(&mut self, rep: Resource<GpuQuerySet>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let query_set_id = self.table.delete(rep).unwrap();
-        self.instance.query_set_drop(query_set_id);
+        self.gpu_state.query_sets.remove(&query_set_id);
+        self.gpu.release(|global| global.query_set_drop(query_set_id));
         Ok(())
     }
 }
@@ -116,61 +817,72 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuBufferDescriptor,
     ) -> Resource<GpuBuffer> {
-        let device_id = self.table.get(&device).unwrap();
-
-        let buffer_descriptor;
-        let buffer_id = if let Some(contents) = descriptor.contents {
-            let unpadded_size = contents.len() as wgpu_types::BufferAddress;
-            // Valid vulkan usage is
-            // 1. buffer size must be a multiple of COPY_BUFFER_ALIGNMENT.
-            // 2. buffer size must be greater than 0.
-            // Therefore we round the value up to the nearest multiple, and ensure it's at least COPY_BUFFER_ALIGNMENT.
-            let align_mask = wgpu_types::COPY_BUFFER_ALIGNMENT - 1;
-            let padded_size =
-                ((unpadded_size + align_mask) & !align_mask).max(wgpu_types::COPY_BUFFER_ALIGNMENT);
-
-            buffer_descriptor = wgpu_core::resource::BufferDescriptor {
+        let device_id = *self.table.get(&device).unwrap();
+        let label = descriptor.label.clone();
+        let usage: wgpu_types::BufferUsages = descriptor.usage.into();
+
+        let buffer_descriptor = match descriptor.contents {
+            Some(ref contents) => {
+                let unpadded_size = contents.len() as wgpu_types::BufferAddress;
+                // Valid vulkan usage is
+                // 1. buffer size must be a multiple of COPY_BUFFER_ALIGNMENT.
+                // 2. buffer size must be greater than 0.
+                // Therefore we round the value up to the nearest multiple, and ensure it's at least COPY_BUFFER_ALIGNMENT.
+                let align_mask = wgpu_types::COPY_BUFFER_ALIGNMENT - 1;
+                let padded_size =
+                    ((unpadded_size + align_mask) & !align_mask).max(wgpu_types::COPY_BUFFER_ALIGNMENT);
+
+                wgpu_core::resource::BufferDescriptor {
+                    label: descriptor.label.map(|s| s.into()),
+                    size: padded_size,
+                    usage,
+                    mapped_at_creation: true,
+                }
+            }
+            None => wgpu_core::resource::BufferDescriptor {
                 label: descriptor.label.map(|s| s.into()),
-                size: padded_size,
-                usage: descriptor.usage.into(),
-                mapped_at_creation: true,
-            };
+                size: descriptor.size,
+                usage,
+                mapped_at_creation: false,
+            },
+        };
 
-            let buffer_id = wgpu_id(self.instance.device_create_buffer(
-                *device_id,
-                &buffer_descriptor,
-                None,
-            ))
-            .unwrap();
+        let created = self.gpu.create_checked(
+            || {
+                format!(
+                    "create-buffer {{ label: {:?}, size: {}, usage: {:?}, mapped_at_creation: {} }}",
+                    buffer_descriptor.label, buffer_descriptor.size, usage, buffer_descriptor.mapped_at_creation
+                )
+            },
+            || {
+                check(
+                    !usage.contains(wgpu_types::BufferUsages::MAP_READ)
+                        || (wgpu_types::BufferUsages::MAP_READ | wgpu_types::BufferUsages::COPY_DST).contains(usage),
+                    || format!("MAP_READ can only be combined with COPY_DST, got {:?}", usage),
+                )?;
+                check(
+                    !usage.contains(wgpu_types::BufferUsages::MAP_WRITE)
+                        || (wgpu_types::BufferUsages::MAP_WRITE | wgpu_types::BufferUsages::COPY_SRC).contains(usage),
+                    || format!("MAP_WRITE can only be combined with COPY_SRC, got {:?}", usage),
+                )
+            },
+            |global| global.device_create_buffer(device_id, &buffer_descriptor, None),
+        );
+        let buffer_id = self.gpu_id(label.as_deref(), created);
 
-            let (buffer, buffer_length) = self
-                .instance
-                .buffer_get_mapped_range(buffer_id, 0, Some(unpadded_size))
-                .unwrap();
+        // Null buffers have no memory behind them to copy the contents into.
+        if let (Some(contents), Some(global)) = (descriptor.contents, self.gpu.wgpu()) {
+            let result = global.buffer_get_mapped_range(buffer_id, 0, Some(contents.len() as u64));
 
-            unsafe {
-                assert!(buffer_length as usize >= contents.len());
-                std::ptr::copy_nonoverlapping(contents.as_ptr(), buffer.as_ptr(), contents.len());
+            if let Some((buffer, buffer_length)) = self.gpu_result(label.as_deref(), result) {
+                unsafe {
+                    assert!(buffer_length as usize >= contents.len());
+                    std::ptr::copy_nonoverlapping(contents.as_ptr(), buffer.as_ptr(), contents.len());
+                }
             }
 
-            self.instance.buffer_unmap(buffer_id).ok();
-
-            buffer_id
-        } else {
-            buffer_descriptor = wgpu_core::resource::BufferDescriptor {
-                label: None,
-                size: descriptor.size,
-                usage: descriptor.usage.into(),
-                mapped_at_creation: false,
-            };
-
-            wgpu_id(self.instance.device_create_buffer(
-                *device_id,
-                &buffer_descriptor,
-                None,
-            ))
-            .unwrap()
-        };
+            global.buffer_unmap(buffer_id).ok();
+        }
 
         self.gpu_state.buffers.insert(
             buffer_id,
@@ -181,7 +893,8 @@ impl HostGpuDevice for RuneRuntimeState {
             },
         );
 
-        self.table.push_child(buffer_id, &device).unwrap()
+        let buffer = self.table.push_child(buffer_id, &device).unwrap();
+        self.track_gpu_resource(buffer, GpuResourceKind::Buffer, label, buffer_descriptor.size)
     }
 
     async fn create_texture(
@@ -189,10 +902,11 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuTextureDescriptor,
     ) -> Resource<GpuTexture> {
-        let device_id = self.table.get(&device).unwrap();
+        let device_id = *self.table.get(&device).unwrap();
 
+        let label = descriptor.label;
         let texture_descriptor = wgpu_core::resource::TextureDescriptor {
-            label: None,
+            label: label.as_deref().map(Cow::from),
             size: descriptor.size.into(),
             mip_level_count: descriptor.mip_level_count,
             sample_count: descriptor.sample_count,
@@ -206,27 +920,73 @@ impl HostGpuDevice for RuneRuntimeState {
                 .collect(),
         };
 
-        let texture_id = wgpu_id(self.instance.device_create_texture(
-            *device_id,
-            &texture_descriptor,
-            None,
-        ))
-        .unwrap();
+        let created = self.gpu.create_checked(
+            || format!("create-texture {:?}", texture_descriptor),
+            || {
+                let size = texture_descriptor.size;
+                check(size.width > 0 && size.height > 0 && size.depth_or_array_layers > 0, || {
+                    format!("Textures must have a non-zero size, got {:?}", size)
+                })?;
+                check(texture_descriptor.mip_level_count > 0, || {
+                    "Textures must have at least one mip level".to_owned()
+                })
+            },
+            |global| global.device_create_texture(device_id, &texture_descriptor, None),
+        );
+        let texture_id = self.gpu_id(label.as_deref(), created);
+
+        self.push_texture(&device, texture_id, &texture_descriptor)
+    }
 
-        self.gpu_state.textures.insert(
-            texture_id,
-            Texture {
-                height: 0,
-                width: 0,
-                dimension: texture_descriptor.dimension,
-                format: texture_descriptor.format,
-                mip_level_count: texture_descriptor.mip_level_count,
-                sample_count: texture_descriptor.sample_count,
-                usage: texture_descriptor.usage,
+    async fn create_texture_from_image(
+        &mut self,
+        device: Resource<GpuDevice>,
+        image: Vec<u8>,
+        usage: GpuTextureUsage,
+        options: GpuImageDecodeOptions,
+        label: Option<String>,
+    ) -> Result<Resource<GpuTexture>, String> {
+        let device_id = *self.table.get(&device).unwrap();
+        let image = texture_decoder::decode(
+            &image,
+            &DecodeOptions {
+                format: None,
+                generate_mipmaps: options.generate_mipmaps,
+                max_mip_level_count: u32::MAX,
+                max_size: self.gpu.device_limits(device_id).max_texture_dimension_2d,
+                flip_y: options.flip_y,
+                srgb: options.srgb,
+                premultiply_alpha: false,
             },
+        )?;
+
+        let usage: wgpu_types::TextureUsages = (usage | GpuTextureUsage::COPY_DST).into();
+        let texture_descriptor = wgpu_core::resource::TextureDescriptor {
+            label: label.map(Cow::from),
+            size: image.size,
+            mip_level_count: image.mips.len() as u32,
+            sample_count: 1,
+            dimension: wgpu_types::TextureDimension::D2,
+            format: image.format,
+            usage,
+            view_formats: Vec::new(),
+        };
+        let (texture_id, error) = self.gpu.create(
+            || format!("create-texture {:?}", texture_descriptor),
+            |global| global.device_create_texture(device_id, &texture_descriptor, None),
         );
+        if let Some(error) = error {
+            self.gpu.release(|global| global.texture_drop(texture_id));
+            return Err(format!(
+                "Couldn't create a texture for the image: {}",
+                error
+            ));
+        }
+
+        let texture = self.push_texture(&device, texture_id, &texture_descriptor);
+        self.write_decoded_image(texture_id, texture_descriptor.label.as_deref(), 0, Origin3d::ZERO, &image);
 
-        self.table.push_child(texture_id, &device).unwrap()
+        Ok(texture)
     }
 
     async fn create_sampler(
@@ -234,31 +994,32 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuSamplerDescriptor,
     ) -> Resource<GpuSampler> {
-        let device_id = self.table.get(&device).unwrap();
-
-        let sampler_id = wgpu_id(self.instance.device_create_sampler(
-            *device_id,
-            &wgpu_core::resource::SamplerDescriptor {
-                label: None,
-                address_modes: [
-                    descriptor.address_mode_u.into(),
-                    descriptor.address_mode_v.into(),
-                    descriptor.address_mode_w.into(),
-                ],
-                mag_filter: descriptor.mag_filter.into(),
-                min_filter: descriptor.min_filter.into(),
-                mipmap_filter: descriptor.mipmap_filter.into(),
-                lod_min_clamp: descriptor.lod_min_clamp,
-                lod_max_clamp: descriptor.lod_max_clamp,
-                compare: descriptor.compare.map(|c| c.into()),
-                anisotropy_clamp: descriptor.max_anisotrophy,
-                border_color: None,
-            },
-            None,
-        ))
-        .unwrap();
+        let device_id = *self.table.get(&device).unwrap();
+
+        let sampler_descriptor = wgpu_core::resource::SamplerDescriptor {
+            label: descriptor.label.as_deref().map(Cow::from),
+            address_modes: [
+                descriptor.address_mode_u.into(),
+                descriptor.address_mode_v.into(),
+                descriptor.address_mode_w.into(),
+            ],
+            mag_filter: descriptor.mag_filter.into(),
+            min_filter: descriptor.min_filter.into(),
+            mipmap_filter: descriptor.mipmap_filter.into(),
+            lod_min_clamp: descriptor.lod_min_clamp,
+            lod_max_clamp: descriptor.lod_max_clamp,
+            compare: descriptor.compare.map(|c| c.into()),
+            anisotropy_clamp: descriptor.max_anisotrophy,
+            border_color: None,
+        };
+        let created = self.gpu.create(
+            || format!("create-sampler {:?}", descriptor),
+            |global| global.device_create_sampler(device_id, &sampler_descriptor, None),
+        );
+        let sampler_id = self.gpu_id(descriptor.label.as_deref(), created);
 
-        self.table.push_child(sampler_id, &device).unwrap()
+        let sampler = self.table.push_child(sampler_id, &device).unwrap();
+        self.track_gpu_resource(sampler, GpuResourceKind::Sampler, descriptor.label, 0)
     }
 
     async fn create_bind_group_layout(
@@ -266,7 +1027,7 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuBindGroupLayoutDescriptor,
     ) -> Resource<GpuBindGroupLayout> {
-        let device_id = self.table.get(&device).unwrap();
+        let device_id = *self.table.get(&device).unwrap();
         let bind_group_layout_entries: Vec<_> = descriptor
             .entries
             .iter()
@@ -279,22 +1040,27 @@ impl HostGpuDevice for RuneRuntimeState {
             })
             .collect();
 
-        let bind_group_layout_id = wgpu_id(
-            self.instance
-                .device_create_bind_group_layout(
-                    *device_id,
+        let created = self.gpu.create(
+            || format!("create-bind-group-layout {:?}", bind_group_layout_entries),
+            |global| {
+                global.device_create_bind_group_layout(
+                    device_id,
                     &BindGroupLayoutDescriptor {
                         label: None,
-                        entries: Cow::Owned(bind_group_layout_entries),
+                        entries: Cow::Borrowed(&bind_group_layout_entries),
                     },
                     None,
-                ),
-        )
-        .unwrap();
+                )
+            },
+        );
+        // The guest can't label layouts, so their errors go unlabelled.
+        let bind_group_layout_id = self.gpu_id(None, created);
 
-        self.table
+        let bind_group_layout = self
+            .table
             .push_child(bind_group_layout_id, &device)
-            .unwrap()
+            .unwrap();
+        self.track_gpu_resource(bind_group_layout, GpuResourceKind::BindGroupLayout, None, 0)
     }
 
     async fn create_pipeline_layout(
@@ -302,28 +1068,32 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuPipelineLayoutDescriptor,
     ) -> Resource<GpuPipelineLayout> {
-        let device_id = self.table.get(&device).unwrap();
+        let device_id = *self.table.get(&device).unwrap();
         let bind_group_layouts: Vec<_> = descriptor
             .bind_group_layouts
             .iter()
             .map(|layout| *self.table.get(&layout).unwrap())
             .collect();
 
-        let pipeline_layout_id = wgpu_id(
-            self.instance
-                .device_create_pipeline_layout(
-                    *device_id,
+        let created = self.gpu.create(
+            || format!("create-pipeline-layout {:?}", bind_group_layouts),
+            |global| {
+                global.device_create_pipeline_layout(
+                    device_id,
                     &PipelineLayoutDescriptor {
                         label: None,
-                        bind_group_layouts: Cow::Owned(bind_group_layouts),
+                        bind_group_layouts: Cow::Borrowed(&bind_group_layouts),
                         push_constant_ranges: Cow::Owned(Vec::new()),
                     },
                     None,
-                ),
-        )
-        .unwrap();
+                )
+            },
+        );
+        // The guest can't label layouts, so their errors go unlabelled.
+        let pipeline_layout_id = self.gpu_id(None, created);
 
-        self.table.push_child(pipeline_layout_id, &device).unwrap()
+        let pipeline_layout = self.table.push_child(pipeline_layout_id, &device).unwrap();
+        self.track_gpu_resource(pipeline_layout, GpuResourceKind::PipelineLayout, None, 0)
     }
 
     async fn create_bind_group(
@@ -331,8 +1101,8 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuBindGroupDescriptor,
     ) -> Resource<GpuBindGroup> {
-        let device_id = self.table.get(&device).unwrap();
-        let bind_group_layout_id = self.table.get(&descriptor.layout).unwrap();
+        let device_id = *self.table.get(&device).unwrap();
+        let bind_group_layout_id = *self.table.get(&descriptor.layout).unwrap();
 
         let bind_group_entries: Vec<_> = descriptor
             .entries
@@ -340,18 +1110,25 @@ impl HostGpuDevice for RuneRuntimeState {
             .map(|entry| convert_bind_group_entry(&self.table, entry))
             .collect();
 
-        let bind_group_id = wgpu_id(self.instance.device_create_bind_group(
-            *device_id,
-            &wgpu_core::binding_model::BindGroupDescriptor {
-                label: descriptor.label.map(|label| label.into()),
-                layout: *bind_group_layout_id,
-                entries: Cow::Owned(bind_group_entries),
+        let label = descriptor.label;
+        let created = self.gpu.create(
+            || format!("create-bind-group {{ label: {:?}, entries: {:?} }}", label, bind_group_entries),
+            |global| {
+                global.device_create_bind_group(
+                    device_id,
+                    &wgpu_core::binding_model::BindGroupDescriptor {
+                        label: label.as_deref().map(|label| label.into()),
+                        layout: bind_group_layout_id,
+                        entries: Cow::Borrowed(&bind_group_entries),
+                    },
+                    None,
+                )
             },
-            None,
-        ))
-        .unwrap();
+        );
+        let bind_group_id = self.gpu_id(label.as_deref(), created);
 
-        self.table.push_child(bind_group_id, &device).unwrap()
+        let bind_group = self.table.push_child(bind_group_id, &device).unwrap();
+        self.track_gpu_resource(bind_group, GpuResourceKind::BindGroup, label, 0)
     }
 
     async fn create_shader_module(
@@ -359,20 +1136,96 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuShaderModuleDescriptor,
     ) -> Resource<GpuShaderModule> {
-        let device_id = self.table.get(&device).unwrap();
+        let device_id = *self.table.get(&device).unwrap();
 
-        let shader_module = wgpu_id(self.instance.device_create_shader_module(
-            *device_id,
-            &wgpu_core::pipeline::ShaderModuleDescriptor {
-                label: descriptor.label.map(|label| label.into()),
-                runtime_checks: Default::default()
+        let label = descriptor.label;
+        let spv_options = naga::front::spv::Options {
+            adjust_coordinate_space: false,
+            strict_capabilities: true,
+            block_ctx_dump_prefix: None,
+        };
+        // Parsed modules are cached between runs. Sources that don't parse go to wgpu as they are,
+        // so it reports the error.
+        let cached_module = self
+            .pipeline_cache
+            .as_ref()
+            .and_then(|pipeline_cache| pipeline_cache.shader_module(&descriptor.code));
+        let source = match cached_module {
+            Some(module) => wgpu_core::pipeline::ShaderModuleSource::Naga(Cow::Owned(module)),
+            None => match &descriptor.code {
+                GpuShaderSource::Wgsl(code) => wgpu_core::pipeline::ShaderModuleSource::Wgsl(Cow::Borrowed(code)),
+                GpuShaderSource::Spirv(words) => wgpu_core::pipeline::ShaderModuleSource::SpirV(
+                    Cow::Borrowed(words),
+                    spv_options.clone(),
+                ),
+                GpuShaderSource::Glsl((code, stage)) => wgpu_core::pipeline::ShaderModuleSource::Glsl(
+                    Cow::Borrowed(code),
+                    naga::front::glsl::Options::from(<GpuGlslStage as Into<naga::ShaderStage>>::into(*stage)),
+                ),
             },
-            wgpu_core::pipeline::ShaderModuleSource::Wgsl(Cow::Owned(descriptor.code)),
-            None,
-        ))
-        .unwrap();
+        };
+
+        let created = self.gpu.create_checked(
+            || {
+                let source = match descriptor.code {
+                    GpuShaderSource::Wgsl(_) => "wgsl",
+                    GpuShaderSource::Spirv(_) => "spirv",
+                    GpuShaderSource::Glsl(_) => "glsl",
+                };
+                format!("create-shader-module {{ label: {:?}, source: {} }}", label, source)
+            },
+            // Shaders are still parsed and validated, since that needs no GPU. SPIR-V has no
+            // source text to point errors at.
+            || {
+                let (module, code) = match &descriptor.code {
+                    GpuShaderSource::Wgsl(code) => (
+                        naga::front::wgsl::parse_str(code)
+                            .map_err(|error| NullGpuError(error.emit_to_string(code)))?,
+                        Some(code.as_str()),
+                    ),
+                    GpuShaderSource::Spirv(words) => (
+                        naga::front::spv::Frontend::new(words.iter().copied(), &spv_options)
+                            .parse()
+                            .map_err(|error| NullGpuError(error.to_string()))?,
+                        None,
+                    ),
+                    GpuShaderSource::Glsl((code, stage)) => (
+                        naga::front::glsl::Frontend::default()
+                            .parse(
+                                &naga::front::glsl::Options::from(<GpuGlslStage as Into<naga::ShaderStage>>::into(*stage)),
+                                code,
+                            )
+                            .map_err(|error| NullGpuError(error.emit_to_string(code)))?,
+                        Some(code.as_str()),
+                    ),
+                };
+                naga::valid::Validator::new(
+                    naga::valid::ValidationFlags::all(),
+                    SHADER_CAPABILITIES,
+                )
+                .validate(&module)
+                .map(|_| ())
+                .map_err(|error| match code {
+                    Some(code) => NullGpuError(error.emit_to_string(code)),
+                    None => NullGpuError(error.to_string()),
+                })
+            },
+            |global| {
+                global.device_create_shader_module(
+                    device_id,
+                    &wgpu_core::pipeline::ShaderModuleDescriptor {
+                        label: label.as_deref().map(|label| label.into()),
+                        runtime_checks: Default::default()
+                    },
+                    source,
+                    None,
+                )
+            },
+        );
+        let shader_module = self.gpu_id(label.as_deref(), created);
 
-        self.table.push(shader_module).unwrap()
+        let shader_module = self.table.push(shader_module).unwrap();
+        self.track_gpu_resource(shader_module, GpuResourceKind::ShaderModule, label, 0)
     }
 
     async fn create_compute_pipeline(
@@ -388,6 +1241,7 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuRenderPipelineDescriptor,
     ) -> Resource<GpuRenderPipeline> {
+        let label = descriptor.label.clone();
         let layout = match descriptor.layout {
             GpuLayout::Auto => None,
             GpuLayout::Pipeline(pipeline_layout) => {
@@ -454,10 +1308,10 @@ impl HostGpuDevice for RuneRuntimeState {
             None => Vec::<VertexBufferLayout>::new(),
         };
 
-        let vertex_module = self.table.get(&descriptor.vertex.module).unwrap();
+        let vertex_module = *self.table.get(&descriptor.vertex.module).unwrap();
         let vertex = wgpu_core::pipeline::VertexState {
             stage: wgpu_core::pipeline::ProgrammableStageDescriptor {
-                module: *vertex_module,
+                module: vertex_module,
                 entry_point: Some(descriptor.vertex.entry_point.into()),
                 constants: Cow::Owned(HashMap::new()),
                 zero_initialize_workgroup_memory: false,
@@ -466,10 +1320,10 @@ impl HostGpuDevice for RuneRuntimeState {
         };
 
         let fragment = descriptor.fragment.map(|fragment| {
-            let fragment_module = self.table.get(&fragment.module).unwrap();
+            let fragment_module = *self.table.get(&fragment.module).unwrap();
             wgpu_core::pipeline::FragmentState {
                 stage: wgpu_core::pipeline::ProgrammableStageDescriptor {
-                    module: *fragment_module,
+                    module: fragment_module,
                     entry_point: Some(fragment.entry_point.into()),
                     constants: Cow::Owned(HashMap::new()),
                     zero_initialize_workgroup_memory: false,
@@ -478,7 +1332,7 @@ impl HostGpuDevice for RuneRuntimeState {
             }
         });
 
-        let device_id = self.table.get(&device).unwrap();
+        let device_id = *self.table.get(&device).unwrap();
 
         let desc = &wgpu_core::pipeline::RenderPipelineDescriptor {
             vertex,
@@ -550,18 +1404,22 @@ impl HostGpuDevice for RuneRuntimeState {
                 },
             },
             multiview: Default::default(),
-            label: Default::default(),
+            label: label.as_deref().map(Cow::from),
             layout,
-            cache: None,
+            cache: self
+                .pipeline_cache
+                .as_ref()
+                .and_then(|pipeline_cache| pipeline_cache.pipeline_cache),
         };
 
-        let render_pipeline_id = wgpu_id(
-            self.instance
-                .device_create_render_pipeline(*device_id, desc, None, None),
-        )
-        .unwrap();
+        let created = self.gpu.create(
+            || format!("create-render-pipeline {:?}", desc),
+            |global| global.device_create_render_pipeline(device_id, desc, None, None),
+        );
+        let render_pipeline_id = self.gpu_id(label.as_deref(), created);
 
-        self.table.push_child(render_pipeline_id, &device).unwrap()
+        let render_pipeline = self.table.push_child(render_pipeline_id, &device).unwrap();
+        self.track_gpu_resource(render_pipeline, GpuResourceKind::RenderPipeline, label, 0)
     }
 
     async fn create_command_encoder(
@@ -569,21 +1427,28 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuCommandEncoderDescriptor,
     ) -> Resource<GpuCommandEncoder> {
-        let device_id = self.table.get(&device).unwrap();
-
-        let command_encoder_id = wgpu_id(
-            self.instance
-                .device_create_command_encoder(
-                    *device_id,
+        let device_id = *self.table.get(&device).unwrap();
+
+        let label = descriptor.label;
+        let created = self.gpu.create(
+            || format!("create-command-encoder {{ label: {:?} }}", label),
+            |global| {
+                global.device_create_command_encoder(
+                    device_id,
                     &wgpu_types::CommandEncoderDescriptor {
-                        label: descriptor.label.map(|label| label.into())
+                        label: label.as_deref().map(|label| label.into())
                     },
                     None,
-                ),
-        )
-        .unwrap();
+                )
+            },
+        );
+        let command_encoder_id = self.gpu_id(label.as_deref(), created);
 
-        self.table.push_child(command_encoder_id, &device).unwrap()
+        let command_encoder = self.table.push_child(command_encoder_id, &device).unwrap();
+        if let Some(label) = label {
+            self.gpu_state.labels.insert(command_encoder.rep(), label);
+        }
+        command_encoder
     }
 
     async fn create_render_bundle_encoder(
@@ -591,11 +1456,12 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         _descriptor: GpuRenderBundleDescriptor,
     ) -> Resource<GpuRenderBundleEncoder> {
-        let device_id = self.table.get(&device).unwrap();
+        let device_id = *self.table.get(&device).unwrap();
 
+        let global = self.gpu.wgpu().expect("Render bundles aren't supported on the null GPU backend");
         #[allow(unused_variables)]
-        let (render_bundle_encoder, _) = self.instance.device_create_render_bundle_encoder(
-            *device_id,
+        let (render_bundle_encoder, _) = global.device_create_render_bundle_encoder(
+            device_id,
             &wgpu_core::command::RenderBundleEncoderDescriptor {
                 label: todo!(),
                 #[allow(unreachable_code)]
@@ -619,7 +1485,7 @@ impl HostGpuDevice for RuneRuntimeState {
         device: Resource<GpuDevice>,
         descriptor: GpuQuerySetDescriptor,
     ) -> Resource<GpuQuerySet> {
-        let device_id = self.table.get(&device).unwrap();
+        let device_id = *self.table.get(&device).unwrap();
 
         let query_set_descriptor = wgpu_core::resource::QuerySetDescriptor {
             label: Some(descriptor.label.into()),
@@ -627,12 +1493,11 @@ impl HostGpuDevice for RuneRuntimeState {
             count: descriptor.count,
         };
 
-        let query_set_id = wgpu_id(self.instance.device_create_query_set(
-            *device_id,
-            &query_set_descriptor,
-            None,
-        ))
-        .unwrap();
+        let created = self.gpu.create(
+            || format!("create-query-set {:?}", query_set_descriptor),
+            |global| global.device_create_query_set(device_id, &query_set_descriptor, None),
+        );
+        let query_set_id = self.gpu_id(query_set_descriptor.label.as_deref(), created);
 
         self.gpu_state.query_sets.insert(
             query_set_id,
@@ -642,11 +1507,69 @@ impl HostGpuDevice for RuneRuntimeState {
             },
         );
 
-        self.table.push_child(query_set_id, &device).unwrap()
+        let query_set = self.table.push_child(query_set_id, &device).unwrap();
+        self.track_gpu_resource(
+            query_set,
+            GpuResourceKind::QuerySet,
+            query_set_descriptor.label.map(|label| label.into_owned()),
+            query_set_descriptor.count as u64 * wgpu_types::QUERY_SIZE as u64,
+        )
+    }
+
+    async fn queue(&mut self, _device: Resource<GpuDevice>) -> Resource<GpuQueue> {
+        Resource::new_own(self.queue_resource_id)
+    }
+
+    async fn push_error_scope(&mut self, _device: Resource<GpuDevice>, filter: GpuErrorFilter) -> () {
+        self.gpu_state.error_scopes.push(ErrorScope {
+            filter,
+            error: None,
+        });
+        ()
+    }
+
+    async fn pop_error_scope(&mut self, _device: Resource<GpuDevice>) -> Option<GpuError> {
+        match self.gpu_state.error_scopes.pop() {
+            Some(scope) => scope.error,
+            None => {
+                debug::warn("pop-error-scope called with no error scope on the stack");
+                None
+            }
+        }
+    }
+
+    async fn lost(&mut self, _device: Resource<GpuDevice>) -> Option<GpuDeviceLostInfo> {
+        self.gpu_state.device_lost.lock().unwrap().clone()
+    }
+
+    async fn texture_allocation_size(
+        &mut self,
+        _device: Resource<GpuDevice>,
+        descriptor: GpuTextureDescriptor,
+    ) -> u64 {
+        let size: Extent3d = descriptor.size.into();
+        Texture {
+            height: size.height,
+            width: size.width,
+            depth_or_array_layers: size.depth_or_array_layers,
+            dimension: descriptor.dimension.into(),
+            format: descriptor.format.into(),
+            mip_level_count: descriptor.mip_level_count,
+            sample_count: descriptor.sample_count,
+            usage: descriptor.usage.into(),
+        }
+        .estimated_size()
     }
 
-    async fn queue(&mut self, _device: Resource<GpuDevice>) -> Resource<GpuQueue> {
-        Resource::new_own(self.queue_resource_id)
+    async fn memory_report(&mut self, _device: Resource<GpuDevice>) -> GpuMemoryReport {
+        let resource_bytes = self.gpu_state.resources.values().map(|resource| resource.size).sum();
+        // Only backends that allocate through gpu-allocator, Vulkan and DX12, can report this.
+        let allocator_report = self.gpu.allocator_report(self.device);
+        GpuMemoryReport {
+            resource_bytes,
+            allocated_bytes: allocator_report.as_ref().map(|report| report.total_allocated_bytes),
+            reserved_bytes: allocator_report.as_ref().map(|report| report.total_reserved_bytes),
+        }
     }
 
     async fn drop(&mut self, _rep: Resource<GpuDevice>) -> Result<()> {
@@ -660,15 +1583,22 @@ impl HostGpuQueue for RuneRuntimeState {
         queue: Resource<GpuQueue>,
         command_buffers: Vec<Resource<GpuCommandBuffer>>,
     ) -> () {
+        let labels = command_buffers
+            .iter()
+            .filter_map(|buffer| self.gpu_state.labels.remove(&buffer.rep()))
+            .collect::<Vec<_>>();
+        let label = (!labels.is_empty()).then(|| labels.join(", "));
         let command_buffers = command_buffers
             .into_iter()
             .map(|buffer| self.table.delete(buffer).unwrap())
             .collect::<Vec<_>>();
 
-        let queue_id = self.table.get(&queue).unwrap();
-        self.instance
-            .queue_submit(*queue_id, &command_buffers)
-            .unwrap();
+        let queue_id = *self.table.get(&queue).unwrap();
+        let result = self.gpu.call(
+            || format!("submit {:?}", command_buffers),
+            |global| global.queue_submit(queue_id, &command_buffers).map_err(|(_, error)| error),
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -682,12 +1612,28 @@ impl HostGpuQueue for RuneRuntimeState {
         _data_offset: GpuSizeU64,
         _size: GpuSizeU64,
     ) -> () {
-        let queue_id = self.table.get(&queue).unwrap();
-        let buffer_id = self.table.get(&buffer).unwrap();
+        let queue_id = *self.table.get(&queue).unwrap();
+        let buffer_id = *self.table.get(&buffer).unwrap();
 
-        self.instance
-            .queue_write_buffer(*queue_id, *buffer_id, buffer_offset, &data)
-            .unwrap();
+        let buffer_size = self.gpu_state.buffers.get(&buffer_id).map(|b| b.size).unwrap_or(0);
+        let size = data.len() as u64;
+        let result = self.gpu.call_checked(
+            || format!("write-buffer {{ buffer: {:?}, offset: {}, size: {} }}", buffer_id, buffer_offset, size),
+            || {
+                check(buffer_offset % wgpu_types::COPY_BUFFER_ALIGNMENT == 0, || {
+                    format!("write-buffer offset {} is not a multiple of {}", buffer_offset, wgpu_types::COPY_BUFFER_ALIGNMENT)
+                })?;
+                check(size % wgpu_types::COPY_BUFFER_ALIGNMENT == 0, || {
+                    format!("write-buffer size {} is not a multiple of {}", size, wgpu_types::COPY_BUFFER_ALIGNMENT)
+                })?;
+                check(buffer_offset + size <= buffer_size, || {
+                    format!("write-buffer of {} bytes at offset {} overruns a buffer of {} bytes", size, buffer_offset, buffer_size)
+                })
+            },
+            |global| global.queue_write_buffer(queue_id, buffer_id, buffer_offset, &data),
+        );
+        let label = self.gpu_label(&buffer);
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -700,35 +1646,120 @@ impl HostGpuQueue for RuneRuntimeState {
         data_layout: GpuImageDataLayout,
         size: GpuExtentD3,
     ) -> () {
-        let queue_id = self.table.get(&queue).unwrap();
-        let texture_id = self.table.get(&destination.texture).unwrap();
-
-        self.instance
-            .queue_write_texture(
-                *queue_id,
-                &ImageCopyTexture {
-                    texture: *texture_id,
-                    mip_level: destination.mip_level,
-                    origin: Origin3d {
-                        x: destination.origin[0],
-                        y: destination.origin[1],
-                        z: destination.origin[2],
+        let queue_id = *self.table.get(&queue).unwrap();
+        let texture_id = *self.table.get(&destination.texture).unwrap();
+
+        let result = self.gpu.call(
+            || {
+                format!(
+                    "write-texture {{ texture: {:?}, mip_level: {}, origin: {:?}, size: {}, layout: {:?}, extent: {:?} }}",
+                    texture_id, destination.mip_level, destination.origin, data.len(), data_layout, size
+                )
+            },
+            |global| {
+                global.queue_write_texture(
+                    queue_id,
+                    &ImageCopyTexture {
+                        texture: texture_id,
+                        mip_level: destination.mip_level,
+                        origin: Origin3d {
+                            x: destination.origin[0],
+                            y: destination.origin[1],
+                            z: destination.origin[2],
+                        },
+                        aspect: destination.aspect.into(),
                     },
-                    aspect: destination.aspect.into(),
-                },
-                &data,
-                &ImageDataLayout {
-                    offset: data_layout.offset,
-                    bytes_per_row: Some(data_layout.bytes_per_row),
-                    rows_per_image: Some(data_layout.rows_per_image),
-                },
-                &size.into(),
-            )
-            .unwrap();
+                    &data,
+                    &ImageDataLayout {
+                        offset: data_layout.offset,
+                        bytes_per_row: Some(data_layout.bytes_per_row),
+                        rows_per_image: Some(data_layout.rows_per_image),
+                    },
+                    &size.into(),
+                )
+            },
+        );
+        let label = self.gpu_label(&destination.texture);
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
 
+    async fn copy_external_image_to_texture(
+        &mut self,
+        _queue: Resource<GpuQueue>,
+        image: Vec<u8>,
+        destination: GpuImageCopyTextureTagged,
+        options: GpuImageDecodeOptions,
+    ) -> Result<(), String> {
+        let texture_id = *self.table.get(&destination.texture).unwrap();
+        let texture = self
+            .gpu_state
+            .textures
+            .get(&texture_id)
+            .ok_or("Images can't be copied into surface textures")?;
+        if destination.mip_level >= texture.mip_level_count {
+            return Err(format!(
+                "The texture has {} mip levels, so there's no mip level {}",
+                texture.mip_level_count, destination.mip_level
+            ));
+        }
+        let mip_size = Extent3d {
+            width: texture.width,
+            height: texture.height,
+            depth_or_array_layers: texture.depth_or_array_layers,
+        }
+        .mip_level_size(destination.mip_level, texture.dimension);
+
+        // Decoded images are sRGB, which display-p3 destinations get as is.
+        let image = texture_decoder::decode(
+            &image,
+            &DecodeOptions {
+                format: Some(texture.format),
+                generate_mipmaps: options.generate_mipmaps,
+                max_mip_level_count: texture.mip_level_count.saturating_sub(destination.mip_level),
+                max_size: mip_size.width.max(mip_size.height),
+                flip_y: options.flip_y,
+                srgb: texture.format.is_srgb(),
+                premultiply_alpha: destination.premultiplied_alpha,
+            },
+        )?;
+
+        let origin = Origin3d {
+            x: destination.origin[0],
+            y: destination.origin[1],
+            z: destination.origin[2],
+        };
+        if origin.x as u64 + image.size.width as u64 > mip_size.width as u64
+            || origin.y as u64 + image.size.height as u64 > mip_size.height as u64
+            || origin.z >= mip_size.depth_or_array_layers
+        {
+            return Err(format!(
+                "A {}x{} image at {:?} doesn't fit in mip level {} of the texture, which is {}x{}",
+                image.size.width,
+                image.size.height,
+                destination.origin,
+                destination.mip_level,
+                mip_size.width,
+                mip_size.height
+            ));
+        }
+
+        let label = self.gpu_label(&destination.texture);
+        self.write_decoded_image(texture_id, label.as_deref(), destination.mip_level, origin, &image);
+        Ok(())
+    }
+
+    async fn generate_mipmaps(
+        &mut self,
+        _queue: Resource<GpuQueue>,
+        texture: Resource<GpuTexture>,
+        filter: GpuMipmapFilter,
+    ) -> Result<(), String> {
+        let texture_id = *self.table.get(&texture).unwrap();
+        self.generate_texture_mipmaps(texture_id, filter)
+    }
+
     async fn drop(&mut self, _rep: Resource<GpuQueue>) -> Result<()> {
         Ok(())
     }
@@ -736,19 +1767,19 @@ impl HostGpuQueue for RuneRuntimeState {
 
 impl HostGpuBuffer for RuneRuntimeState {
     async fn size(&mut self, buffer: Resource<GpuBuffer>) -> GpuSizeU64 {
-        let buffer_id = self.table.get(&buffer).unwrap();
+        let buffer_id = *self.table.get(&buffer).unwrap();
         let buffer = self.gpu_state.buffers.get(&buffer_id).unwrap();
         buffer.size
     }
 
     async fn usage(&mut self, buffer: Resource<GpuBuffer>) -> GpuBufferUsage {
-        let buffer_id = self.table.get(&buffer).unwrap();
+        let buffer_id = *self.table.get(&buffer).unwrap();
         let buffer = self.gpu_state.buffers.get(&buffer_id).unwrap();
         buffer.usage.into()
     }
 
     async fn map_state(&mut self, buffer: Resource<GpuBuffer>) -> GpuBufferMapState {
-        let buffer_id = self.table.get(&buffer).unwrap();
+        let buffer_id = *self.table.get(&buffer).unwrap();
         let buffer = self.gpu_state.buffers.get(&buffer_id).unwrap();
         buffer.map_state
     }
@@ -760,22 +1791,41 @@ impl HostGpuBuffer for RuneRuntimeState {
         offset: GpuSizeU64,
         size: GpuSizeU64,
     ) -> () {
-        let buffer_id = self.table.get(&buffer).unwrap();
-        let buffer = self.gpu_state.buffers.get_mut(buffer_id).unwrap();
-        
+        let label = self.gpu_label(&buffer);
+        let buffer_id = *self.table.get(&buffer).unwrap();
+
+        let buffer = self.gpu_state.buffers.get_mut(&buffer_id).unwrap();
         buffer.map_state = GpuBufferMapState::Pending;
-        self.instance
-            .buffer_map_async(
-                *buffer_id,
-                offset,
-                Some(size),
-                BufferMapOperation {
-                    host: mode.into(),
-                    callback: None
-                }
-            )
-            .unwrap();
-        buffer.map_state = GpuBufferMapState::Mapped;
+        let usage = buffer.usage;
+        let host: HostMap = mode.into();
+        let required = match host {
+            HostMap::Read => wgpu_types::BufferUsages::MAP_READ,
+            HostMap::Write => wgpu_types::BufferUsages::MAP_WRITE,
+        };
+        let result = self.gpu.call_checked(
+            || format!("map-async {{ buffer: {:?}, offset: {}, size: {} }}", buffer_id, offset, size),
+            || {
+                check(usage.contains(required), || {
+                    format!("Mapping a buffer requires {:?}, but its usage is {:?}", required, usage)
+                })
+            },
+            |global| {
+                global.buffer_map_async(
+                    buffer_id,
+                    offset,
+                    Some(size),
+                    BufferMapOperation {
+                        host,
+                        callback: None
+                    }
+                )
+            },
+        );
+        let map_state = match self.gpu_result(label.as_deref(), result) {
+            Some(_) => GpuBufferMapState::Mapped,
+            None => GpuBufferMapState::Unmapped,
+        };
+        self.gpu_state.buffers.get_mut(&buffer_id).unwrap().map_state = map_state;
         ()
     }
 
@@ -785,10 +1835,17 @@ impl HostGpuBuffer for RuneRuntimeState {
         offset: GpuSizeU64,
         size: GpuSizeU64,
     ) -> Vec<u8> {
-        let buffer_id = self.table.get(&buffer).unwrap();
-        let (mapped_range, range_length) = self
-            .instance
-            .buffer_get_mapped_range(*buffer_id, offset, Some(size))
+        let buffer_id = *self.table.get(&buffer).unwrap();
+
+        // Null buffers have no memory behind them, so they read back as zeroes.
+        let Some(global) = self.gpu.wgpu_or_record(|| {
+            format!("get-mapped-range {{ buffer: {:?}, offset: {}, size: {} }}", buffer_id, offset, size)
+        }) else {
+            return vec![0; size as usize];
+        };
+
+        let (mapped_range, range_length) = global
+            .buffer_get_mapped_range(buffer_id, offset, Some(size))
             .unwrap();
 
         unsafe {
@@ -801,77 +1858,79 @@ impl HostGpuBuffer for RuneRuntimeState {
     }
 
     async fn unmap(&mut self, buffer: Resource<GpuBuffer>) -> () {
-        let buffer_id = self.table.get(&buffer).unwrap();
-        let buffer = self.gpu_state.buffers.get_mut(buffer_id).unwrap();
-        self.instance
-            .buffer_unmap(*buffer_id)
-            .ok();
-        buffer.map_state = GpuBufferMapState::Unmapped;
+        let label = self.gpu_label(&buffer);
+        let buffer_id = *self.table.get(&buffer).unwrap();
+        let result = self.gpu.call(|| format!("unmap {:?}", buffer_id), |global| global.buffer_unmap(buffer_id));
+        self.gpu_result(label.as_deref(), result);
+        self.gpu_state.buffers.get_mut(&buffer_id).unwrap().map_state = GpuBufferMapState::Unmapped;
         ()
     }
 
     async fn destroy(&mut self, buffer: Resource<GpuBuffer>) -> () {
-        let buffer_id = self.table.get(&buffer).unwrap();
-        self.instance
-            .buffer_destroy(*buffer_id)
-            .ok();
+        let label = self.gpu_label(&buffer);
+        let buffer_id = *self.table.get(&buffer).unwrap();
+        let result = self.gpu.call(|| format!("destroy {:?}", buffer_id), |global| global.buffer_destroy(buffer_id));
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
     async fn drop(&mut self, rep: Resource<GpuBuffer>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let buffer_id = self.table.delete(rep).unwrap();
         self.gpu_state.buffers.remove(&buffer_id);
-        self.instance.buffer_drop(buffer_id);
+        self.gpu.release(|global| global.buffer_drop(buffer_id));
         Ok(())
     }
 }
 
 impl HostGpuTexture for RuneRuntimeState {
     async fn width(&mut self, texture: Resource<GpuTexture>) -> GpuIntegerCoordinate {
-        let texture_id = self.table.get(&texture).unwrap();
+        let texture_id = *self.table.get(&texture).unwrap();
         let texture = self.gpu_state.textures.get(&texture_id).unwrap();
         texture.width
     }
 
     async fn height(&mut self, texture: Resource<GpuTexture>) -> GpuIntegerCoordinate {
-        let texture_id = self.table.get(&texture).unwrap();
+        let texture_id = *self.table.get(&texture).unwrap();
         let texture = self.gpu_state.textures.get(&texture_id).unwrap();
         texture.height
     }
 
     async fn depth_or_array_layers(
         &mut self,
-        _self_: Resource<GpuTexture>,
+        texture: Resource<GpuTexture>,
     ) -> GpuIntegerCoordinate {
-        todo!()
+        let texture_id = *self.table.get(&texture).unwrap();
+        let texture = self.gpu_state.textures.get(&texture_id).unwrap();
+        texture.depth_or_array_layers
     }
 
     async fn mip_level_count(&mut self, texture: Resource<GpuTexture>) -> GpuIntegerCoordinate {
-        let texture_id = self.table.get(&texture).unwrap();
+        let texture_id = *self.table.get(&texture).unwrap();
         let texture = self.gpu_state.textures.get(&texture_id).unwrap();
         texture.mip_level_count
     }
 
     async fn sample_count(&mut self, texture: Resource<GpuTexture>) -> GpuSizeU32 {
-        let texture_id = self.table.get(&texture).unwrap();
+        let texture_id = *self.table.get(&texture).unwrap();
         let texture = self.gpu_state.textures.get(&texture_id).unwrap();
         texture.sample_count
     }
 
     async fn dimension(&mut self, texture: Resource<GpuTexture>) -> GpuTextureDimension {
-        let texture_id = self.table.get(&texture).unwrap();
+        let texture_id = *self.table.get(&texture).unwrap();
         let texture = self.gpu_state.textures.get(&texture_id).unwrap();
         texture.dimension.into()
     }
 
     async fn format(&mut self, texture: Resource<GpuTexture>) -> GpuTextureFormat {
-        let texture_id = self.table.get(&texture).unwrap();
+        let texture_id = *self.table.get(&texture).unwrap();
         let texture = self.gpu_state.textures.get(&texture_id).unwrap();
         texture.format.into()
     }
 
     async fn usage(&mut self, texture: Resource<GpuTexture>) -> GpuTextureUsage {
-        let texture_id = self.table.get(&texture).unwrap();
+        let texture_id = *self.table.get(&texture).unwrap();
         let texture = self.gpu_state.textures.get(&texture_id).unwrap();
         texture.usage.into()
     }
@@ -880,12 +1939,18 @@ impl HostGpuTexture for RuneRuntimeState {
         &mut self,
         texture_resource: Resource<GpuTexture>,
     ) -> Resource<GpuTextureView> {
-        let texture_id = self.table.get(&texture_resource).unwrap();
-
-        let texture_view_descriptor = if let Some(texture) = self.gpu_state.textures.get(texture_id)
+        let texture_id = *self.table.get(&texture_resource).unwrap();
+
+        // Views are named after their texture, since the guest doesn't name them.
+        let label = self
+            .gpu_state
+            .resources
+            .get(&texture_resource.rep())
+            .and_then(|texture| texture.label.clone());
+        let texture_view_descriptor = if let Some(texture) = self.gpu_state.textures.get(&texture_id)
         {
             TextureViewDescriptor {
-                label: None,
+                label: label.as_deref().map(Cow::from),
                 format: Some(texture.format),
                 dimension: None,
                 range: ImageSubresourceRange::default(),
@@ -895,73 +1960,89 @@ impl HostGpuTexture for RuneRuntimeState {
             TextureViewDescriptor::default()
         };
 
-        let texture_view_id = wgpu_id(self.instance.texture_create_view(
-            *texture_id,
-            &texture_view_descriptor,
-            None,
-        ))
-        .unwrap();
+        let created = self.gpu.create(
+            || format!("create-view {:?} {:?}", texture_id, texture_view_descriptor),
+            |global| global.texture_create_view(texture_id, &texture_view_descriptor, None),
+        );
+        let texture_view_id = self.gpu_id(label.as_deref(), created);
 
-        self.table
+        let texture_view = self
+            .table
             .push_child(texture_view_id, &texture_resource)
-            .unwrap()
+            .unwrap();
+        self.track_gpu_resource(texture_view, GpuResourceKind::TextureView, label, 0)
     }
 
     async fn destroy(&mut self, texture: Resource<GpuTexture>) -> () {
-        let texture_id = self.table.get(&texture).unwrap();
-        self.instance
-            .texture_destroy(*texture_id)
-            .unwrap();
-        self.table.delete(texture).unwrap();
+        let label = self.gpu_label(&texture);
+        let texture_id = *self.table.get(&texture).unwrap();
+        let result = self.gpu.call(|| format!("destroy {:?}", texture_id), |global| global.texture_destroy(texture_id));
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
-    async fn drop(&mut self, _texture: Resource<GpuTexture>) -> Result<()> {
+    async fn drop(&mut self, rep: Resource<GpuTexture>) -> Result<()> {
+        // Surface textures aren't tracked, and belong to the surface rather than the guest.
+        let tracked = self.gpu_state.resources.remove(&rep.rep()).is_some();
+        let texture_id = self.table.delete(rep).unwrap();
+        // The render scaling target is handed out every frame, and outlives each handle to it.
+        let scaling_target = self
+            .gpu_state
+            .render_scaling
+            .as_ref()
+            .is_some_and(|scaling| scaling.target == Some(texture_id));
+        if scaling_target {
+            return Ok(());
+        }
+        self.gpu_state.textures.remove(&texture_id);
+        if tracked {
+            self.gpu.release(|global| global.texture_drop(texture_id));
+        }
         Ok(())
     }
 }
 
 impl HostGpuTextureView for RuneRuntimeState {
     async fn drop(&mut self, rep: Resource<GpuTextureView>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let texture_view_id = self.table.delete(rep).unwrap();
-        self.instance
-            .texture_view_drop(texture_view_id)
-            .unwrap();
+        self.gpu.release(|global| global.texture_view_drop(texture_view_id).unwrap());
         Ok(())
     }
 }
 
 impl HostGpuSampler for RuneRuntimeState {
     async fn drop(&mut self, rep: Resource<GpuSampler>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let sampler_id = self.table.delete(rep).unwrap();
-        self.instance.sampler_drop(sampler_id);
+        self.gpu.release(|global| global.sampler_drop(sampler_id));
         Ok(())
     }
 }
 
 impl HostGpuBindGroupLayout for RuneRuntimeState {
     async fn drop(&mut self, rep: Resource<GpuBindGroupLayout>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let bind_group_layout_id = self.table.delete(rep).unwrap();
-        self.instance
-            .bind_group_layout_drop(bind_group_layout_id);
+        self.gpu.release(|global| global.bind_group_layout_drop(bind_group_layout_id));
         Ok(())
     }
 }
 
 impl HostGpuBindGroup for RuneRuntimeState {
     async fn drop(&mut self, rep: Resource<GpuBindGroup>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let bind_group_id = self.table.delete(rep).unwrap();
-        self.instance
-            .bind_group_drop(bind_group_id);
+        self.gpu.release(|global| global.bind_group_drop(bind_group_id));
         Ok(())
     }
 }
 
 impl HostGpuPipelineLayout for RuneRuntimeState {
     async fn drop(&mut self, rep: Resource<GpuPipelineLayout>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let pipeline_layout_id = self.table.delete(rep).unwrap();
-        self.instance
-            .pipeline_layout_drop(pipeline_layout_id);
+        self.gpu.release(|global| global.pipeline_layout_drop(pipeline_layout_id));
         Ok(())
     }
 }
@@ -975,9 +2056,9 @@ impl HostGpuShaderModule for RuneRuntimeState {
     }
 
     async fn drop(&mut self, rep: Resource<GpuShaderModule>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let shader_module_id = self.table.delete(rep).unwrap();
-        self.instance
-            .shader_module_drop(shader_module_id);
+        self.gpu.release(|global| global.shader_module_drop(shader_module_id));
         Ok(())
     }
 }
@@ -988,25 +2069,24 @@ impl HostGpuComputePipeline for RuneRuntimeState {
         pipeline: Resource<GpuComputePipeline>,
         index: u32,
     ) -> Resource<GpuBindGroupLayout> {
-        let pipeline_id = self.table.get(&pipeline).unwrap();
-        let bind_group_layout_id = wgpu_id(
-            self.instance
-                .compute_pipeline_get_bind_group_layout(
-                    *pipeline_id,
-                    index,
-                    None,
-                ),
-        )
-        .unwrap();
-        self.table
+        let pipeline_id = *self.table.get(&pipeline).unwrap();
+        let created = self.gpu.create(
+            || format!("get-bind-group-layout {:?} {}", pipeline_id, index),
+            |global| global.compute_pipeline_get_bind_group_layout(pipeline_id, index, None),
+        );
+        let label = self.gpu_label(&pipeline);
+        let bind_group_layout_id = self.gpu_id(label.as_deref(), created);
+        let bind_group_layout = self
+            .table
             .push_child(bind_group_layout_id, &pipeline)
-            .unwrap()
+            .unwrap();
+        self.track_gpu_resource(bind_group_layout, GpuResourceKind::BindGroupLayout, None, 0)
     }
 
     async fn drop(&mut self, rep: Resource<GpuComputePipeline>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let pipeline_id = self.table.delete(rep).unwrap();
-        self.instance
-            .compute_pipeline_drop(pipeline_id);
+        self.gpu.release(|global| global.compute_pipeline_drop(pipeline_id));
         Ok(())
     }
 }
@@ -1017,27 +2097,31 @@ impl HostGpuRenderPipeline for RuneRuntimeState {
         pipeline: Resource<GpuRenderPipeline>,
         index: u32,
     ) -> Resource<GpuBindGroupLayout> {
-        let pipeline_id = self.table.get(&pipeline).unwrap();
-        let bind_group_layout_id = wgpu_id(
-            self.instance
-                .render_pipeline_get_bind_group_layout(*pipeline_id, index, None),
-        )
-        .unwrap();
-        self.table
+        let pipeline_id = *self.table.get(&pipeline).unwrap();
+        let created = self.gpu.create(
+            || format!("get-bind-group-layout {:?} {}", pipeline_id, index),
+            |global| global.render_pipeline_get_bind_group_layout(pipeline_id, index, None),
+        );
+        let label = self.gpu_label(&pipeline);
+        let bind_group_layout_id = self.gpu_id(label.as_deref(), created);
+        let bind_group_layout = self
+            .table
             .push_child(bind_group_layout_id, &pipeline)
-            .unwrap()
+            .unwrap();
+        self.track_gpu_resource(bind_group_layout, GpuResourceKind::BindGroupLayout, None, 0)
     }
 
     async fn drop(&mut self, rep: Resource<GpuRenderPipeline>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let render_pipeline_id = self.table.delete(rep).unwrap();
-        self.instance
-            .render_pipeline_drop(render_pipeline_id);
+        self.gpu.release(|global| global.render_pipeline_drop(render_pipeline_id));
         Ok(())
     }
 }
 
 impl HostGpuCommandBuffer for RuneRuntimeState {
-    async fn drop(&mut self, _: Resource<GpuCommandBuffer>) -> Result<()> {
+    async fn drop(&mut self, rep: Resource<GpuCommandBuffer>) -> Result<()> {
+        self.gpu_state.labels.remove(&rep.rep());
         Ok(())
     }
 }
@@ -1048,7 +2132,7 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
         command_encoder: Resource<GpuCommandEncoder>,
         descriptor: GpuRenderPassDescriptor,
     ) -> Resource<GpuRenderPassEncoder> {
-        let command_encoder = self.table.get(&command_encoder).unwrap();
+        let command_encoder = *self.table.get(&command_encoder).unwrap();
         let views = descriptor
             .color_attachments
             .iter()
@@ -1092,38 +2176,77 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
                     }
                 });
 
-        let (render_pass, _) = self
-            .instance
-            .command_encoder_create_render_pass(
-                *command_encoder,
-                &wgpu_core::command::RenderPassDescriptor {
-                    label: None,
-                    color_attachments: color_attachments.into(),
-                    depth_stencil_attachment: depth_stencil_attachment.as_ref(),
-                    timestamp_writes: None,
-                    occlusion_query_set: None,
-                },
-            );
+        let label = descriptor.label;
+        let profiled = self.profiler_begin_pass("render", label.clone());
+        let render_pass = self.gpu.begin_pass(
+            command_encoder,
+            || {
+                format!(
+                    "begin-render-pass {{ encoder: {:?}, color_attachments: {:?}, depth_stencil_attachment: {:?} }}",
+                    command_encoder, color_attachments, depth_stencil_attachment
+                )
+            },
+            |global| {
+                global
+                    .command_encoder_create_render_pass(
+                        command_encoder,
+                        &wgpu_core::command::RenderPassDescriptor {
+                            label: label.as_deref().map(Cow::from),
+                            color_attachments: Cow::Borrowed(&color_attachments),
+                            depth_stencil_attachment: depth_stencil_attachment.as_ref(),
+                            timestamp_writes: profiled.as_ref().map(|(_, timestamp_writes)| timestamp_writes),
+                            occlusion_query_set: None,
+                        },
+                    )
+                    .0
+            },
+        );
 
-        self.table.push(render_pass).unwrap()
+        let render_pass = self.table.push(render_pass).unwrap();
+        if let Some(label) = label {
+            self.gpu_state.labels.insert(render_pass.rep(), label);
+        }
+        if let Some((index, _)) = profiled {
+            let profiler = self.gpu_state.profiler.as_mut().unwrap();
+            profiler.active_passes.insert(render_pass.rep(), index);
+        }
+        render_pass
     }
 
     async fn begin_compute_pass(
         &mut self,
         command_encoder: wasmtime::component::Resource<GpuCommandEncoder>,
-        _descriptor: Option<GpuComputePassDescriptor>,
+        descriptor: Option<GpuComputePassDescriptor>,
     ) -> Resource<GpuComputePassEncoder> {
-        let command_encoder = self.table.get(&command_encoder).unwrap();
-
-        let (compute_pass, _) = self.instance.command_encoder_create_compute_pass(
-            *command_encoder,
-            &wgpu_core::command::ComputePassDescriptor {
-                label: None,
-                timestamp_writes: None,
+        let command_encoder = *self.table.get(&command_encoder).unwrap();
+
+        let label = descriptor.and_then(|d| d.label);
+        let profiled = self.profiler_begin_pass("compute", label.clone());
+        let compute_pass = self.gpu.begin_pass(
+            command_encoder,
+            || format!("begin-compute-pass {{ encoder: {:?} }}", command_encoder),
+            |global| {
+                global
+                    .command_encoder_create_compute_pass(
+                        command_encoder,
+                        &wgpu_core::command::ComputePassDescriptor {
+                            label: label.as_deref().map(Cow::from),
+                            timestamp_writes: profiled.as_ref().map(|(_, timestamp_writes)| timestamp_writes),
+                        },
+                    )
+                    .0
             },
         );
 
-        self.table.push(compute_pass).unwrap()
+        let compute_pass = self.table.push(compute_pass).unwrap();
+        if let Some(label) = label {
+            self.gpu_state.labels.insert(compute_pass.rep(), label);
+        }
+        if let Some((index, _)) = profiled {
+            let profiler = self.gpu_state.profiler.as_mut().unwrap();
+            profiler.active_passes.insert(compute_pass.rep(), index);
+        }
+        compute_pass
     }
 
     async fn copy_buffer_to_buffer(
@@ -1135,20 +2258,25 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
         destination_offset: GpuSizeU64,
         size: GpuSizeU64,
     ) -> () {
-        let command_encoder_id = self.table.get(&command_encoder).unwrap();
-        let source_id = self.table.get(&source).unwrap();
-        let destination_id = self.table.get(&destination).unwrap();
-
-        self.instance
-            .command_encoder_copy_buffer_to_buffer(
-                *command_encoder_id,
-                *source_id,
-                source_offset,
-                *destination_id,
-                destination_offset,
-                size,
-            )
-            .ok();
+        let label = self.gpu_label(&command_encoder);
+        let command_encoder_id = *self.table.get(&command_encoder).unwrap();
+        let source_id = *self.table.get(&source).unwrap();
+        let destination_id = *self.table.get(&destination).unwrap();
+
+        let result = self.gpu.call(
+            || format!("copy-buffer-to-buffer {{ source: {:?}, source_offset: {}, destination: {:?}, destination_offset: {}, size: {} }}", source_id, source_offset, destination_id, destination_offset, size),
+            |global| {
+                global.command_encoder_copy_buffer_to_buffer(
+                    command_encoder_id,
+                    source_id,
+                    source_offset,
+                    destination_id,
+                    destination_offset,
+                    size,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1159,12 +2287,13 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
         destination: GpuImageCopyTexture,
         copy_size: GpuExtentD3,
     ) -> () {
-        let command_encoder_id = self.table.get(&command_encoder).unwrap();
-        let source_buffer_id = self.table.get(&source.buffer).unwrap();
-        let destination_texture_id = self.table.get(&destination.texture).unwrap();
+        let label = self.gpu_label(&command_encoder);
+        let command_encoder_id = *self.table.get(&command_encoder).unwrap();
+        let source_buffer_id = *self.table.get(&source.buffer).unwrap();
+        let destination_texture_id = *self.table.get(&destination.texture).unwrap();
 
         let source = wgpu_core::command::ImageCopyBuffer {
-            buffer: *source_buffer_id,
+            buffer: source_buffer_id,
             layout: wgpu_types::ImageDataLayout {
                 offset: source.layout.offset,
                 bytes_per_row: Some(source.layout.bytes_per_row),
@@ -1172,7 +2301,7 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
             },
         };
         let destination = wgpu_core::command::ImageCopyTexture {
-            texture: *destination_texture_id,
+            texture: destination_texture_id,
             mip_level: destination.mip_level,
             origin: Origin3d {
                 x: destination.origin[0],
@@ -1182,14 +2311,18 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
             aspect: destination.aspect.into(),
         };
 
-        self.instance
-            .command_encoder_copy_buffer_to_texture(
-                *command_encoder_id,
-                &source,
-                &destination,
-                &copy_size.into(),
-            )
-            .ok();
+        let result = self.gpu.call(
+            || format!("copy-buffer-to-texture {{ source: {:?}, destination: {:?}, size: {:?} }}", source, destination, copy_size),
+            |global| {
+                global.command_encoder_copy_buffer_to_texture(
+                    command_encoder_id,
+                    &source,
+                    &destination,
+                    &copy_size.into(),
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1200,12 +2333,13 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
         destination: GpuImageCopyBuffer,
         copy_size: GpuExtentD3,
     ) -> () {
-        let command_encoder_id = self.table.get(&command_encoder).unwrap();
-        let source_texture_id = self.table.get(&source.texture).unwrap();
-        let destination_texture_id = self.table.get(&destination.buffer).unwrap();
+        let label = self.gpu_label(&command_encoder);
+        let command_encoder_id = *self.table.get(&command_encoder).unwrap();
+        let source_texture_id = *self.table.get(&source.texture).unwrap();
+        let destination_texture_id = *self.table.get(&destination.buffer).unwrap();
 
         let source = wgpu_core::command::ImageCopyTexture {
-            texture: *source_texture_id,
+            texture: source_texture_id,
             mip_level: source.mip_level,
             origin: Origin3d {
                 x: source.origin[0],
@@ -1215,7 +2349,7 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
             aspect: source.aspect.into(),
         };
         let destination = wgpu_core::command::ImageCopyBuffer {
-            buffer: *destination_texture_id,
+            buffer: destination_texture_id,
             layout: wgpu_types::ImageDataLayout {
                 offset: destination.layout.offset,
                 bytes_per_row: Some(destination.layout.bytes_per_row),
@@ -1223,14 +2357,18 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
             },
         };
 
-        self.instance
-            .command_encoder_copy_texture_to_buffer(
-                *command_encoder_id,
-                &source,
-                &destination,
-                &copy_size.into(),
-            )
-            .ok();
+        let result = self.gpu.call(
+            || format!("copy-texture-to-buffer {{ source: {:?}, destination: {:?}, size: {:?} }}", source, destination, copy_size),
+            |global| {
+                global.command_encoder_copy_texture_to_buffer(
+                    command_encoder_id,
+                    &source,
+                    &destination,
+                    &copy_size.into(),
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1241,12 +2379,13 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
         destination: GpuImageCopyTexture,
         copy_size: GpuExtentD3,
     ) -> () {
-        let command_encoder_id = self.table.get(&command_encoder).unwrap();
-        let source_texture_id = self.table.get(&source.texture).unwrap();
-        let destination_texture_id = self.table.get(&destination.texture).unwrap();
+        let label = self.gpu_label(&command_encoder);
+        let command_encoder_id = *self.table.get(&command_encoder).unwrap();
+        let source_texture_id = *self.table.get(&source.texture).unwrap();
+        let destination_texture_id = *self.table.get(&destination.texture).unwrap();
 
         let source = wgpu_core::command::ImageCopyTexture {
-            texture: *source_texture_id,
+            texture: source_texture_id,
             mip_level: source.mip_level,
             origin: Origin3d {
                 x: source.origin[0],
@@ -1256,7 +2395,7 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
             aspect: source.aspect.into(),
         };
         let destination = wgpu_core::command::ImageCopyTexture {
-            texture: *destination_texture_id,
+            texture: destination_texture_id,
             mip_level: destination.mip_level,
             origin: Origin3d {
                 x: destination.origin[0],
@@ -1266,14 +2405,18 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
             aspect: destination.aspect.into(),
         };
 
-        self.instance
-            .command_encoder_copy_texture_to_texture(
-                *command_encoder_id,
-                &source,
-                &destination,
-                &copy_size.into(),
-            )
-            .ok();
+        let result = self.gpu.call(
+            || format!("copy-texture-to-texture {{ source: {:?}, destination: {:?}, size: {:?} }}", source, destination, copy_size),
+            |global| {
+                global.command_encoder_copy_texture_to_texture(
+                    command_encoder_id,
+                    &source,
+                    &destination,
+                    &copy_size.into(),
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1284,17 +2427,22 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
         offset: Option<GpuSizeU64>,
         size: Option<GpuSizeU64>,
     ) -> () {
-        let command_encoder_id = self.table.get(&command_encoder).unwrap();
-        let buffer_id = self.table.get(&buffer).unwrap();
-
-        self.instance
-            .command_encoder_clear_buffer(
-                *command_encoder_id,
-                *buffer_id,
-                offset.unwrap_or(0),
-                size,
-            )
-            .ok();
+        let label = self.gpu_label(&command_encoder);
+        let command_encoder_id = *self.table.get(&command_encoder).unwrap();
+        let buffer_id = *self.table.get(&buffer).unwrap();
+
+        let result = self.gpu.call(
+            || format!("clear-buffer {{ buffer: {:?}, offset: {:?}, size: {:?} }}", buffer_id, offset, size),
+            |global| {
+                global.command_encoder_clear_buffer(
+                    command_encoder_id,
+                    buffer_id,
+                    offset.unwrap_or(0),
+                    size,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1304,16 +2452,21 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
         query_set: wasmtime::component::Resource<GpuQuerySet>,
         query_index: GpuSizeU32,
     ) -> () {
-        let command_encoder_id = self.table.get(&command_encoder).unwrap();
-        let query_set_id = self.table.get(&query_set).unwrap();
-
-        self.instance
-            .command_encoder_write_timestamp(
-                *command_encoder_id,
-                *query_set_id,
-                query_index,
-            )
-            .ok();
+        let label = self.gpu_label(&command_encoder);
+        let command_encoder_id = *self.table.get(&command_encoder).unwrap();
+        let query_set_id = *self.table.get(&query_set).unwrap();
+
+        let result = self.gpu.call(
+            || format!("write-timestamp {{ query_set: {:?}, query_index: {} }}", query_set_id, query_index),
+            |global| {
+                global.command_encoder_write_timestamp(
+                    command_encoder_id,
+                    query_set_id,
+                    query_index,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1326,20 +2479,25 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
         destination: wasmtime::component::Resource<GpuBuffer>,
         destination_offset: GpuSizeU64,
     ) -> () {
-        let command_encoder_id = self.table.get(&command_encoder).unwrap();
-        let query_set_id = self.table.get(&query_set).unwrap();
-        let destination_id = self.table.get(&destination).unwrap();
-
-        self.instance
-            .command_encoder_resolve_query_set(
-                *command_encoder_id,
-                *query_set_id,
-                first_query,
-                query_count,
-                *destination_id,
-                destination_offset,
-            )
-            .ok();
+        let label = self.gpu_label(&command_encoder);
+        let command_encoder_id = *self.table.get(&command_encoder).unwrap();
+        let query_set_id = *self.table.get(&query_set).unwrap();
+        let destination_id = *self.table.get(&destination).unwrap();
+
+        let result = self.gpu.call(
+            || format!("resolve-query-set {{ query_set: {:?}, first_query: {}, query_count: {}, destination: {:?}, destination_offset: {} }}", query_set_id, first_query, query_count, destination_id, destination_offset),
+            |global| {
+                global.command_encoder_resolve_query_set(
+                    command_encoder_id,
+                    query_set_id,
+                    first_query,
+                    query_count,
+                    destination_id,
+                    destination_offset,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1347,26 +2505,35 @@ impl HostGpuCommandEncoder for RuneRuntimeState {
         &mut self,
         command_encoder: wasmtime::component::Resource<GpuCommandEncoder>,
     ) -> Resource<GpuCommandBuffer> {
-        let command_encoder_id = self.table.get(&command_encoder).unwrap();
-
-        let command_buffer_id = wgpu_id(self.instance.command_encoder_finish(
-            *command_encoder_id,
-            &wgpu_types::CommandBufferDescriptor { label: None },
-        ))
-        .unwrap();
+        let command_encoder_id = *self.table.get(&command_encoder).unwrap();
+
+        // The command buffer takes the encoder's label, as the guest can't give it one.
+        let label = self.gpu_label(&command_encoder);
+        let finished = self.gpu.finish(command_encoder_id, |global| {
+            global.command_encoder_finish(
+                command_encoder_id,
+                &wgpu_types::CommandBufferDescriptor { label: label.as_deref().map(Cow::from) },
+            )
+        });
+        let command_buffer_id = self.gpu_id(label.as_deref(), finished);
 
-        self.table
+        let command_buffer = self
+            .table
             .push_child(command_buffer_id, &command_encoder)
-            .unwrap()
+            .unwrap();
+        if let Some(label) = label {
+            self.gpu_state.labels.insert(command_buffer.rep(), label);
+        }
+        command_buffer
     }
 
     async fn drop(
         &mut self,
         rep: wasmtime::component::Resource<GpuCommandEncoder>,
     ) -> wasmtime::Result<()> {
+        self.gpu_state.labels.remove(&rep.rep());
         let command_encoder_id = self.table.delete(rep).unwrap();
-        self.instance
-            .command_encoder_drop(command_encoder_id);
+        self.gpu.release(|global| global.command_encoder_drop(command_encoder_id));
         Ok(())
     }
 }
@@ -1377,12 +2544,16 @@ impl HostGpuComputePassEncoder for RuneRuntimeState {
         compute_pass_encoder: Resource<GpuComputePassEncoder>,
         pipeline: Resource<GpuComputePipeline>,
     ) -> () {
+        let label = self.gpu_label(&compute_pass_encoder);
         let pipeline_id = *self.table.get(&pipeline).unwrap();
-        let compute_pass_encoder_id = self.table.get_mut(&compute_pass_encoder).unwrap();
+        let pass = self.table.get_mut(&compute_pass_encoder).unwrap();
 
-        self.instance
-            .compute_pass_set_pipeline(compute_pass_encoder_id, pipeline_id)
-            .unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-pipeline {:?}", pipeline_id),
+            |global, pass| global.compute_pass_set_pipeline(pass, pipeline_id),
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1393,16 +2564,22 @@ impl HostGpuComputePassEncoder for RuneRuntimeState {
         workgroup_count_y: Option<GpuSizeU32>,
         workgroup_count_z: Option<GpuSizeU32>,
     ) -> () {
-        let compute_pass_encoder_id = self.table.get_mut(&compute_pass_encoder).unwrap();
-
-        self.instance
-            .compute_pass_dispatch_workgroups(
-                compute_pass_encoder_id,
-                workgroup_count_x,
-                workgroup_count_y.unwrap_or(1),
-                workgroup_count_z.unwrap_or(1),
-            )
-            .unwrap();
+        let label = self.gpu_label(&compute_pass_encoder);
+        let pass = self.table.get_mut(&compute_pass_encoder).unwrap();
+
+        let result = self.gpu.pass(
+            pass,
+            || format!("dispatch-workgroups {} {:?} {:?}", workgroup_count_x, workgroup_count_y, workgroup_count_z),
+            |global, pass| {
+                global.compute_pass_dispatch_workgroups(
+                    pass,
+                    workgroup_count_x,
+                    workgroup_count_y.unwrap_or(1),
+                    workgroup_count_z.unwrap_or(1),
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1413,26 +2590,36 @@ impl HostGpuComputePassEncoder for RuneRuntimeState {
         indirect_buffer: Resource<GpuBuffer>,
         indirect_offset: GpuSizeU64,
     ) -> () {
+        let label = self.gpu_label(&compute_pass_encoder);
         let indirect_buffer_id = *self.table.get(&indirect_buffer).unwrap();
-        let compute_pass_encoder_id = self.table.get_mut(&compute_pass_encoder).unwrap();
-
-        self.instance
-            .compute_pass_dispatch_workgroups_indirect(
-                compute_pass_encoder_id,
-                indirect_buffer_id,
-                indirect_offset,
-            )
-            .unwrap();
+        let pass = self.table.get_mut(&compute_pass_encoder).unwrap();
+
+        let result = self.gpu.pass(
+            pass,
+            || format!("dispatch-workgroups-indirect {:?} {}", indirect_buffer_id, indirect_offset),
+            |global, pass| {
+                global.compute_pass_dispatch_workgroups_indirect(
+                    pass,
+                    indirect_buffer_id,
+                    indirect_offset,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
 
     async fn end(&mut self, compute_pass_encoder: Resource<GpuComputePassEncoder>) -> () {
-        let compute_pass_encoder_id = self.table.get_mut(&compute_pass_encoder).unwrap();
+        let label = self.gpu_label(&compute_pass_encoder);
+        let pass = self.table.get_mut(&compute_pass_encoder).unwrap();
 
-        self.instance
-            .compute_pass_end(compute_pass_encoder_id)
-            .unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || "end".to_owned(),
+            |global, pass| global.compute_pass_end(pass),
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1443,19 +2630,25 @@ impl HostGpuComputePassEncoder for RuneRuntimeState {
         bind_group: Option<Resource<GpuBindGroup>>,
         dynamic_offsets: Option<Vec<GpuBufferDynamicOffset>>,
     ) -> () {
+        let label = self.gpu_label(&compute_pass_encoder);
         let bind_group_id = bind_group.map(|bg| *self.table.get(&bg).unwrap());
-        let compute_pass_encoder_id = self.table.get_mut(&compute_pass_encoder).unwrap();
+        let pass = self.table.get_mut(&compute_pass_encoder).unwrap();
 
         let dynamic_offsets_slice = dynamic_offsets.as_deref().unwrap_or(&[]);
 
-        self.instance
-            .compute_pass_set_bind_group(
-                compute_pass_encoder_id,
-                index,
-                bind_group_id,
-                dynamic_offsets_slice,
-            )
-            .unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-bind-group {} {:?} {:?}", index, bind_group_id, dynamic_offsets),
+            |global, pass| {
+                global.compute_pass_set_bind_group(
+                    pass,
+                    index,
+                    bind_group_id,
+                    dynamic_offsets_slice,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1468,8 +2661,9 @@ impl HostGpuComputePassEncoder for RuneRuntimeState {
         _dynamic_offsets_data_start: GpuSizeU64,
         _dynamic_offsets_data_lengh: GpuSizeU32,
     ) -> () {
+        let label = self.gpu_label(&_self_);
         let bind_group_id = _bind_group.map(|bg| *self.table.get(&bg).unwrap());
-        let compute_pass_encoder_id = self.table.get_mut(&_self_).unwrap();
+        let pass = self.table.get_mut(&_self_).unwrap();
 
         let dynamic_offsets_slice = if _dynamic_offsets_data_lengh > 0 {
             let start = _dynamic_offsets_data_start as usize;
@@ -1481,14 +2675,19 @@ impl HostGpuComputePassEncoder for RuneRuntimeState {
             &[]
         };
 
-        self.instance
-            .compute_pass_set_bind_group(
-                compute_pass_encoder_id,
-                _index,
-                bind_group_id,
-                dynamic_offsets_slice,
-            )
-            .unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-bind-group {} {:?}", _index, bind_group_id),
+            |global, pass| {
+                global.compute_pass_set_bind_group(
+                    pass,
+                    _index,
+                    bind_group_id,
+                    dynamic_offsets_slice,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1497,18 +2696,27 @@ impl HostGpuComputePassEncoder for RuneRuntimeState {
         compute_pass_encoder: Resource<GpuComputePassEncoder>,
         group_label: String,
     ) -> () {
-        let compute_pass_encoder_id = self.table.get_mut(&compute_pass_encoder).unwrap();
-        self.instance
-            .compute_pass_push_debug_group(compute_pass_encoder_id, &group_label, 0)
-            .unwrap();
+        let label = self.gpu_label(&compute_pass_encoder);
+        self.profiler_name_pass(compute_pass_encoder.rep(), &group_label);
+        let pass = self.table.get_mut(&compute_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("push-debug-group {:?}", group_label),
+            |global, pass| global.compute_pass_push_debug_group(pass, &group_label, 0),
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
     async fn pop_debug_group(&mut self, compute_pass_encoder: Resource<GpuComputePassEncoder>) -> () {
-        let compute_pass_encoder_id = self.table.get_mut(&compute_pass_encoder).unwrap();
-        self.instance
-            .compute_pass_pop_debug_group(compute_pass_encoder_id)
-            .unwrap();
+        let label = self.gpu_label(&compute_pass_encoder);
+        let pass = self.table.get_mut(&compute_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || "pop-debug-group".to_owned(),
+            |global, pass| global.compute_pass_pop_debug_group(pass),
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1517,18 +2725,28 @@ impl HostGpuComputePassEncoder for RuneRuntimeState {
         compute_pass_encoder: Resource<GpuComputePassEncoder>,
         marker_label: String,
     ) -> () {
-        let compute_pass_encoder_id = self.table.get_mut(&compute_pass_encoder).unwrap();
-        self.instance
-            .compute_pass_insert_debug_marker(
-                compute_pass_encoder_id,
-                &marker_label,
-                0,
-            )
-            .unwrap();
+        let label = self.gpu_label(&compute_pass_encoder);
+        let pass = self.table.get_mut(&compute_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("insert-debug-marker {:?}", marker_label),
+            |global, pass| {
+                global.compute_pass_insert_debug_marker(
+                    pass,
+                    &marker_label,
+                    0,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
     async fn drop(&mut self, rep: Resource<GpuComputePassEncoder>) -> Result<()> {
+        if let Some(profiler) = self.gpu_state.profiler.as_mut() {
+            profiler.active_passes.remove(&rep.rep());
+        }
+        self.gpu_state.labels.remove(&rep.rep());
         self.table.delete(rep).unwrap();
         Ok(())
     }
@@ -1540,12 +2758,16 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         render_pass_encoder: Resource<GpuRenderPassEncoder>,
         pipeline: Resource<GpuRenderPipeline>,
     ) -> () {
+        let label = self.gpu_label(&render_pass_encoder);
         let render_pipeline_id = *self.table.get(&pipeline).unwrap();
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
 
-        self.instance
-            .render_pass_set_pipeline(render_pass_encoder_id, render_pipeline_id)
-            .unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-pipeline {:?}", render_pipeline_id),
+            |global, pass| global.render_pass_set_pipeline(pass, render_pipeline_id),
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1557,18 +2779,24 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         offset: GpuSizeU64,
         size: Option<GpuSizeU64>,
     ) -> () {
+        let label = self.gpu_label(&render_pass_encoder);
         let buffer_id = *self.table.get(&buffer).unwrap();
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-
-        self.instance
-            .render_pass_set_index_buffer(
-                render_pass_encoder_id,
-                buffer_id,
-                index_format.into(),
-                offset,
-                size.map(|s| NonZeroU64::new(s).unwrap()),
-            )
-            .unwrap();
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-index-buffer {:?} {:?} {} {:?}", buffer_id, index_format, offset, size),
+            |global, pass| {
+                global.render_pass_set_index_buffer(
+                    pass,
+                    buffer_id,
+                    index_format.into(),
+                    offset,
+                    size.map(|s| NonZeroU64::new(s).unwrap()),
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1581,18 +2809,24 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         offset: GpuSizeU64,
         size: Option<GpuSizeU64>,
     ) -> () {
+        let label = self.gpu_label(&render_pass_encoder);
         let buffer_id = *self.table.get(&buffer).unwrap();
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-
-        self.instance
-            .render_pass_set_vertex_buffer(
-                render_pass_encoder_id,
-                slot,
-                buffer_id,
-                offset,
-                size.map(|s| NonZeroU64::new(s).unwrap()),
-            )
-            .unwrap();
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-vertex-buffer {} {:?} {} {:?}", slot, buffer_id, offset, size),
+            |global, pass| {
+                global.render_pass_set_vertex_buffer(
+                    pass,
+                    slot,
+                    buffer_id,
+                    offset,
+                    size.map(|s| NonZeroU64::new(s).unwrap()),
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1605,17 +2839,23 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         first_vertex: GpuSizeU32,
         first_instance: GpuSizeU32,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-
-        self.instance
-            .render_pass_draw(
-                render_pass_encoder_id,
-                vertex_count,
-                instance_count,
-                first_vertex,
-                first_instance,
-            )
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+
+        let result = self.gpu.pass(
+            pass,
+            || format!("draw {} {} {} {}", vertex_count, instance_count, first_vertex, first_instance),
+            |global, pass| {
+                global.render_pass_draw(
+                    pass,
+                    vertex_count,
+                    instance_count,
+                    first_vertex,
+                    first_instance,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1629,18 +2869,24 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         base_vertex: GpuSignedOffsetS32,
         first_instance: GpuSizeU32,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-
-        self.instance
-            .render_pass_draw_indexed(
-                render_pass_encoder_id,
-                index_count,
-                instance_count,
-                first_index,
-                base_vertex,
-                first_instance,
-            )
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+
+        let result = self.gpu.pass(
+            pass,
+            || format!("draw-indexed {} {} {} {} {}", index_count, instance_count, first_index, base_vertex, first_instance),
+            |global, pass| {
+                global.render_pass_draw_indexed(
+                    pass,
+                    index_count,
+                    instance_count,
+                    first_index,
+                    base_vertex,
+                    first_instance,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1651,16 +2897,22 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         indirect_buffer: Resource<GpuBuffer>,
         indirect_offset: GpuSizeU64,
     ) -> () {
+        let label = self.gpu_label(&render_pass_encoder);
         let buffer_id = *self.table.get(&indirect_buffer).unwrap();
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-
-        self.instance
-            .render_pass_draw_indirect(
-                render_pass_encoder_id,
-                buffer_id,
-                indirect_offset,
-            )
-            .unwrap();
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+
+        let result = self.gpu.pass(
+            pass,
+            || format!("draw-indirect {:?} {}", buffer_id, indirect_offset),
+            |global, pass| {
+                global.render_pass_draw_indirect(
+                    pass,
+                    buffer_id,
+                    indirect_offset,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1671,16 +2923,22 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         indirect_buffer: Resource<GpuBuffer>,
         indirect_offset: GpuSizeU64,
     ) -> () {
+        let label = self.gpu_label(&render_pass_encoder);
         let buffer_id = *self.table.get(&indirect_buffer).unwrap();
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-
-        self.instance
-            .render_pass_draw_indexed_indirect(
-                render_pass_encoder_id,
-                buffer_id,
-                indirect_offset,
-            )
-            .unwrap();
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+
+        let result = self.gpu.pass(
+            pass,
+            || format!("draw-indexed-indirect {:?} {}", buffer_id, indirect_offset),
+            |global, pass| {
+                global.render_pass_draw_indexed_indirect(
+                    pass,
+                    buffer_id,
+                    indirect_offset,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1695,18 +2953,24 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         min_depth: f32,
         max_depth: f32,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-        self.instance
-            .render_pass_set_viewport(
-                render_pass_encoder_id,
-                x,
-                y,
-                width,
-                height,
-                min_depth,
-                max_depth,
-            )
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-viewport {} {} {} {} {} {}", x, y, width, height, min_depth, max_depth),
+            |global, pass| {
+                global.render_pass_set_viewport(
+                    pass,
+                    x,
+                    y,
+                    width,
+                    height,
+                    min_depth,
+                    max_depth,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1719,16 +2983,22 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         width: GpuIntegerCoordinate,
         height: GpuIntegerCoordinate,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-        self.instance
-            .render_pass_set_scissor_rect(
-                render_pass_encoder_id,
-                x,
-                y,
-                width,
-                height,
-            )
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-scissor-rect {} {} {} {}", x, y, width, height),
+            |global, pass| {
+                global.render_pass_set_scissor_rect(
+                    pass,
+                    x,
+                    y,
+                    width,
+                    height,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1738,18 +3008,24 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         render_pass_encoder: Resource<GpuRenderPassEncoder>,
         color: GpuColor,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-        self.instance
-            .render_pass_set_blend_constant(
-                render_pass_encoder_id,
-                Color {
-                    r: color[0],
-                    g: color[1],
-                    b: color[2],
-                    a: color[3],
-                },
-            )
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-blend-constant {:?}", color),
+            |global, pass| {
+                global.render_pass_set_blend_constant(
+                    pass,
+                    Color {
+                        r: color[0],
+                        g: color[1],
+                        b: color[2],
+                        a: color[3],
+                    },
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1759,10 +3035,14 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         render_pass_encoder: Resource<GpuRenderPassEncoder>,
         reference: GpuStencilValue,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-        self.instance
-            .render_pass_set_stencil_reference(render_pass_encoder_id, reference)
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-stencil-reference {}", reference),
+            |global, pass| global.render_pass_set_stencil_reference(pass, reference),
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1772,13 +3052,19 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         render_pass_encoder: Resource<GpuRenderPassEncoder>,
         query_index: GpuSizeU32,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-        self.instance
-            .render_pass_begin_occlusion_query(
-                render_pass_encoder_id,
-                query_index,
-            )
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("begin-occlusion-query {}", query_index),
+            |global, pass| {
+                global.render_pass_begin_occlusion_query(
+                    pass,
+                    query_index,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1787,10 +3073,14 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         &mut self,
         render_pass_encoder: Resource<GpuRenderPassEncoder>,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-        self.instance
-            .render_pass_end_occlusion_query(render_pass_encoder_id)
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || "end-occlusion-query".to_owned(),
+            |global, pass| global.render_pass_end_occlusion_query(pass),
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1800,29 +3090,39 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         render_pass_encoder: Resource<GpuRenderPassEncoder>,
         bundles: Vec<Resource<GpuRenderBundle>>,
     ) -> () {
+        let label = self.gpu_label(&render_pass_encoder);
         let render_bundle_ids: Vec<_> = bundles
             .iter()
             .map(|b| *self.table.get(b).unwrap())
             .collect();
 
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
 
-        self.instance
-            .render_pass_execute_bundles(
-                render_pass_encoder_id,
-                &render_bundle_ids[..],
-            )
-            .unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("execute-bundles {:?}", render_bundle_ids),
+            |global, pass| {
+                global.render_pass_execute_bundles(
+                    pass,
+                    &render_bundle_ids[..],
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
 
     async fn end(&mut self, render_pass: Resource<GpuRenderPassEncoder>) -> () {
-        let render_pass = self.table.get_mut(&render_pass).unwrap();
+        let label = self.gpu_label(&render_pass);
+        let pass = self.table.get_mut(&render_pass).unwrap();
 
-        self.instance
-            .render_pass_end(render_pass)
-            .unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || "end".to_owned(),
+            |global, pass| global.render_pass_end(pass),
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1833,8 +3133,9 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         bind_group: Option<Resource<GpuBindGroup>>,
         dynamic_offsets: Option<Vec<GpuBufferDynamicOffset>>,
     ) -> () {
+        let label = self.gpu_label(&render_pass);
         let bind_group_id = *self.table.get(&bind_group.unwrap()).unwrap();
-        let render_pass = self.table.get_mut(&render_pass).unwrap();
+        let pass = self.table.get_mut(&render_pass).unwrap();
 
         let dynamic_offsets = if let Some(dynamic_offsets) = dynamic_offsets {
             dynamic_offsets
@@ -1845,14 +3146,19 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
             Vec::new()
         };
 
-        self.instance
-            .render_pass_set_bind_group(
-                render_pass,
-                index,
-                Some(bind_group_id),
-                &dynamic_offsets,
-            )
-            .unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-bind-group {} {:?} {:?}", index, bind_group_id, dynamic_offsets),
+            |global, pass| {
+                global.render_pass_set_bind_group(
+                    pass,
+                    index,
+                    Some(bind_group_id),
+                    &dynamic_offsets,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1866,8 +3172,9 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         _dynamic_offsets_data_start: GpuSizeU64,
         _dynamic_offsets_data_length: GpuSizeU32,
     ) -> () {
+        let label = self.gpu_label(&_render_pass_encoder);
         let bind_group_id = _bind_group.map(|bg| *self.table.get(&bg).unwrap());
-        let render_pass_encoder_id = self.table.get_mut(&_render_pass_encoder).unwrap();
+        let pass = self.table.get_mut(&_render_pass_encoder).unwrap();
 
         let dynamic_offsets_slice = if _dynamic_offsets_data_length > 0 {
             let start = _dynamic_offsets_data_start as usize;
@@ -1879,14 +3186,19 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
             &[]
         };
 
-        self.instance
-            .render_pass_set_bind_group(
-                render_pass_encoder_id,
-                _index,
-                bind_group_id,
-                dynamic_offsets_slice,
-            )
-            .unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("set-bind-group {} {:?}", _index, bind_group_id),
+            |global, pass| {
+                global.render_pass_set_bind_group(
+                    pass,
+                    _index,
+                    bind_group_id,
+                    dynamic_offsets_slice,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
         ()
     }
 
@@ -1895,19 +3207,28 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         render_pass_encoder: Resource<GpuRenderPassEncoder>,
         group_label: String,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-        self.instance
-            .render_pass_push_debug_group(render_pass_encoder_id, &group_label, 0)
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        self.profiler_name_pass(render_pass_encoder.rep(), &group_label);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("push-debug-group {:?}", group_label),
+            |global, pass| global.render_pass_push_debug_group(pass, &group_label, 0),
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
 
     async fn pop_debug_group(&mut self, render_pass_encoder: Resource<GpuRenderPassEncoder>) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-        self.instance
-            .render_pass_pop_debug_group(render_pass_encoder_id)
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || "pop-debug-group".to_owned(),
+            |global, pass| global.render_pass_pop_debug_group(pass),
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
@@ -1917,19 +3238,29 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
         render_pass_encoder: Resource<GpuRenderPassEncoder>,
         marker_label: String,
     ) -> () {
-        let render_pass_encoder_id = self.table.get_mut(&render_pass_encoder).unwrap();
-        self.instance
-            .render_pass_insert_debug_marker(
-                render_pass_encoder_id,
-                &marker_label,
-                0,
-            )
-            .unwrap();
+        let label = self.gpu_label(&render_pass_encoder);
+        let pass = self.table.get_mut(&render_pass_encoder).unwrap();
+        let result = self.gpu.pass(
+            pass,
+            || format!("insert-debug-marker {:?}", marker_label),
+            |global, pass| {
+                global.render_pass_insert_debug_marker(
+                    pass,
+                    &marker_label,
+                    0,
+                )
+            },
+        );
+        self.gpu_result(label.as_deref(), result);
 
         ()
     }
 
     async fn drop(&mut self, rep: Resource<GpuRenderPassEncoder>) -> Result<()> {
+        if let Some(profiler) = self.gpu_state.profiler.as_mut() {
+            profiler.active_passes.remove(&rep.rep());
+        }
+        self.gpu_state.labels.remove(&rep.rep());
         self.table.delete(rep).unwrap();
         Ok(())
     }
@@ -1937,9 +3268,9 @@ impl HostGpuRenderPassEncoder for RuneRuntimeState {
 
 impl HostGpuRenderBundle for RuneRuntimeState {
     async fn drop(&mut self, rep: Resource<GpuRenderBundle>) -> Result<()> {
+        self.gpu_state.resources.remove(&rep.rep());
         let render_bundle_id = self.table.delete(rep).unwrap();
-        self.instance
-            .render_bundle_drop(render_bundle_id);
+        self.gpu.release(|global| global.render_bundle_drop(render_bundle_id));
         Ok(())
     }
 }
@@ -1951,19 +3282,22 @@ impl HostGpuRenderBundleEncoder for RuneRuntimeState {
         descriptor: GpuRenderBundleDescriptor,
     ) -> Resource<GpuRenderBundle> {
         let render_bundle_encoder = self.table.delete(render_bundle_encoder_resource).unwrap();
-        let render_bundle_id = wgpu_id(
-            self.instance
-                .render_bundle_encoder_finish(
+        let created = self.gpu.create(
+            || format!("finish-render-bundle {{ label: {:?} }}", descriptor.label),
+            |global| {
+                global.render_bundle_encoder_finish(
                     render_bundle_encoder,
                     &wgpu_core::command::RenderBundleDescriptor {
-                        label: Some(descriptor.label.into()),
+                        label: Some(descriptor.label.as_str().into()),
                     },
                     None,
-                ),
-        )
-        .unwrap();
+                )
+            },
+        );
+        let render_bundle_id = self.gpu_id(Some(descriptor.label.as_str()), created);
 
-        self.table.push(render_bundle_id).unwrap()
+        let render_bundle = self.table.push(render_bundle_id).unwrap();
+        self.track_gpu_resource(render_bundle, GpuResourceKind::RenderBundle, Some(descriptor.label), 0)
     }
 
     async fn drop(&mut self, rep: Resource<GpuRenderBundleEncoder>) -> Result<()> {
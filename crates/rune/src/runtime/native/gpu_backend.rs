@@ -0,0 +1,452 @@
+use std::{collections::HashMap, error::Error, fmt, sync::Arc};
+
+use naga::valid::Capabilities;
+use wgpu_core::{
+    device::DeviceLostClosure,
+    global::Global,
+    id::{AdapterId, CommandBufferId, CommandEncoderId, DeviceId, Id, Marker, SurfaceId},
+    present::{SurfaceError, SurfaceOutput},
+};
+use wgpu_types::{
+    AllocatorReport, CompositeAlphaMode, Features, Limits, PresentMode, SurfaceCapabilities,
+    SurfaceStatus, TextureFormat, TextureFormatFeatures, TextureUsages,
+};
+
+use crate::runtime::gpu::{NullPass, PassEncoder};
+
+/// What shaders can use on every device the runtime creates. It only asks for features that
+/// leave shaders alone, so this is WebGPU's baseline. Shaders are validated against it wherever
+/// there's no device to ask, as on the null backend and when `rune build` checks them.
+pub const SHADER_CAPABILITIES: Capabilities =
+    Capabilities::MULTISAMPLED_SHADING.union(Capabilities::CUBE_ARRAY_TEXTURES);
+
+/// An error from a GPU call, which is wgpu's own or one found by the null backend.
+pub type GpuCallError = Box<dyn Error>;
+
+/// Where the runtime's GPU calls go. wgpu makes them on a real GPU, while the null backend
+/// validates and records them, so games can run where there's no GPU at all, such as on CI.
+///
+/// Calls that create or use resources go through `create`, `call` and `pass`, which take the
+/// wgpu call and what the null backend records in its place. Work that only means something
+/// on a real GPU, such as reading back a frame, asks for `wgpu` instead.
+pub enum Gpu {
+    Wgpu(Arc<Global>),
+    Null(NullGpu),
+}
+
+/// Stands in for the GPU when running with the null backend. Resources get ids that only exist
+/// here, and every call is validated and recorded instead of being executed.
+pub struct NullGpu {
+    next_index: u32,
+    commands: Vec<String>,
+    /// How many passes each command encoder has begun and not yet ended.
+    open_passes: HashMap<CommandEncoderId, u32>,
+}
+
+impl NullGpu {
+    pub fn new() -> NullGpu {
+        NullGpu {
+            next_index: 0,
+            commands: Vec::new(),
+            open_passes: HashMap::new(),
+        }
+    }
+
+    pub fn create_id<T: Marker>(&mut self) -> Id<T> {
+        let index = self.next_index;
+        self.next_index += 1;
+        Id::zip(index, 1)
+    }
+
+    /// Records a pass command, checking the pass is still open and that draws and dispatches
+    /// have a pipeline set.
+    fn pass(&mut self, pass: &mut NullPass, command: String) -> Result<(), NullGpuError> {
+        let name = command.split(' ').next().unwrap_or_default().to_owned();
+        self.commands.push(command);
+
+        if pass.ended {
+            return Err(NullGpuError(format!(
+                "'{}' called on a pass that has ended",
+                name
+            )));
+        }
+        match name.as_str() {
+            "set-pipeline" => pass.has_pipeline = true,
+            "end" => {
+                pass.ended = true;
+                if let Some(count) = self.open_passes.get_mut(&pass.encoder) {
+                    *count -= 1;
+                }
+            }
+            _ if (name.starts_with("draw") || name.starts_with("dispatch"))
+                && !pass.has_pipeline =>
+            {
+                return Err(NullGpuError(format!(
+                    "'{}' called before a pipeline was set",
+                    name
+                )));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// A validation error raised by the null backend.
+#[derive(Debug)]
+pub struct NullGpuError(pub String);
+
+impl fmt::Display for NullGpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for NullGpuError {}
+
+/// Fails the null backend's validation with `message` when `valid` is false.
+pub fn check(valid: bool, message: impl FnOnce() -> String) -> Result<(), NullGpuError> {
+    match valid {
+        true => Ok(()),
+        false => Err(NullGpuError(message())),
+    }
+}
+
+impl Gpu {
+    /// The wgpu instance, for work that only means something on a real GPU.
+    pub fn wgpu(&self) -> Option<Arc<Global>> {
+        match self {
+            Gpu::Wgpu(global) => Some(global.clone()),
+            Gpu::Null(_) => None,
+        }
+    }
+
+    /// The wgpu instance for work that takes several calls, or None once the null backend has
+    /// recorded `command` in its place.
+    pub fn wgpu_or_record(&mut self, command: impl FnOnce() -> String) -> Option<Arc<Global>> {
+        match self {
+            Gpu::Wgpu(global) => Some(global.clone()),
+            Gpu::Null(null) => {
+                null.commands.push(command());
+                None
+            }
+        }
+    }
+
+    /// Creates a resource with wgpu, or records `command` and hands out an id on the null backend.
+    pub fn create<T: Marker, E: Into<GpuCallError>>(
+        &mut self,
+        command: impl FnOnce() -> String,
+        create: impl FnOnce(&Global) -> (Id<T>, Option<E>),
+    ) -> (Id<T>, Option<GpuCallError>) {
+        self.create_checked(command, || Ok(()), create)
+    }
+
+    /// Like `create`, with what the null backend checks in place of wgpu's validation.
+    pub fn create_checked<T: Marker, E: Into<GpuCallError>>(
+        &mut self,
+        command: impl FnOnce() -> String,
+        validate: impl FnOnce() -> Result<(), NullGpuError>,
+        create: impl FnOnce(&Global) -> (Id<T>, Option<E>),
+    ) -> (Id<T>, Option<GpuCallError>) {
+        match self {
+            Gpu::Wgpu(global) => {
+                let (id, error) = create(global);
+                (id, error.map(Into::into))
+            }
+            Gpu::Null(null) => {
+                null.commands.push(command());
+                (null.create_id(), validate().err().map(Into::into))
+            }
+        }
+    }
+
+    /// Makes a call with wgpu, or records `command` on the null backend.
+    pub fn call<T, E: Into<GpuCallError>>(
+        &mut self,
+        command: impl FnOnce() -> String,
+        call: impl FnOnce(&Global) -> Result<T, E>,
+    ) -> Result<(), GpuCallError> {
+        self.call_checked(command, || Ok(()), call)
+    }
+
+    /// Like `call`, with what the null backend checks in place of wgpu's validation.
+    pub fn call_checked<T, E: Into<GpuCallError>>(
+        &mut self,
+        command: impl FnOnce() -> String,
+        validate: impl FnOnce() -> Result<(), NullGpuError>,
+        call: impl FnOnce(&Global) -> Result<T, E>,
+    ) -> Result<(), GpuCallError> {
+        match self {
+            Gpu::Wgpu(global) => call(global).map(|_| ()).map_err(Into::into),
+            Gpu::Null(null) => {
+                null.commands.push(command());
+                validate().map_err(Into::into)
+            }
+        }
+    }
+
+    /// Releases a resource, which the null backend has nothing behind.
+    pub fn release(&self, release: impl FnOnce(&Global)) {
+        if let Gpu::Wgpu(global) = self {
+            release(global);
+        }
+    }
+
+    /// Begins a render or compute pass on a command encoder.
+    pub fn begin_pass<P>(
+        &mut self,
+        encoder: CommandEncoderId,
+        command: impl FnOnce() -> String,
+        begin: impl FnOnce(&Global) -> P,
+    ) -> PassEncoder<P> {
+        match self {
+            Gpu::Wgpu(global) => PassEncoder::Wgpu(begin(global)),
+            Gpu::Null(null) => {
+                null.commands.push(command());
+                *null.open_passes.entry(encoder).or_default() += 1;
+                PassEncoder::Null(NullPass {
+                    encoder,
+                    has_pipeline: false,
+                    ended: false,
+                })
+            }
+        }
+    }
+
+    /// Makes a call in a pass begun with `begin_pass`.
+    pub fn pass<P, E: Into<GpuCallError>>(
+        &mut self,
+        pass: &mut PassEncoder<P>,
+        command: impl FnOnce() -> String,
+        call: impl FnOnce(&Global, &mut P) -> Result<(), E>,
+    ) -> Result<(), GpuCallError> {
+        match (self, pass) {
+            (Gpu::Wgpu(global), PassEncoder::Wgpu(pass)) => call(global, pass).map_err(Into::into),
+            (Gpu::Null(null), PassEncoder::Null(pass)) => {
+                null.pass(pass, command()).map_err(Into::into)
+            }
+            _ => unreachable!("Passes are only used with the backend that began them"),
+        }
+    }
+
+    /// Finishes a command encoder. The null backend checks none of its passes are still open.
+    pub fn finish<E: Into<GpuCallError>>(
+        &mut self,
+        encoder: CommandEncoderId,
+        finish: impl FnOnce(&Global) -> (CommandBufferId, Option<E>),
+    ) -> (CommandBufferId, Option<GpuCallError>) {
+        let open_passes = match self {
+            Gpu::Wgpu(_) => 0,
+            Gpu::Null(null) => null.open_passes.remove(&encoder).unwrap_or(0),
+        };
+        self.create_checked(
+            || format!("finish {:?}", encoder),
+            || {
+                check(open_passes == 0, || {
+                    "Command encoder finished while one of its passes is still open".to_owned()
+                })
+            },
+            finish,
+        )
+    }
+
+    /// Acquires the next texture of the surface. The null backend hands out a new texture each time.
+    pub fn surface_current_texture(
+        &mut self,
+        surface: SurfaceId,
+    ) -> Result<SurfaceOutput, SurfaceError> {
+        match self {
+            Gpu::Wgpu(global) => global.surface_get_current_texture(surface, None),
+            Gpu::Null(null) => {
+                null.commands.push("get-current-texture".to_owned());
+                Ok(SurfaceOutput {
+                    status: SurfaceStatus::Good,
+                    texture_id: Some(null.create_id()),
+                })
+            }
+        }
+    }
+
+    /// What the surface supports. The null backend has no surface to ask, so it gets the
+    /// capabilities of a typical one, as does a surface wgpu can't answer for.
+    pub fn surface_capabilities(
+        &self,
+        surface: SurfaceId,
+        adapter: AdapterId,
+    ) -> SurfaceCapabilities {
+        let typical = || SurfaceCapabilities {
+            formats: vec![TextureFormat::Bgra8UnormSrgb],
+            present_modes: vec![PresentMode::Fifo],
+            alpha_modes: vec![CompositeAlphaMode::Opaque],
+            usages: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        };
+        match self {
+            Gpu::Wgpu(global) => match global.surface_get_capabilities(surface, adapter) {
+                Ok(capabilities) => capabilities,
+                Err(err) => {
+                    super::debug::warn(&format!(
+                        "Couldn't get the surface's capabilities, assuming a typical surface: {}",
+                        err
+                    ));
+                    typical()
+                }
+            },
+            Gpu::Null(_) => typical(),
+        }
+    }
+
+    /// The limits of a device, which are the defaults on the null backend.
+    pub fn device_limits(&self, device: DeviceId) -> Limits {
+        match self {
+            Gpu::Wgpu(global) => global.device_limits(device),
+            Gpu::Null(_) => Limits::default(),
+        }
+    }
+
+    /// What an adapter supports for a texture format, which on the null backend is only what
+    /// every adapter supports.
+    pub fn texture_format_features(
+        &self,
+        adapter: AdapterId,
+        format: TextureFormat,
+    ) -> TextureFormatFeatures {
+        match self {
+            Gpu::Wgpu(global) => global.adapter_get_texture_format_features(adapter, format),
+            Gpu::Null(_) => format.guaranteed_format_features(Features::empty()),
+        }
+    }
+
+    /// Calls `closure` when the device is lost, which never happens on the null backend.
+    pub fn set_device_lost_closure(&self, device: DeviceId, closure: DeviceLostClosure) {
+        if let Gpu::Wgpu(global) = self {
+            global.device_set_device_lost_closure(device, closure);
+        }
+    }
+
+    /// How much memory the device has allocated, for backends that allocate through
+    /// gpu-allocator.
+    pub fn allocator_report(&self, device: DeviceId) -> Option<AllocatorReport> {
+        match self {
+            Gpu::Wgpu(global) => global.device_generate_allocator_report(device),
+            Gpu::Null(_) => None,
+        }
+    }
+
+    /// Takes the calls the null backend has recorded since it was last asked.
+    pub fn take_commands(&mut self) -> Vec<String> {
+        match self {
+            Gpu::Wgpu(_) => Vec::new(),
+            Gpu::Null(null) => std::mem::take(&mut null.commands),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn null_gpu() -> (Gpu, CommandEncoderId) {
+        let mut null = NullGpu::new();
+        let encoder = null.create_id();
+        (Gpu::Null(null), encoder)
+    }
+
+    fn begin(gpu: &mut Gpu, encoder: CommandEncoderId) -> PassEncoder<()> {
+        gpu.begin_pass(encoder, || "begin-render-pass".to_owned(), |_| ())
+    }
+
+    fn pass(gpu: &mut Gpu, pass: &mut PassEncoder<()>, command: &str) -> Result<(), String> {
+        gpu.pass(pass, || command.to_owned(), |_, _| Ok::<(), NullGpuError>(()))
+            .map_err(|error| error.to_string())
+    }
+
+    fn finish(gpu: &mut Gpu, encoder: CommandEncoderId) -> Option<String> {
+        let (_, error) = gpu.finish(encoder, |_| -> (CommandBufferId, Option<NullGpuError>) {
+            unreachable!()
+        });
+        error.map(|error| error.to_string())
+    }
+
+    #[test]
+    fn draws_need_a_pipeline() {
+        let (mut gpu, encoder) = null_gpu();
+        let mut render_pass = begin(&mut gpu, encoder);
+        assert_eq!(
+            pass(&mut gpu, &mut render_pass, "draw 3 1 0 0"),
+            Err("'draw' called before a pipeline was set".to_owned())
+        );
+        assert_eq!(
+            pass(&mut gpu, &mut render_pass, "draw-indexed 3 1 0 0 0"),
+            Err("'draw-indexed' called before a pipeline was set".to_owned())
+        );
+        assert_eq!(pass(&mut gpu, &mut render_pass, "set-pipeline 1"), Ok(()));
+        assert_eq!(pass(&mut gpu, &mut render_pass, "draw 3 1 0 0"), Ok(()));
+    }
+
+    #[test]
+    fn dispatches_need_a_pipeline() {
+        let (mut gpu, encoder) = null_gpu();
+        let mut compute_pass = begin(&mut gpu, encoder);
+        assert_eq!(
+            pass(&mut gpu, &mut compute_pass, "dispatch-workgroups 1 1 1"),
+            Err("'dispatch-workgroups' called before a pipeline was set".to_owned())
+        );
+    }
+
+    #[test]
+    fn ended_passes_take_no_more_calls() {
+        let (mut gpu, encoder) = null_gpu();
+        let mut render_pass = begin(&mut gpu, encoder);
+        assert_eq!(pass(&mut gpu, &mut render_pass, "end"), Ok(()));
+        assert_eq!(
+            pass(&mut gpu, &mut render_pass, "set-pipeline 1"),
+            Err("'set-pipeline' called on a pass that has ended".to_owned())
+        );
+        assert_eq!(
+            pass(&mut gpu, &mut render_pass, "end"),
+            Err("'end' called on a pass that has ended".to_owned())
+        );
+    }
+
+    #[test]
+    fn finish_needs_every_pass_ended() {
+        let (mut gpu, encoder) = null_gpu();
+        let mut first = begin(&mut gpu, encoder);
+        let _second = begin(&mut gpu, encoder);
+        pass(&mut gpu, &mut first, "end").unwrap();
+        assert_eq!(
+            finish(&mut gpu, encoder),
+            Some("Command encoder finished while one of its passes is still open".to_owned())
+        );
+    }
+
+    #[test]
+    fn finish_succeeds_once_passes_have_ended() {
+        let (mut gpu, encoder) = null_gpu();
+        for _ in 0..2 {
+            let mut render_pass = begin(&mut gpu, encoder);
+            pass(&mut gpu, &mut render_pass, "end").unwrap();
+        }
+        assert_eq!(finish(&mut gpu, encoder), None);
+    }
+
+    #[test]
+    fn calls_are_recorded_in_order() {
+        let (mut gpu, encoder) = null_gpu();
+        let mut render_pass = begin(&mut gpu, encoder);
+        pass(&mut gpu, &mut render_pass, "set-pipeline 1").unwrap();
+        pass(&mut gpu, &mut render_pass, "end").unwrap();
+        finish(&mut gpu, encoder);
+        assert_eq!(
+            gpu.take_commands(),
+            vec![
+                "begin-render-pass".to_owned(),
+                "set-pipeline 1".to_owned(),
+                "end".to_owned(),
+                format!("finish {:?}", encoder),
+            ]
+        );
+        assert!(gpu.take_commands().is_empty());
+    }
+}
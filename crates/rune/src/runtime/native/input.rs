@@ -27,7 +27,11 @@ impl Host for RuneRuntimeState {
 impl HostGamepadDevice for RuneRuntimeState {
     async fn name(&mut self, gamepad: Resource<GamepadDevice>) -> String {
         let gamepad_id = self.table.get(&gamepad).unwrap();
-        let gamepad = self.gilrs.connected_gamepad(*gamepad_id).unwrap();
+        // Without gilrs there are no gamepads to name.
+        let Some(gilrs) = self.gilrs.as_ref() else {
+            return String::new();
+        };
+        let gamepad = gilrs.connected_gamepad(*gamepad_id).unwrap();
 
         gamepad.name().to_owned()
     }
@@ -130,12 +134,28 @@ impl HostMouseDevice for RuneRuntimeState {
         false
     }
 
+    async fn position(&mut self, _device: Resource<MouseDevice>) -> Option<(f32, f32)> {
+        let (x, y) = self.pointer_state.cursor_position?;
+        Some(self.window_to_render_position(x, y))
+    }
+
     async fn drop(&mut self, _rep: Resource<MouseDevice>) -> Result<()> {
         Ok(())
     }
 }
 
 impl HostTouchDevice for RuneRuntimeState {
+    async fn touches(&mut self, _device: Resource<TouchDevice>) -> Vec<TouchPoint> {
+        self.pointer_state
+            .touches
+            .iter()
+            .map(|&(id, x, y)| {
+                let (x, y) = self.window_to_render_position(x, y);
+                TouchPoint { id, x, y }
+            })
+            .collect()
+    }
+
     async fn drop(&mut self, _rep: Resource<TouchDevice>) -> Result<()> {
         Ok(())
     }
@@ -0,0 +1,460 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use wgpu_core::{
+    binding_model::{
+        BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindingResource,
+        PipelineLayoutDescriptor,
+    },
+    command::{PassChannel, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor},
+    global::Global,
+    id::{BindGroupLayoutId, RenderPipelineId, ShaderModuleId, TextureId},
+    pipeline::{FragmentState, ProgrammableStageDescriptor, RenderPipelineDescriptor, VertexState},
+    resource::TextureViewDescriptor,
+};
+use wgpu_types::{
+    BindGroupLayoutEntry, BindingType, Color, ColorTargetState, ColorWrites, CompareFunction,
+    DepthStencilState, ImageSubresourceRange, LoadOp, SamplerBindingType, ShaderStages, StoreOp,
+    TextureAspect, TextureDimension, TextureFormat, TextureFormatFeatureFlags, TextureSampleType,
+    TextureUsages, TextureViewDimension,
+};
+
+use crate::rune::runtime::gpu::GpuMipmapFilter;
+
+use super::state::RuneRuntimeState;
+
+/// Draws a triangle covering the whole target, with UVs running from the top left.
+const FULLSCREEN_VERTEX: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+"#;
+
+/// Samples the level above between its texels, which averages them. Views of sRGB formats decode
+/// and encode around the sample, so the average is of linear colors.
+const COLOR_FRAGMENT: &str = r#"
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@fragment
+fn fs_linear(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
+"#;
+
+/// Reduces every texel of the level above that the output pixel covers, which is three rather
+/// than two in a direction with an odd size, so no depth is lost.
+const DEPTH_FRAGMENT: &str = r#"
+@group(0) @binding(0) var source: texture_depth_2d;
+
+fn reduce(position: vec4<f32>, keep_max: bool) -> f32 {
+    let source_size = textureDimensions(source);
+    let size = max(source_size / 2u, vec2<u32>(1u));
+    let pixel = vec2<u32>(position.xy);
+    let start = pixel * source_size / size;
+    let end = ((pixel + 1u) * source_size + size - 1u) / size;
+
+    var depth = select(1.0, 0.0, keep_max);
+    for (var y = start.y; y < end.y; y++) {
+        for (var x = start.x; x < end.x; x++) {
+            let texel = textureLoad(source, vec2<u32>(x, y), 0);
+            depth = select(min(depth, texel), max(depth, texel), keep_max);
+        }
+    }
+    return depth;
+}
+
+@fragment
+fn fs_min(in: VertexOutput) -> @builtin(frag_depth) f32 {
+    return reduce(in.position, false);
+}
+
+@fragment
+fn fs_max(in: VertexOutput) -> @builtin(frag_depth) f32 {
+    return reduce(in.position, true);
+}
+"#;
+
+impl RuneRuntimeState {
+    /// Fills each mip level after the first from the one above it, for every array layer.
+    pub(super) fn generate_texture_mipmaps(
+        &mut self,
+        texture_id: TextureId,
+        filter: GpuMipmapFilter,
+    ) -> Result<(), String> {
+        let texture = self
+            .gpu_state
+            .textures
+            .get(&texture_id)
+            .ok_or("Mipmaps can't be generated for surface textures")?;
+        let format = texture.format;
+        let mip_level_count = texture.mip_level_count;
+        let layer_count = texture.depth_or_array_layers;
+
+        if texture.dimension != TextureDimension::D2 {
+            return Err("Mipmaps can only be generated for 2D and 2D array textures".to_owned());
+        }
+        if texture.sample_count > 1 {
+            return Err("Mipmaps can't be generated for multisampled textures".to_owned());
+        }
+        if !texture
+            .usage
+            .contains(TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT)
+        {
+            return Err("Generating mipmaps needs texture-binding and render-attachment usage".to_owned());
+        }
+
+        let depth = format.is_depth_stencil_format();
+        let entry_point = match (depth, filter) {
+            (false, GpuMipmapFilter::Linear) => "fs_linear",
+            (true, GpuMipmapFilter::Min) => "fs_min",
+            (true, GpuMipmapFilter::Max) => "fs_max",
+            (true, GpuMipmapFilter::Linear) => {
+                return Err("Depth can't be averaged, use the min or max filter".to_owned())
+            }
+            (false, _) => return Err("The min and max filters are only for depth textures".to_owned()),
+        };
+        if depth && format.has_stencil_aspect() {
+            return Err(format!("Mipmaps can't be generated for {:?}, which has a stencil aspect", format));
+        }
+        if !depth {
+            let features = self.gpu.texture_format_features(self.adapter, format);
+            if !features.flags.contains(TextureFormatFeatureFlags::FILTERABLE)
+                || !features.allowed_usages.contains(TextureUsages::RENDER_ATTACHMENT)
+            {
+                return Err(format!("{:?} textures can't be filtered and rendered to", format));
+            }
+        }
+
+        if mip_level_count < 2 {
+            return Ok(());
+        }
+        let Some(global) = self
+            .gpu
+            .wgpu_or_record(|| format!("generate-mipmaps {{ texture: {:?}, filter: {:?} }}", texture_id, filter))
+        else {
+            return Ok(());
+        };
+
+        let (pipeline_id, bind_group_layout_id) = self.mipmap_pipeline(&global, format, entry_point);
+        let aspect = match depth {
+            true => TextureAspect::DepthOnly,
+            false => TextureAspect::All,
+        };
+
+        let command_encoder_id = self.gpu_id(
+            Some("generate-mipmaps"),
+            global.device_create_command_encoder(
+                self.device,
+                &wgpu_types::CommandEncoderDescriptor {
+                    label: Some("generate-mipmaps".into()),
+                },
+                None,
+            ),
+        );
+
+        let mut views = Vec::new();
+        let mut bind_groups = Vec::new();
+        for layer in 0..layer_count {
+            for mip in 1..mip_level_count {
+                let view = |mip: u32| TextureViewDescriptor {
+                    label: None,
+                    format: None,
+                    dimension: Some(TextureViewDimension::D2),
+                    range: ImageSubresourceRange {
+                        aspect,
+                        base_mip_level: mip,
+                        mip_level_count: Some(1),
+                        base_array_layer: layer,
+                        array_layer_count: Some(1),
+                    },
+                    ..Default::default()
+                };
+                let source_id = self.gpu_id(
+                    Some("generate-mipmaps"),
+                    global.texture_create_view(texture_id, &view(mip - 1), None),
+                );
+                let target_id = self.gpu_id(
+                    Some("generate-mipmaps"),
+                    global.texture_create_view(texture_id, &view(mip), None),
+                );
+                views.extend([source_id, target_id]);
+
+                let mut entries = vec![BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(source_id),
+                }];
+                if let Some(sampler_id) = self.gpu_state.mipmaps.sampler.filter(|_| !depth) {
+                    entries.push(BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(sampler_id),
+                    });
+                }
+                let bind_group_id = self.gpu_id(
+                    Some("generate-mipmaps"),
+                    global.device_create_bind_group(
+                        self.device,
+                        &BindGroupDescriptor {
+                            label: None,
+                            layout: bind_group_layout_id,
+                            entries: Cow::Owned(entries),
+                        },
+                        None,
+                    ),
+                );
+                bind_groups.push(bind_group_id);
+
+                let color_attachments = match depth {
+                    true => vec![],
+                    false => vec![Some(RenderPassColorAttachment {
+                        view: target_id,
+                        resolve_target: None,
+                        load_op: LoadOp::Clear(Color::TRANSPARENT),
+                        store_op: StoreOp::Store,
+                    })],
+                };
+                let depth_stencil_attachment = depth.then(|| RenderPassDepthStencilAttachment {
+                    view: target_id,
+                    depth: PassChannel {
+                        load_op: Some(LoadOp::Clear(Some(0.0))),
+                        store_op: Some(StoreOp::Store),
+                        read_only: false,
+                    },
+                    stencil: PassChannel {
+                        load_op: None,
+                        store_op: None,
+                        read_only: false,
+                    },
+                });
+
+                let (mut render_pass, error) = global.command_encoder_create_render_pass(
+                    command_encoder_id,
+                    &RenderPassDescriptor {
+                        label: Some("generate-mipmaps".into()),
+                        color_attachments: Cow::Owned(color_attachments),
+                        depth_stencil_attachment: depth_stencil_attachment.as_ref(),
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    },
+                );
+                if let Some(error) = error {
+                    self.gpu_result::<(), _>(Some("generate-mipmaps"), Err(error));
+                }
+                let result = global.render_pass_set_pipeline(&mut render_pass, pipeline_id);
+                self.gpu_result(Some("generate-mipmaps"), result);
+                let result = global
+                    .render_pass_set_bind_group(&mut render_pass, 0, Some(bind_group_id), &[]);
+                self.gpu_result(Some("generate-mipmaps"), result);
+                let result = global.render_pass_draw(&mut render_pass, 3, 1, 0, 0);
+                self.gpu_result(Some("generate-mipmaps"), result);
+                let result = global.render_pass_end(&mut render_pass);
+                self.gpu_result(Some("generate-mipmaps"), result);
+            }
+        }
+
+        let command_buffer_id = self.gpu_id(
+            Some("generate-mipmaps"),
+            global.command_encoder_finish(
+                command_encoder_id,
+                &wgpu_types::CommandBufferDescriptor { label: None },
+            ),
+        );
+        let result = global
+            .queue_submit(self.queue, &[command_buffer_id])
+            .map_err(|(_, error)| error);
+        self.gpu_result(Some("generate-mipmaps"), result);
+
+        // wgpu keeps these alive until the submission has finished with them.
+        for bind_group_id in bind_groups {
+            global.bind_group_drop(bind_group_id);
+        }
+        for view_id in views {
+            let result = global.texture_view_drop(view_id);
+            self.gpu_result(Some("generate-mipmaps"), result);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the pipeline drawing `entry_point` into `format` targets, creating it, and the
+    /// shader and sampler it needs, on first use.
+    pub(super) fn mipmap_pipeline(
+        &mut self,
+        global: &Global,
+        format: TextureFormat,
+        entry_point: &'static str,
+    ) -> (RenderPipelineId, BindGroupLayoutId) {
+        if let Some(pipeline) = self.gpu_state.mipmaps.pipelines.get(&(format, entry_point)) {
+            return *pipeline;
+        }
+
+        let depth = format.is_depth_stencil_format();
+        let shader_module_id = match depth {
+            true => self.gpu_state.mipmaps.depth_shader,
+            false => self.gpu_state.mipmaps.color_shader,
+        };
+        let shader_module_id = match shader_module_id {
+            Some(shader_module_id) => shader_module_id,
+            None => {
+                let fragment = if depth { DEPTH_FRAGMENT } else { COLOR_FRAGMENT };
+                let shader_module_id = self.mipmap_shader(global, fragment);
+                match depth {
+                    true => self.gpu_state.mipmaps.depth_shader = Some(shader_module_id),
+                    false => self.gpu_state.mipmaps.color_shader = Some(shader_module_id),
+                }
+                shader_module_id
+            }
+        };
+
+        if !depth && self.gpu_state.mipmaps.sampler.is_none() {
+            let sampler_id = self.gpu_id(
+                Some("generate-mipmaps"),
+                global.device_create_sampler(
+                    self.device,
+                    &wgpu_core::resource::SamplerDescriptor {
+                        label: Some("generate-mipmaps".into()),
+                        address_modes: [wgpu_types::AddressMode::ClampToEdge; 3],
+                        mag_filter: wgpu_types::FilterMode::Linear,
+                        min_filter: wgpu_types::FilterMode::Linear,
+                        mipmap_filter: wgpu_types::FilterMode::Nearest,
+                        lod_min_clamp: 0.0,
+                        lod_max_clamp: 32.0,
+                        compare: None,
+                        anisotropy_clamp: 1,
+                        border_color: None,
+                    },
+                    None,
+                ),
+            );
+            self.gpu_state.mipmaps.sampler = Some(sampler_id);
+        }
+
+        let mut entries = vec![BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: match depth {
+                    true => TextureSampleType::Depth,
+                    false => TextureSampleType::Float { filterable: true },
+                },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }];
+        if !depth {
+            entries.push(BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+        let bind_group_layout_id = self.gpu_id(
+            Some("generate-mipmaps"),
+            global.device_create_bind_group_layout(
+                self.device,
+                &BindGroupLayoutDescriptor {
+                    label: Some("generate-mipmaps".into()),
+                    entries: Cow::Owned(entries),
+                },
+                None,
+            ),
+        );
+        let pipeline_layout_id = self.gpu_id(
+            Some("generate-mipmaps"),
+            global.device_create_pipeline_layout(
+                self.device,
+                &PipelineLayoutDescriptor {
+                    label: Some("generate-mipmaps".into()),
+                    bind_group_layouts: Cow::Borrowed(&[bind_group_layout_id]),
+                    push_constant_ranges: Cow::Borrowed(&[]),
+                },
+                None,
+            ),
+        );
+
+        let stage = |entry_point: &'static str| ProgrammableStageDescriptor {
+            module: shader_module_id,
+            entry_point: Some(Cow::Borrowed(entry_point)),
+            constants: Cow::Owned(HashMap::new()),
+            zero_initialize_workgroup_memory: false,
+        };
+        let color_targets = match depth {
+            true => vec![],
+            false => vec![Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        };
+        let pipeline_id = self.gpu_id(
+            Some("generate-mipmaps"),
+            global.device_create_render_pipeline(
+                self.device,
+                &RenderPipelineDescriptor {
+                    label: Some("generate-mipmaps".into()),
+                    layout: Some(pipeline_layout_id),
+                    vertex: VertexState {
+                        stage: stage("vs_main"),
+                        buffers: Cow::Borrowed(&[]),
+                    },
+                    primitive: Default::default(),
+                    depth_stencil: depth.then(|| DepthStencilState {
+                        format,
+                        depth_write_enabled: true,
+                        depth_compare: CompareFunction::Always,
+                        stencil: Default::default(),
+                        bias: Default::default(),
+                    }),
+                    multisample: Default::default(),
+                    fragment: Some(FragmentState {
+                        stage: stage(entry_point),
+                        targets: Cow::Owned(color_targets),
+                    }),
+                    multiview: None,
+                    cache: self
+                        .pipeline_cache
+                        .as_ref()
+                        .and_then(|pipeline_cache| pipeline_cache.pipeline_cache),
+                },
+                None,
+                None,
+            ),
+        );
+        global.pipeline_layout_drop(pipeline_layout_id);
+
+        self.gpu_state
+            .mipmaps
+            .pipelines
+            .insert((format, entry_point), (pipeline_id, bind_group_layout_id));
+        (pipeline_id, bind_group_layout_id)
+    }
+
+    fn mipmap_shader(&mut self, global: &Global, fragment: &str) -> ShaderModuleId {
+        self.gpu_id(
+            Some("generate-mipmaps"),
+            global.device_create_shader_module(
+                self.device,
+                &wgpu_core::pipeline::ShaderModuleDescriptor {
+                    label: Some("generate-mipmaps".into()),
+                    runtime_checks: Default::default(),
+                },
+                wgpu_core::pipeline::ShaderModuleSource::Wgsl(Cow::Owned(format!(
+                    "{}{}",
+                    FULLSCREEN_VERTEX, fragment
+                ))),
+                None,
+            ),
+        )
+    }
+}
@@ -1,10 +1,25 @@
 pub mod audio;
+pub mod audio_decode;
+pub mod audio_devices;
+pub mod audio_input;
+pub mod audio_output;
+pub mod audio_spatial;
+pub mod audio_stream;
+pub mod audio_worklet;
+pub mod cloud;
 pub mod debug;
 pub mod gpu;
+pub mod gpu_backend;
 pub mod input;
+pub mod mipmaps;
 pub mod network;
+pub mod pipeline_cache;
+pub mod replay;
 pub mod run;
+pub mod scaling;
 pub mod state;
 pub mod storage;
+pub mod surface;
+pub mod texture_decoder;
 pub mod utilities;
 pub mod window;
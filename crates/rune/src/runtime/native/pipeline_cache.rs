@@ -0,0 +1,253 @@
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use wgpu_core::{
+    global::Global,
+    id::{AdapterId, DeviceId, PipelineCacheId},
+    pipeline::PipelineCacheDescriptor,
+};
+
+use crate::rune::runtime::gpu::{GpuGlslStage, GpuShaderSource};
+
+use super::debug;
+
+/// Shader and pipeline compilation results kept in the game's data directory between runs,
+/// so later launches skip most of the work of recompiling them.
+pub struct PipelineCache {
+    /// The cache of one adapter, named after a hash of it and its driver, so switching between
+    /// GPUs keeps both caches and a new driver starts a new one.
+    dir: PathBuf,
+    /// The driver's own pipeline cache, on backends that support one.
+    pub pipeline_cache: Option<PipelineCacheId>,
+}
+
+impl PipelineCache {
+    /// Opens the cache under `data_dir` for the adapter.
+    pub fn open(
+        data_dir: &Path,
+        instance: &Global,
+        adapter: AdapterId,
+        device: DeviceId,
+    ) -> Option<PipelineCache> {
+        let mut cache = PipelineCache::for_adapter(data_dir, &adapter_key(instance, adapter))?;
+
+        if instance
+            .device_features(device)
+            .contains(wgpu_types::Features::PIPELINE_CACHE)
+        {
+            let data = fs::read(cache.dir.join("pipelines.bin")).ok();
+            // SAFETY: the data was written by `pipeline_cache_get_data` for this adapter and
+            // driver, which the directory name guarantees, and wgpu checks its header again.
+            let (pipeline_cache, error) = unsafe {
+                instance.device_create_pipeline_cache(
+                    device,
+                    &PipelineCacheDescriptor {
+                        label: Some("pipeline cache".into()),
+                        data: data.as_deref().map(Cow::Borrowed),
+                        fallback: true,
+                    },
+                    None,
+                )
+            };
+            match error {
+                Some(err) => debug::warn(&format!("Couldn't load the pipeline cache: {}", err)),
+                None => cache.pipeline_cache = Some(pipeline_cache),
+            }
+        }
+
+        Some(cache)
+    }
+
+    /// Opens the cache under `data_dir` for the adapter identified by `adapter_key`, without
+    /// the driver's pipeline cache.
+    fn for_adapter(data_dir: &Path, adapter_key: &str) -> Option<PipelineCache> {
+        let key = blake3::hash(adapter_key.as_bytes());
+        let dir = data_dir.join("gpu-cache").join(&key.to_hex()[..16]);
+        if let Err(err) = fs::create_dir_all(dir.join("shaders")) {
+            debug::warn(&format!(
+                "Couldn't create the pipeline cache in {}: {}",
+                dir.display(),
+                err
+            ));
+            return None;
+        }
+
+        Some(PipelineCache {
+            dir,
+            pipeline_cache: None,
+        })
+    }
+
+    /// Returns the parsed naga module for a shader, from the cache when it has been seen before.
+    /// Sources that don't parse return None, so wgpu can report the error as usual.
+    pub fn shader_module(&self, source: &GpuShaderSource) -> Option<naga::Module> {
+        if let Some(module) = self.cached_shader_module(source) {
+            return Some(module);
+        }
+
+        let module = match source {
+            GpuShaderSource::Wgsl(code) => naga::front::wgsl::parse_str(code).ok()?,
+            GpuShaderSource::Spirv(words) => naga::front::spv::Frontend::new(
+                words.iter().copied(),
+                &naga::front::spv::Options {
+                    adjust_coordinate_space: false,
+                    strict_capabilities: true,
+                    block_ctx_dump_prefix: None,
+                },
+            )
+            .parse()
+            .ok()?,
+            GpuShaderSource::Glsl((code, stage)) => naga::front::glsl::Frontend::default()
+                .parse(
+                    &naga::front::glsl::Options::from(<GpuGlslStage as Into<naga::ShaderStage>>::into(*stage)),
+                    code,
+                )
+                .ok()?,
+        };
+
+        // A failed write only costs the next launch a parse.
+        if let Ok(bytes) = bincode::serialize(&module) {
+            let _ = fs::write(self.shader_path(source), bytes);
+        }
+
+        Some(module)
+    }
+
+    /// The module saved for a shader by an earlier `shader_module`, if any.
+    fn cached_shader_module(&self, source: &GpuShaderSource) -> Option<naga::Module> {
+        let bytes = fs::read(self.shader_path(source)).ok()?;
+        bincode::deserialize(&bytes).ok()
+    }
+
+    fn shader_path(&self, source: &GpuShaderSource) -> PathBuf {
+        self.dir
+            .join("shaders")
+            .join(format!("{}.naga", &shader_hash(source).to_hex()[..16]))
+    }
+
+    /// Writes the driver's pipeline cache to disk, including pipelines created during this run.
+    pub fn save(&self, instance: &Global) {
+        let Some(pipeline_cache_id) = self.pipeline_cache else {
+            return;
+        };
+        if let Some(data) = instance.pipeline_cache_get_data(pipeline_cache_id) {
+            if let Err(err) = fs::write(self.dir.join("pipelines.bin"), data) {
+                debug::warn(&format!("Couldn't save the pipeline cache: {}", err));
+            }
+        }
+    }
+}
+
+/// Identifies everything that makes cached output invalid when it changes.
+fn adapter_key(instance: &Global, adapter: AdapterId) -> String {
+    let info = instance.adapter_get_info(adapter);
+    format!(
+        "{}\n{:?}\n{:x}:{:x}\n{}\n{}\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        info.backend,
+        info.vendor,
+        info.device,
+        info.name,
+        info.driver,
+        info.driver_info
+    )
+}
+
+/// A hash of the shader's language and source, which is stable across Rust releases.
+fn shader_hash(source: &GpuShaderSource) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new();
+    match source {
+        GpuShaderSource::Wgsl(code) => {
+            hasher.update(b"wgsl\n");
+            hasher.update(code.as_bytes());
+        }
+        GpuShaderSource::Spirv(words) => {
+            hasher.update(b"spirv\n");
+            for word in words {
+                hasher.update(&word.to_le_bytes());
+            }
+        }
+        GpuShaderSource::Glsl((code, stage)) => {
+            let stage: naga::ShaderStage = (*stage).into();
+            hasher.update(format!("glsl {:?}\n", stage).as_bytes());
+            hasher.update(code.as_bytes());
+        }
+    }
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = "@fragment fn main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }";
+
+    /// A data directory for one test, which is removed afterwards.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            let dir = std::env::temp_dir().join(format!("rune-pipeline-cache-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn shader_modules_are_loaded_on_the_next_launch() {
+        let data_dir = TempDir::new();
+        let source = GpuShaderSource::Wgsl(SHADER.to_owned());
+
+        let cache = PipelineCache::for_adapter(&data_dir.0, "adapter").unwrap();
+        assert!(cache.cached_shader_module(&source).is_none());
+        let module = cache.shader_module(&source).unwrap();
+
+        let cache = PipelineCache::for_adapter(&data_dir.0, "adapter").unwrap();
+        let cached = cache.cached_shader_module(&source).unwrap();
+        assert_eq!(cached.entry_points.len(), module.entry_points.len());
+        assert_eq!(cached.entry_points[0].name, "main");
+    }
+
+    #[test]
+    fn another_adapter_starts_an_empty_cache() {
+        let data_dir = TempDir::new();
+        let source = GpuShaderSource::Wgsl(SHADER.to_owned());
+
+        let cache = PipelineCache::for_adapter(&data_dir.0, "adapter").unwrap();
+        cache.shader_module(&source).unwrap();
+
+        let other = PipelineCache::for_adapter(&data_dir.0, "new driver").unwrap();
+        assert_ne!(other.dir, cache.dir);
+        assert!(other.cached_shader_module(&source).is_none());
+        // The first adapter's cache is still there for when it's used again.
+        assert!(cache.cached_shader_module(&source).is_some());
+    }
+
+    #[test]
+    fn shaders_are_keyed_by_language_and_source() {
+        let wgsl = GpuShaderSource::Wgsl(SHADER.to_owned());
+        let glsl = GpuShaderSource::Glsl((SHADER.to_owned(), GpuGlslStage::Fragment));
+        assert_eq!(shader_hash(&wgsl), shader_hash(&GpuShaderSource::Wgsl(SHADER.to_owned())));
+        assert_ne!(shader_hash(&wgsl), shader_hash(&glsl));
+        assert_ne!(shader_hash(&wgsl), shader_hash(&GpuShaderSource::Wgsl(format!("{}\n", SHADER))));
+    }
+
+    #[test]
+    fn sources_that_dont_parse_arent_cached() {
+        let data_dir = TempDir::new();
+        let source = GpuShaderSource::Wgsl("fn main( {".to_owned());
+
+        let cache = PipelineCache::for_adapter(&data_dir.0, "adapter").unwrap();
+        assert!(cache.shader_module(&source).is_none());
+        assert!(!cache.shader_path(&source).exists());
+    }
+}
@@ -0,0 +1,701 @@
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use wgpu_core::{
+    command::{ImageCopyBuffer, RenderBundleDescriptor, RenderBundleEncoder},
+    device::{
+        trace::{Action, Command, FILE_NAME},
+        DeviceDescriptor, HostMap, ImplicitPipelineIds,
+    },
+    global::Global,
+    id::{BufferId, CommandBufferId, CommandEncoderId, DeviceId, Id, QueueId, TextureId},
+    pipeline::ShaderModuleSource,
+    resource::{BufferDescriptor, BufferMapOperation, TextureDescriptor},
+};
+
+use super::debug;
+use wgpu_types::{
+    Backend, BufferUsages, Extent3d, ImageCopyTexture, ImageDataLayout, Maintain, Origin3d,
+    SurfaceConfiguration, TextureAspect, TextureDimension, TextureFormat, TextureUsages,
+};
+
+/// Replays a GPU trace recorded with `RunOptions::gpu_trace` without a window, and saves the
+/// last `frames` frames the game presented as PNGs in `output_dir`. Errors raised by individual
+/// calls are reported along with their position in the trace, and the replay carries on.
+/// Returns how many frames were saved.
+pub fn replay_gpu_trace(trace_dir: &Path, output_dir: &Path, frames: usize) -> Result<usize> {
+    let trace_path = trace_dir.join(FILE_NAME);
+    let trace = fs::read_to_string(&trace_path)
+        .with_context(|| format!("Couldn't read {}", trace_path.display()))?;
+    let (desc, backend, actions) =
+        parse_trace(&trace).with_context(|| format!("Couldn't parse {}", trace_path.display()))?;
+
+    let instance = Global::new(
+        "rune-replay",
+        &wgpu_types::InstanceDescriptor {
+            backends: backend.into(),
+            flags: wgpu_types::InstanceFlags::from_build_config(),
+            ..Default::default()
+        },
+    );
+    let adapter_id = instance
+        .request_adapter(&Default::default(), backend.into(), None)
+        .map_err(|err| anyhow!("No {:?} GPU adapter is available: {}", backend, err))?;
+    let (device_id, queue_id) = instance
+        .adapter_request_device(adapter_id, &desc, None, None, None)
+        .map_err(|err| anyhow!("Couldn't create the device the trace was recorded on: {}", err))?;
+    debug::log(&format!(
+        "Replaying on {} ({:?})",
+        instance.adapter_get_info(adapter_id).name,
+        backend
+    ));
+
+    let presents = actions
+        .iter()
+        .filter(|action| matches!(action, Action::Present(_)))
+        .count();
+    // Frame readback buffers need ids that none of the game's buffers use.
+    let readback_buffer_index = actions
+        .iter()
+        .filter_map(|action| match action {
+            Action::CreateBuffer(id, _) => Some(id.unzip().0 + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("Couldn't create {}", output_dir.display()))?;
+
+    let mut replay = Replay {
+        instance,
+        device: device_id,
+        queue: queue_id,
+        dir: trace_dir.to_path_buf(),
+        index: 0,
+        surface_config: None,
+        surface_texture: None,
+        readback_buffer: Id::zip(readback_buffer_index, 1),
+    };
+
+    let mut presented = 0;
+    let mut saved = 0;
+    for (index, action) in actions.into_iter().enumerate() {
+        // Actions are numbered from the start of the trace, which began with the device.
+        replay.index = index + 1;
+        match action {
+            Action::Present(_) => {
+                if presented + frames >= presents {
+                    let path = output_dir.join(format!("frame-{:05}.png", presented));
+                    if replay.save_surface_texture(&path) {
+                        saved += 1;
+                    }
+                }
+                presented += 1;
+                replay.release_surface_texture();
+            }
+            action => replay.process(action),
+        }
+    }
+
+    let result = replay.instance.device_poll(replay.device, Maintain::Wait);
+    replay.done(result);
+
+    Ok(saved)
+}
+
+/// Splits a trace into the device it was recorded on and the actions that followed.
+fn parse_trace(trace: &str) -> Result<(DeviceDescriptor<'static>, Backend, Vec<Action<'static>>)> {
+    // The trace is only closed when the device is dropped, so one from a crashed game isn't.
+    let mut trace = trace.trim_end().to_owned();
+    if !trace.ends_with(']') {
+        trace.push(']');
+    }
+    let mut actions: Vec<Action<'static>> = ron::de::from_str(&trace)?;
+
+    if !matches!(actions.first(), Some(Action::Init { .. })) {
+        return Err(anyhow!("The trace doesn't start with the device it was recorded on"));
+    }
+    match actions.remove(0) {
+        Action::Init { desc, backend } => Ok((desc, backend, actions)),
+        _ => unreachable!(),
+    }
+}
+
+/// Plays a trace's actions back against a device, by the ids they were recorded with.
+struct Replay {
+    instance: Global,
+    device: DeviceId,
+    queue: QueueId,
+    dir: PathBuf,
+    /// The position of the action being replayed, for error messages.
+    index: usize,
+    surface_config: Option<SurfaceConfiguration<Vec<TextureFormat>>>,
+    /// The offscreen texture standing in for the surface texture of the current frame.
+    surface_texture: Option<TextureId>,
+    readback_buffer: BufferId,
+}
+
+impl Replay {
+    fn process(&mut self, action: Action) {
+        let device = self.device;
+        match action {
+            Action::ConfigureSurface(_, config) => self.surface_config = Some(config),
+            Action::GetSurfaceTexture { id, .. } => {
+                let Some(config) = self.surface_config.as_ref() else {
+                    self.error("The surface texture was taken before the surface was configured");
+                    return;
+                };
+                let desc = TextureDescriptor {
+                    label: Some("surface".into()),
+                    size: Extent3d {
+                        width: config.width,
+                        height: config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: config.format,
+                    usage: config.usage | TextureUsages::COPY_SRC,
+                    view_formats: config.view_formats.clone(),
+                };
+                let created = self.instance.device_create_texture(device, &desc, Some(id));
+                self.created(created);
+                self.surface_texture = Some(id);
+            }
+            Action::DiscardSurfaceTexture(_) => self.release_surface_texture(),
+            Action::CreateBuffer(id, desc) => {
+                let created = self.instance.device_create_buffer(device, &desc, Some(id));
+                self.created(created);
+            }
+            Action::FreeBuffer(id) => {
+                let result = self.instance.buffer_destroy(id);
+                self.done(result);
+            }
+            Action::DestroyBuffer(id) => self.instance.buffer_drop(id),
+            Action::CreateTexture(id, desc) => {
+                let created = self.instance.device_create_texture(device, &desc, Some(id));
+                self.created(created);
+            }
+            Action::FreeTexture(id) => {
+                let result = self.instance.texture_destroy(id);
+                self.done(result);
+            }
+            Action::DestroyTexture(id) => self.instance.texture_drop(id),
+            Action::CreateTextureView { id, parent_id, desc } => {
+                let created = self.instance.texture_create_view(parent_id, &desc, Some(id));
+                self.created(created);
+            }
+            Action::DestroyTextureView(id) => {
+                let result = self.instance.texture_view_drop(id);
+                self.done(result);
+            }
+            Action::CreateSampler(id, desc) => {
+                let created = self.instance.device_create_sampler(device, &desc, Some(id));
+                self.created(created);
+            }
+            Action::DestroySampler(id) => self.instance.sampler_drop(id),
+            Action::CreateBindGroupLayout(id, desc) => {
+                let created = self
+                    .instance
+                    .device_create_bind_group_layout(device, &desc, Some(id));
+                self.created(created);
+            }
+            Action::DestroyBindGroupLayout(id) => self.instance.bind_group_layout_drop(id),
+            Action::CreatePipelineLayout(id, desc) => {
+                let created = self
+                    .instance
+                    .device_create_pipeline_layout(device, &desc, Some(id));
+                self.created(created);
+            }
+            Action::DestroyPipelineLayout(id) => self.instance.pipeline_layout_drop(id),
+            Action::CreateBindGroup(id, desc) => {
+                let created = self.instance.device_create_bind_group(device, &desc, Some(id));
+                self.created(created);
+            }
+            Action::DestroyBindGroup(id) => self.instance.bind_group_drop(id),
+            Action::CreateShaderModule { id, desc, data } => {
+                let code = match fs::read_to_string(self.dir.join(&data)) {
+                    Ok(code) => code,
+                    Err(err) => return self.error(format!("Couldn't read shader {}: {}", data, err)),
+                };
+                // wgpu-core writes WGSL as it was given, and everything else as a parsed naga module.
+                let source = if data.ends_with(".wgsl") {
+                    ShaderModuleSource::Wgsl(Cow::Owned(code))
+                } else {
+                    match ron::de::from_str(&code) {
+                        Ok(module) => ShaderModuleSource::Naga(Cow::Owned(module)),
+                        Err(err) => return self.error(format!("Couldn't parse shader {}: {}", data, err)),
+                    }
+                };
+                let created = self
+                    .instance
+                    .device_create_shader_module(device, &desc, source, Some(id));
+                self.created(created);
+            }
+            Action::DestroyShaderModule(id) => self.instance.shader_module_drop(id),
+            Action::CreateComputePipeline {
+                id,
+                desc,
+                implicit_context,
+            } => {
+                let implicit_ids = implicit_context.as_ref().map(|context| ImplicitPipelineIds {
+                    root_id: context.root_id,
+                    group_ids: &context.group_ids,
+                });
+                let created = self
+                    .instance
+                    .device_create_compute_pipeline(device, &desc, Some(id), implicit_ids);
+                self.created(created);
+            }
+            Action::DestroyComputePipeline(id) => self.instance.compute_pipeline_drop(id),
+            Action::CreateRenderPipeline {
+                id,
+                desc,
+                implicit_context,
+            } => {
+                let implicit_ids = implicit_context.as_ref().map(|context| ImplicitPipelineIds {
+                    root_id: context.root_id,
+                    group_ids: &context.group_ids,
+                });
+                let created = self
+                    .instance
+                    .device_create_render_pipeline(device, &desc, Some(id), implicit_ids);
+                self.created(created);
+            }
+            Action::DestroyRenderPipeline(id) => self.instance.render_pipeline_drop(id),
+            Action::CreatePipelineCache { id, desc } => {
+                // SAFETY: the cache data comes from the recording, which wgpu checks against the
+                // adapter before using it.
+                let created = unsafe { self.instance.device_create_pipeline_cache(device, &desc, Some(id)) };
+                self.created(created);
+            }
+            Action::DestroyPipelineCache(id) => self.instance.pipeline_cache_drop(id),
+            Action::CreateRenderBundle { id, desc, base } => {
+                let encoder = match RenderBundleEncoder::new(&desc, device, Some(base)) {
+                    Ok(encoder) => encoder,
+                    Err(err) => return self.error(err),
+                };
+                let created = self.instance.render_bundle_encoder_finish(
+                    encoder,
+                    &RenderBundleDescriptor {
+                        label: desc.label.clone(),
+                    },
+                    Some(id),
+                );
+                self.created(created);
+            }
+            Action::DestroyRenderBundle(id) => self.instance.render_bundle_drop(id),
+            Action::CreateQuerySet { id, desc } => {
+                let created = self.instance.device_create_query_set(device, &desc, Some(id));
+                self.created(created);
+            }
+            Action::DestroyQuerySet(id) => self.instance.query_set_drop(id),
+            Action::WriteBuffer {
+                id,
+                data,
+                range,
+                queued,
+            } => {
+                let Some(bytes) = self.read_data(&data) else {
+                    return;
+                };
+                let size = (range.end - range.start) as usize;
+                if queued {
+                    let result = self.instance.queue_write_buffer(self.queue, id, range.start, &bytes);
+                    self.done(result);
+                } else {
+                    // Writes to buffers mapped at creation, which went straight to the mapping.
+                    let result = self
+                        .instance
+                        .device_set_buffer_data(id, range.start, &bytes[..size]);
+                    self.done(result);
+                }
+            }
+            Action::WriteTexture {
+                to,
+                data,
+                layout,
+                size,
+            } => {
+                let Some(bytes) = self.read_data(&data) else {
+                    return;
+                };
+                let result = self
+                    .instance
+                    .queue_write_texture(self.queue, &to, &bytes, &layout, &size);
+                self.done(result);
+            }
+            Action::Submit(_, commands) => {
+                let command_buffers = match commands.is_empty() {
+                    true => vec![],
+                    false => vec![self.encode(commands)],
+                };
+                let result = self
+                    .instance
+                    .queue_submit(self.queue, &command_buffers)
+                    .map_err(|(_, error)| error);
+                self.done(result);
+            }
+            Action::Init { .. } => self.error("The trace records a second device, which is ignored"),
+            action => self.error(format!("Replaying {:?} isn't supported", action)),
+        }
+    }
+
+    /// Records a submission's commands into a new command buffer.
+    fn encode(&mut self, commands: Vec<Command>) -> CommandBufferId {
+        let created = self.instance.device_create_command_encoder(
+            self.device,
+            &wgpu_types::CommandEncoderDescriptor { label: None },
+            None,
+        );
+        let encoder: CommandEncoderId = self.created(created);
+
+        for command in commands {
+            match command {
+                Command::CopyBufferToBuffer {
+                    src,
+                    src_offset,
+                    dst,
+                    dst_offset,
+                    size,
+                } => {
+                    let result = self
+                        .instance
+                        .command_encoder_copy_buffer_to_buffer(encoder, src, src_offset, dst, dst_offset, size);
+                    self.done(result);
+                }
+                Command::CopyBufferToTexture { src, dst, size } => {
+                    let result = self
+                        .instance
+                        .command_encoder_copy_buffer_to_texture(encoder, &src, &dst, &size);
+                    self.done(result);
+                }
+                Command::CopyTextureToBuffer { src, dst, size } => {
+                    let result = self
+                        .instance
+                        .command_encoder_copy_texture_to_buffer(encoder, &src, &dst, &size);
+                    self.done(result);
+                }
+                Command::CopyTextureToTexture { src, dst, size } => {
+                    let result = self
+                        .instance
+                        .command_encoder_copy_texture_to_texture(encoder, &src, &dst, &size);
+                    self.done(result);
+                }
+                Command::ClearBuffer { dst, offset, size } => {
+                    let result = self
+                        .instance
+                        .command_encoder_clear_buffer(encoder, dst, offset, size);
+                    self.done(result);
+                }
+                Command::ClearTexture {
+                    dst,
+                    subresource_range,
+                } => {
+                    let result = self
+                        .instance
+                        .command_encoder_clear_texture(encoder, dst, &subresource_range);
+                    self.done(result);
+                }
+                Command::WriteTimestamp {
+                    query_set_id,
+                    query_index,
+                } => {
+                    let result = self
+                        .instance
+                        .command_encoder_write_timestamp(encoder, query_set_id, query_index);
+                    self.done(result);
+                }
+                Command::ResolveQuerySet {
+                    query_set_id,
+                    start_query,
+                    query_count,
+                    destination,
+                    destination_offset,
+                } => {
+                    let result = self.instance.command_encoder_resolve_query_set(
+                        encoder,
+                        query_set_id,
+                        start_query,
+                        query_count,
+                        destination,
+                        destination_offset,
+                    );
+                    self.done(result);
+                }
+                Command::PushDebugGroup(label) => {
+                    let result = self.instance.command_encoder_push_debug_group(encoder, &label);
+                    self.done(result);
+                }
+                Command::PopDebugGroup => {
+                    let result = self.instance.command_encoder_pop_debug_group(encoder);
+                    self.done(result);
+                }
+                Command::InsertDebugMarker(label) => {
+                    let result = self.instance.command_encoder_insert_debug_marker(encoder, &label);
+                    self.done(result);
+                }
+                // Errors inside passes are kept on the encoder, and come out of finish below.
+                Command::RunComputePass {
+                    base,
+                    timestamp_writes,
+                } => {
+                    self.instance.compute_pass_end_with_unresolved_commands(
+                        encoder,
+                        base,
+                        timestamp_writes.as_ref(),
+                    );
+                }
+                Command::RunRenderPass {
+                    base,
+                    target_colors,
+                    target_depth_stencil,
+                    timestamp_writes,
+                    occlusion_query_set_id,
+                } => {
+                    self.instance.render_pass_end_with_unresolved_commands(
+                        encoder,
+                        base,
+                        &target_colors,
+                        target_depth_stencil.as_ref(),
+                        timestamp_writes.as_ref(),
+                        occlusion_query_set_id,
+                    );
+                }
+                command => self.error(format!("Replaying {:?} isn't supported", command)),
+            }
+        }
+
+        let created = self
+            .instance
+            .command_encoder_finish(encoder, &wgpu_types::CommandBufferDescriptor { label: None });
+        self.created(created)
+    }
+
+    /// Reads back the current surface texture and saves it as a PNG, returning whether it was saved.
+    /// This blocks until the GPU has finished everything submitted so far.
+    fn save_surface_texture(&mut self, path: &Path) -> bool {
+        let (Some(texture_id), Some(config)) = (self.surface_texture, self.surface_config.as_ref()) else {
+            self.error("A frame was presented without a surface texture");
+            return false;
+        };
+        let (width, height, format) = (config.width, config.height, config.format);
+        if !matches!(
+            format,
+            TextureFormat::Rgba8Unorm
+                | TextureFormat::Rgba8UnormSrgb
+                | TextureFormat::Bgra8Unorm
+                | TextureFormat::Bgra8UnormSrgb
+        ) {
+            self.error(format!("Frames can't be saved from surface format {:?}", format));
+            return false;
+        }
+
+        let unpadded_bytes_per_row = width * 4;
+        let align = wgpu_types::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
+        let size = padded_bytes_per_row as u64 * height as u64;
+
+        let buffer_id = self.readback_buffer;
+        let created = self.instance.device_create_buffer(
+            self.device,
+            &BufferDescriptor {
+                label: Some("frame readback".into()),
+                size,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+            Some(buffer_id),
+        );
+        self.created(created);
+
+        let created = self.instance.device_create_command_encoder(
+            self.device,
+            &wgpu_types::CommandEncoderDescriptor {
+                label: Some("frame readback".into()),
+            },
+            None,
+        );
+        let encoder = self.created(created);
+        let result = self.instance.command_encoder_copy_texture_to_buffer(
+            encoder,
+            &ImageCopyTexture {
+                texture: texture_id,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &ImageCopyBuffer {
+                buffer: buffer_id,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            &Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.done(result);
+        let created = self
+            .instance
+            .command_encoder_finish(encoder, &wgpu_types::CommandBufferDescriptor { label: None });
+        let command_buffer = self.created(created);
+        let result = self
+            .instance
+            .queue_submit(self.queue, &[command_buffer])
+            .map_err(|(_, error)| error);
+        self.done(result);
+
+        let result = self.instance.buffer_map_async(
+            buffer_id,
+            0,
+            Some(size),
+            BufferMapOperation {
+                host: HostMap::Read,
+                callback: None,
+            },
+        );
+        let mapped = result.is_ok();
+        self.done(result);
+        let result = self.instance.device_poll(self.device, Maintain::Wait);
+        self.done(result);
+
+        let mut data = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        if mapped {
+            match self.instance.buffer_get_mapped_range(buffer_id, 0, Some(size)) {
+                Ok((mapped_range, _)) => {
+                    let bytes = unsafe { std::slice::from_raw_parts(mapped_range.as_ptr(), size as usize) };
+                    for row in bytes.chunks(padded_bytes_per_row as usize) {
+                        data.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+                    }
+                }
+                Err(err) => self.error(err),
+            }
+            self.instance.buffer_unmap(buffer_id).ok();
+        }
+        self.instance.buffer_drop(buffer_id);
+
+        if data.is_empty() {
+            return false;
+        }
+        if matches!(format, TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb) {
+            for pixel in data.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        let image = image::RgbaImage::from_raw(width, height, data)
+            .expect("Frame readback should match its dimensions");
+        match image.save(path) {
+            Ok(()) => {
+                debug::log(&format!("Saved frame to {}", path.display()));
+                true
+            }
+            Err(err) => {
+                self.error(format!("Couldn't save {}: {}", path.display(), err));
+                false
+            }
+        }
+    }
+
+    /// Drops the texture standing in for the surface texture, as presenting or discarding it would.
+    fn release_surface_texture(&mut self) {
+        if let Some(texture_id) = self.surface_texture.take() {
+            self.instance.texture_drop(texture_id);
+        }
+    }
+
+    fn read_data(&self, name: &str) -> Option<Vec<u8>> {
+        match fs::read(self.dir.join(name)) {
+            Ok(bytes) => Some(bytes),
+            Err(err) => {
+                self.error(format!("Couldn't read {}: {}", name, err));
+                None
+            }
+        }
+    }
+
+    /// Prints the error from a wgpu-core create call, and returns the id it handed back either way.
+    fn created<I, E: Display>(&self, (id, error): (I, Option<E>)) -> I {
+        if let Some(error) = error {
+            self.error(error);
+        }
+        id
+    }
+
+    fn done<T, E: Display>(&self, result: Result<T, E>) {
+        if let Err(error) = result {
+            self.error(error);
+        }
+    }
+
+    fn error(&self, error: impl Display) {
+        debug::error(&format!("Action {}: {}", self.index, error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu_core::id::SurfaceId;
+
+    use super::*;
+
+    fn action(action: &Action) -> String {
+        ron::ser::to_string(action).unwrap()
+    }
+
+    /// A trace as wgpu-core writes it, with one action per line after the device.
+    fn trace(closed: bool) -> String {
+        let init = Action::Init {
+            desc: DeviceDescriptor::default(),
+            backend: Backend::Vulkan,
+        };
+        let present = Action::Present(SurfaceId::zip(0, 1));
+        let mut trace = format!("[\n{},\n{},\n", action(&init), action(&present));
+        if closed {
+            trace.push_str("]\n");
+        }
+        trace
+    }
+
+    #[test]
+    fn parse_trace_splits_off_the_device() {
+        let (_, backend, actions) = parse_trace(&trace(true)).unwrap();
+        assert_eq!(backend, Backend::Vulkan);
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(actions[0], Action::Present(_)));
+    }
+
+    #[test]
+    fn parse_trace_closes_a_trace_from_a_crash() {
+        let (_, _, actions) = parse_trace(&trace(false)).unwrap();
+        assert!(matches!(actions[..], [Action::Present(_)]));
+    }
+
+    #[test]
+    fn parse_trace_needs_the_device_first() {
+        let trace = format!("[\n{},\n]", action(&Action::Present(SurfaceId::zip(0, 1))));
+        let error = parse_trace(&trace).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The trace doesn't start with the device it was recorded on"
+        );
+        assert!(parse_trace("[]").is_err());
+    }
+
+    #[test]
+    fn parse_trace_rejects_malformed_actions() {
+        assert!(parse_trace("[\nNotAnAction(1),\n]").is_err());
+    }
+}
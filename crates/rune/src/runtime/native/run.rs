@@ -1,20 +1,27 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use anyhow::Result;
-use cpal::traits::HostTrait;
 use libtest_mimic::{Arguments, Trial};
 use pollster;
 
 use crate::host::Game;
+use crate::runtime::gpu::GpuBackend;
+use crate::runtime::storage::CloudConfig;
+use super::audio_devices::AudioDevices;
+use super::gpu_backend::{Gpu, NullGpu};
+use super::pipeline_cache::PipelineCache;
 pub use crate::runtime::common::*;
 
-#[cfg(any(target_os = "macos", target_os = "windows"))]
+#[cfg(not(target_arch = "wasm32"))]
 pub use super::state::RuneRuntimeState;
 
+use wgpu_core::id::{AdapterId, DeviceId, QueueId, SurfaceId};
 use winit::{
+    dpi::PhysicalSize,
     error::EventLoopError,
-    event::{Event, WindowEvent},
+    event::{Event, TouchPhase, WindowEvent},
     event_loop::{ControlFlow, EventLoop, EventLoopBuilder},
+    keyboard::{Key, NamedKey},
     raw_window_handle::{HasDisplayHandle, HasWindowHandle},
     window::{Window, WindowBuilder},
 };
@@ -22,20 +29,74 @@ use winit::{
 #[cfg(target_os = "macos")]
 use winit::platform::macos::WindowBuilderExtMacOS;
 
-async fn run_loop(
-    event_loop: EventLoop<GameEvent>,
-    window: Window,
-    input_path: PathBuf,
-    binary: Vec<u8>,
-) -> Result<(), EventLoopError> {
+/// Options for running a game, set by the CLI in development or by a bundled game's launcher.
+pub struct RunOptions {
+    /// Enables development conveniences, such as the F12 screenshot hotkey.
+    pub dev: bool,
+    /// Writes a screenshot of this render frame, counting from zero.
+    pub screenshot_at_frame: Option<u64>,
+    /// Directory that screenshots are written to.
+    pub screenshot_dir: PathBuf,
+    /// The graphics API to render with.
+    pub gpu_backend: GpuBackend,
+    /// Times each GPU pass from the start and prints a report when the game exits.
+    pub profile: bool,
+    /// The game's `[package] identifier`, which names its platform data directory.
+    pub identifier: Option<String>,
+    /// Records every wgpu call the game makes into this directory, for `rune gpu-replay`.
+    pub gpu_trace: Option<PathBuf>,
+    /// Records the mixed audio output of the game to this WAV file.
+    pub record_audio: Option<PathBuf>,
+    /// Lets the game listen to audio inputs, from `[permissions] microphone` in rune.toml.
+    pub microphone: bool,
+    /// The server the cloud storage device syncs with, from `[cloud]` in rune.toml.
+    pub cloud: Option<CloudConfig>,
+    /// Stops the game once it has rendered this many frames, so runs on CI end by themselves.
+    pub frames: Option<u64>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            dev: false,
+            screenshot_at_frame: None,
+            screenshot_dir: PathBuf::from("screenshots"),
+            gpu_backend: GpuBackend::from_env().unwrap_or(GpuBackend::Auto),
+            profile: false,
+            identifier: None,
+            gpu_trace: std::env::var_os("RUNE_GPU_TRACE").map(PathBuf::from),
+            record_audio: None,
+            microphone: false,
+            cloud: None,
+            frames: None,
+        }
+    }
+}
+
+/// The platform data directory for a game, such as `~/.local/share/<identifier>` on Linux.
+pub fn data_dir(identifier: &str) -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", identifier).map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Size of the frames the null backend pretends to render, since it has no window to size them.
+const HEADLESS_WINDOW_SIZE: PhysicalSize<u32> = PhysicalSize::new(1280, 720);
+
+/// Connects to the GPU and creates a surface for the window, returning it with the ids of the
+/// surface, adapter, device and queue.
+fn create_gpu(
+    window: &Window,
+    options: &RunOptions,
+) -> (Gpu, SurfaceId, AdapterId, DeviceId, QueueId, Option<PipelineCache>) {
+    let backends = options.gpu_backend.backends();
     let instance = wgpu_core::global::Global::new(
         "webgpu",
         &wgpu_types::InstanceDescriptor {
-            backends: wgpu_types::Backends::all(),
+            backends,
             flags: wgpu_types::InstanceFlags::from_build_config(),
             ..Default::default()
         },
     );
+
     let surface_id = unsafe {
         instance
             .instance_create_surface(
@@ -48,50 +109,247 @@ async fn run_loop(
     let adapter_id = instance
         .request_adapter(
             &Default::default(),
-            wgpu_types::Backends::all(),
+            backends,
             None
         )
-        .unwrap();
+        .unwrap_or_else(|err| panic!("No {:?} GPU adapter is available: {}", options.gpu_backend, err));
 
     let adapter_limits = instance
         .adapter_limits(adapter_id);
 
+    // wgpu-core writes the trace as it goes, so a crash still leaves everything up to it.
+    if let Some(trace_dir) = &options.gpu_trace {
+        std::fs::create_dir_all(trace_dir).unwrap_or_else(|err| {
+            panic!("Couldn't create the GPU trace directory {}: {}", trace_dir.display(), err)
+        });
+        super::debug::log(&format!("Recording a GPU trace to {}", trace_dir.display()));
+    }
+
     // Create the logical device and command queue
     let (device_id, queue_id) = instance.adapter_request_device(
         adapter_id,
         &wgpu_types::DeviceDescriptor {
             label: None,
-            required_features: wgpu_types::Features::empty(),
+            // Timestamp queries are only used by the profiler, pipeline caches only speed up
+            // launches and compressed formats only matter to games that ship them, so all of
+            // these are optional.
+            required_features: instance.adapter_features(adapter_id)
+                & (wgpu_types::Features::TIMESTAMP_QUERY
+                    | wgpu_types::Features::PIPELINE_CACHE
+                    | wgpu_types::Features::TEXTURE_COMPRESSION_BC
+                    | wgpu_types::Features::TEXTURE_COMPRESSION_ETC2
+                    | wgpu_types::Features::TEXTURE_COMPRESSION_ASTC),
             // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
             required_limits:
                 wgpu_types::Limits::downlevel_webgl2_defaults().using_resolution(adapter_limits),
             memory_hints: wgpu_types::MemoryHints::default(),
         },
-        None,
+        options.gpu_trace.as_deref(),
         None,
         None,
     ).unwrap();
 
-    let audio_host = cpal::default_host();
-    let audio_device = audio_host.default_output_device().unwrap();
+    // Caches are only kept for games with an identifier, since it names the directory.
+    let pipeline_cache = options.identifier.as_ref().and_then(|identifier| {
+        data_dir(identifier)
+            .and_then(|dir| PipelineCache::open(&dir, &instance, adapter_id, device_id))
+    });
+
+    (
+        Gpu::Wgpu(Arc::new(instance)),
+        surface_id,
+        adapter_id,
+        device_id,
+        queue_id,
+        pipeline_cache,
+    )
+}
 
-    let gilrs = gilrs::Gilrs::new().unwrap();
+/// Gamepads are optional, so a platform gilrs can't use only leaves the game without them.
+fn create_gilrs() -> Option<gilrs::Gilrs> {
+    match gilrs::Gilrs::new() {
+        Ok(gilrs) => Some(gilrs),
+        // gilrs still hands out a working instance that never sees a gamepad.
+        Err(gilrs::Error::NotImplemented(gilrs)) => Some(gilrs),
+        Err(err) => {
+            super::debug::warn(&format!("Gamepads are unavailable: {}", err));
+            None
+        }
+    }
+}
 
-    let mut game = Game::from_binary(&binary).unwrap();
+/// Loads the game and calls its init, with profiling turned on when asked for.
+async fn init_game(
+    input_path: PathBuf,
+    binary: &[u8],
+    options: &RunOptions,
+    window_size: PhysicalSize<u32>,
+    audio_devices: AudioDevices,
+    gpu: Gpu,
+    (surface_id, adapter_id, device_id, queue_id): (SurfaceId, AdapterId, DeviceId, QueueId),
+    pipeline_cache: Option<PipelineCache>,
+) -> Game {
+    let mut game = Game::from_binary(binary).unwrap();
     game.init(
-        &window,
+        window_size,
         input_path,
-        audio_device,
-        instance,
+        audio_devices,
+        gpu,
         surface_id,
         adapter_id,
         device_id,
         queue_id,
-        gilrs,
+        create_gilrs(),
+        pipeline_cache,
+        options.record_audio.clone(),
+        options.microphone,
+        options.identifier.as_deref().and_then(data_dir),
+        options.cloud.clone(),
     )
     .await
     .expect("Game didn't initialize");
 
+    if options.profile {
+        game.store.as_mut().unwrap().data_mut().set_gpu_profiling(true);
+    }
+    game
+}
+
+/// Reports on the run and saves what should outlive it, once the game stops.
+fn finish_game(game: &mut Game, options: &RunOptions) {
+    if options.profile {
+        match game.store.as_ref().unwrap().data().gpu_state.profiler.as_ref() {
+            Some(profiler) => super::debug::log(&format!("GPU pass timings:\n{}", profiler.report())),
+            None => super::debug::warn("No GPU timings were recorded"),
+        }
+    }
+
+    game.store.as_mut().unwrap().data_mut().stop_audio_recordings();
+
+    let state = game.store.as_ref().unwrap().data();
+    if let (Some(pipeline_cache), Some(global)) = (state.pipeline_cache.as_ref(), state.gpu.wgpu()) {
+        pipeline_cache.save(&global);
+    }
+
+    // Anything still alive here was never dropped by the guest.
+    let gpu_state = &state.gpu_state;
+    if options.dev && !gpu_state.resources.is_empty() {
+        super::debug::warn(&format!(
+            "{} GPU resources were never dropped:\n{}",
+            gpu_state.resources.len(),
+            gpu_state.resource_report()
+        ));
+    }
+}
+
+/// Runs the game on the null backend, without a window or audio device, so it works where
+/// there's no display, GPU or sound card. It keeps the windowed loop's frame rates and runs
+/// until `frames` have been rendered, or until the process is stopped without them. The
+/// process then exits with an error if the game caused any uncaptured GPU errors, so CI can
+/// fail on them.
+async fn run_headless(input_path: PathBuf, binary: Vec<u8>, options: RunOptions) {
+    super::debug::log("Running with the null GPU backend, nothing will be rendered");
+    if options.gpu_trace.is_some() {
+        super::debug::warn("The null GPU backend makes no wgpu calls, so no GPU trace will be recorded");
+    }
+
+    let mut null_gpu = NullGpu::new();
+    let ids = (
+        null_gpu.create_id(),
+        null_gpu.create_id(),
+        null_gpu.create_id(),
+        null_gpu.create_id(),
+    );
+    let mut game = init_game(
+        input_path,
+        &binary,
+        &options,
+        HEADLESS_WINDOW_SIZE,
+        AudioDevices::silent(),
+        Gpu::Null(null_gpu),
+        ids,
+        None,
+    )
+    .await;
+
+    let start_time = std::time::Instant::now();
+
+    let mut last_logic_update = start_time;
+    let mut last_render_update = start_time;
+
+    let logic_frame_time = std::time::Duration::from_millis(1000 / 30); // 30 FPS for logic
+    let render_frame_time = std::time::Duration::from_millis(1000 / 60); // 60 FPS for rendering
+
+    let mut render_frame: u64 = 0;
+
+    while options.frames != Some(render_frame) {
+        let now = std::time::Instant::now();
+
+        if now - last_logic_update >= logic_frame_time {
+            let generation = &mut game.store.as_mut().unwrap().data_mut().generation;
+            *generation += 1;
+
+            game.update(now - start_time, now - last_render_update).await.unwrap();
+            last_logic_update = now;
+        }
+
+        if now - last_render_update >= render_frame_time {
+            if options.screenshot_at_frame == Some(render_frame) {
+                game.screenshot_path = Some(
+                    options
+                        .screenshot_dir
+                        .join(format!("frame-{}.png", render_frame)),
+                );
+            }
+
+            game.render(now - start_time, now - last_render_update).await.unwrap();
+            last_render_update = now;
+            render_frame += 1;
+        }
+
+        let next_update = std::cmp::min(
+            last_logic_update + logic_frame_time,
+            last_render_update + render_frame_time,
+        );
+        std::thread::sleep(next_update.saturating_duration_since(std::time::Instant::now()));
+    }
+
+    finish_game(&mut game, &options);
+
+    let errors = game.store.as_ref().unwrap().data().gpu_state.uncaptured_errors;
+    if errors > 0 {
+        super::debug::error(&format!("The game caused {} uncaptured GPU errors", errors));
+        std::process::exit(1);
+    }
+}
+
+async fn run_loop(
+    event_loop: EventLoop<GameEvent>,
+    window: Window,
+    input_path: PathBuf,
+    binary: Vec<u8>,
+    options: RunOptions,
+) -> Result<(), EventLoopError> {
+    let (gpu, surface_id, adapter_id, device_id, queue_id, pipeline_cache) =
+        create_gpu(&window, &options);
+
+    let audio_devices = AudioDevices::watch();
+    if audio_devices.outputs().is_empty() {
+        super::debug::warn("There's no audio output device, so audio will play to a silent sink");
+    }
+
+    let mut game = init_game(
+        input_path,
+        &binary,
+        &options,
+        window.inner_size(),
+        audio_devices,
+        gpu,
+        (surface_id, adapter_id, device_id, queue_id),
+        pipeline_cache,
+    )
+    .await;
+
     let start_time = std::time::Instant::now();
 
     let mut last_logic_update = start_time.clone();
@@ -100,6 +358,9 @@ async fn run_loop(
     let logic_frame_time = std::time::Duration::from_millis(1000 / 30); // 30 FPS for logic
     let render_frame_time = std::time::Duration::from_millis(1000 / 60); // 60 FPS for rendering
 
+    let mut render_frame: u64 = 0;
+    let mut screenshot_requested = false;
+
     event_loop.run(move |event, elwt| {
         let now = std::time::Instant::now();
 
@@ -116,6 +377,30 @@ async fn run_loop(
             } => {
                 game.resize(size);
             }
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                let pointer_state = &mut game.store.as_mut().unwrap().data_mut().pointer_state;
+                pointer_state.cursor_position = Some((position.x, position.y));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorLeft { .. },
+                ..
+            } => {
+                let pointer_state = &mut game.store.as_mut().unwrap().data_mut().pointer_state;
+                pointer_state.cursor_position = None;
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Touch(touch),
+                ..
+            } => {
+                let touches = &mut game.store.as_mut().unwrap().data_mut().pointer_state.touches;
+                touches.retain(|(id, _, _)| *id != touch.id);
+                if let TouchPhase::Started | TouchPhase::Moved = touch.phase {
+                    touches.push((touch.id, touch.location.x, touch.location.y));
+                }
+            }
             Event::WindowEvent {
                 event:
                     WindowEvent::KeyboardInput {
@@ -123,6 +408,14 @@ async fn run_loop(
                     },
                 ..
             } => {
+                if options.dev
+                    && key_event.state.is_pressed()
+                    && !key_event.repeat
+                    && key_event.logical_key == Key::Named(NamedKey::F12)
+                {
+                    screenshot_requested = true;
+                }
+
                 let generation = game.store.as_ref().unwrap().data().generation;
                 let keyboard_state = &mut game.store.as_mut().unwrap().data_mut().keyboard_state;
 
@@ -155,7 +448,7 @@ async fn run_loop(
                 let generation = game.store.as_ref().unwrap().data().generation;
 
                 let game_store = game.store.as_mut().unwrap();
-                let gilrs_event = { game_store.data_mut().gilrs.next_event() };
+                let gilrs_event = game_store.data_mut().gilrs.as_mut().and_then(|gilrs| gilrs.next_event());
                 let gamepad_state = &mut game_store.data_mut().gamepad_state;
 
                 while let Some(button_event) = gilrs_event {
@@ -208,20 +501,43 @@ async fn run_loop(
                 event: WindowEvent::RedrawRequested,
                 ..
             } => {
+                if screenshot_requested || options.screenshot_at_frame == Some(render_frame) {
+                    game.screenshot_path = Some(
+                        options
+                            .screenshot_dir
+                            .join(format!("frame-{}.png", render_frame)),
+                    );
+                    screenshot_requested = false;
+                }
+
                 let epoch_time = now - start_time;
                 let delta_time = now - last_render_update;
                 pollster::block_on(game.render(epoch_time, delta_time)).unwrap();
+                render_frame += 1;
+                if options.frames == Some(render_frame) {
+                    elwt.exit();
+                }
             }
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
             } => elwt.exit(),
+            Event::LoopExiting => finish_game(&mut game, &options),
             _ => {}
         }
     })
 }
 
 pub fn run(input_path: PathBuf, binary: Vec<u8>) {
+    run_with_options(input_path, binary, RunOptions::default());
+}
+
+pub fn run_with_options(input_path: PathBuf, binary: Vec<u8>, options: RunOptions) {
+    if options.gpu_backend == GpuBackend::Null {
+        pollster::block_on(run_headless(input_path, binary, options));
+        return;
+    }
+
     let event_loop = EventLoopBuilder::<GameEvent>::with_user_event()
         .build()
         .unwrap();
@@ -238,7 +554,7 @@ pub fn run(input_path: PathBuf, binary: Vec<u8>) {
     // {
     // env_logger::init();
     // Temporarily avoid srgb formats for the swapchain on the web
-    pollster::block_on(run_loop(event_loop, window, input_path, binary)).ok();
+    pollster::block_on(run_loop(event_loop, window, input_path, binary, options)).ok();
     // }
     // #[cfg(target_arch = "wasm32")]
     // {
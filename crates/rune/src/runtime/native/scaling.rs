@@ -0,0 +1,239 @@
+use std::borrow::Cow;
+
+use wgpu_core::{
+    binding_model::{BindGroupDescriptor, BindGroupEntry, BindingResource},
+    command::{RenderPassColorAttachment, RenderPassDescriptor},
+    id::TextureId,
+    resource::{SamplerDescriptor, TextureDescriptor, TextureViewDescriptor},
+};
+use wgpu_types::{
+    AddressMode, Color, Extent3d, FilterMode, LoadOp, StoreOp, TextureDimension, TextureUsages,
+};
+
+use crate::runtime::gpu::{RenderScaling, Texture};
+
+use super::state::RuneRuntimeState;
+
+impl RuneRuntimeState {
+    /// Replaces the render scaling, releasing the previous target and sampler.
+    pub(super) fn set_scaling(&mut self, scaling: Option<RenderScaling>) {
+        if let Some(previous) = std::mem::replace(&mut self.gpu_state.render_scaling, scaling) {
+            if let Some(texture_id) = previous.target {
+                self.gpu_state.textures.remove(&texture_id);
+                self.gpu.release(|global| global.texture_drop(texture_id));
+            }
+            if let Some(sampler_id) = previous.sampler {
+                self.gpu.release(|global| global.sampler_drop(sampler_id));
+            }
+        }
+    }
+
+    /// Returns the texture the game renders into while render scaling is set, creating it on
+    /// first use and again whenever the surface format changes.
+    pub(super) fn render_scaling_target(&mut self) -> TextureId {
+        let scaling = self
+            .gpu_state
+            .render_scaling
+            .as_ref()
+            .expect("Render scaling should be set");
+        let (width, height, target) = (scaling.width, scaling.height, scaling.target);
+        let format = self.surface_config.format;
+        if let Some(texture_id) = target {
+            if self.gpu_state.textures.get(&texture_id).map(|texture| texture.format) == Some(format) {
+                return texture_id;
+            }
+            self.gpu_state.textures.remove(&texture_id);
+            self.gpu.release(|global| global.texture_drop(texture_id));
+        }
+
+        let descriptor = TextureDescriptor {
+            label: Some("render scaling".into()),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: vec![],
+        };
+        let device_id = self.device;
+        let created = self.gpu.create(
+            || format!("create-texture {:?}", descriptor),
+            |global| global.device_create_texture(device_id, &descriptor, None),
+        );
+        let texture_id = self.gpu_id(Some("render scaling"), created);
+        self.gpu_state.textures.insert(
+            texture_id,
+            Texture {
+                height: descriptor.size.height,
+                width: descriptor.size.width,
+                depth_or_array_layers: 1,
+                dimension: descriptor.dimension,
+                format,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: descriptor.usage,
+            },
+        );
+        self.gpu_state.render_scaling.as_mut().unwrap().target = Some(texture_id);
+        texture_id
+    }
+
+    /// Draws the render scaling target onto the surface texture, letterboxed in black. Returns
+    /// whether there was a surface texture to draw onto.
+    pub(super) fn blit_scaled_target(&mut self) -> bool {
+        let Some(global) = self.gpu.wgpu() else {
+            return false;
+        };
+        let Some(scaling) = self.gpu_state.render_scaling.as_ref() else {
+            return false;
+        };
+        let Some(source_id) = scaling.target else {
+            return false;
+        };
+        let filter = scaling.filter;
+        let (x, y, width, height) = scaling.viewport(self.surface_config.width, self.surface_config.height);
+
+        let Ok(surface_texture_id) = self.acquire_surface_texture() else {
+            return false;
+        };
+
+        let (pipeline_id, bind_group_layout_id) = self.mipmap_pipeline(&global, self.surface_config.format, "fs_linear");
+        let sampler_id = match self.gpu_state.render_scaling.as_ref().unwrap().sampler {
+            Some(sampler_id) => sampler_id,
+            None => {
+                let sampler_id = self.gpu_id(
+                    Some("render scaling"),
+                    global.device_create_sampler(
+                        self.device,
+                        &SamplerDescriptor {
+                            label: Some("render scaling".into()),
+                            address_modes: [AddressMode::ClampToEdge; 3],
+                            mag_filter: filter,
+                            min_filter: filter,
+                            mipmap_filter: FilterMode::Nearest,
+                            lod_min_clamp: 0.0,
+                            lod_max_clamp: 32.0,
+                            compare: None,
+                            anisotropy_clamp: 1,
+                            border_color: None,
+                        },
+                        None,
+                    ),
+                );
+                self.gpu_state.render_scaling.as_mut().unwrap().sampler = Some(sampler_id);
+                sampler_id
+            }
+        };
+
+        let source_view_id = self.gpu_id(
+            Some("render scaling"),
+            global.texture_create_view(source_id, &TextureViewDescriptor::default(), None),
+        );
+        let surface_view_id = self.gpu_id(
+            Some("render scaling"),
+            global.texture_create_view(surface_texture_id, &TextureViewDescriptor::default(), None),
+        );
+        let bind_group_id = self.gpu_id(
+            Some("render scaling"),
+            global.device_create_bind_group(
+                self.device,
+                &BindGroupDescriptor {
+                    label: None,
+                    layout: bind_group_layout_id,
+                    entries: Cow::Owned(vec![
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(source_view_id),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::Sampler(sampler_id),
+                        },
+                    ]),
+                },
+                None,
+            ),
+        );
+
+        let command_encoder_id = self.gpu_id(
+            Some("render scaling"),
+            global.device_create_command_encoder(
+                self.device,
+                &wgpu_types::CommandEncoderDescriptor {
+                    label: Some("render scaling".into()),
+                },
+                None,
+            ),
+        );
+        let (mut render_pass, error) = global.command_encoder_create_render_pass(
+            command_encoder_id,
+            &RenderPassDescriptor {
+                label: Some("render scaling".into()),
+                color_attachments: Cow::Owned(vec![Some(RenderPassColorAttachment {
+                    view: surface_view_id,
+                    resolve_target: None,
+                    load_op: LoadOp::Clear(Color::BLACK),
+                    store_op: StoreOp::Store,
+                })]),
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            },
+        );
+        if let Some(error) = error {
+            self.gpu_result::<(), _>(Some("render scaling"), Err(error));
+        }
+        let result = global.render_pass_set_pipeline(&mut render_pass, pipeline_id);
+        self.gpu_result(Some("render scaling"), result);
+        let result = global
+            .render_pass_set_viewport(&mut render_pass, x, y, width, height, 0.0, 1.0);
+        self.gpu_result(Some("render scaling"), result);
+        let result = global
+            .render_pass_set_bind_group(&mut render_pass, 0, Some(bind_group_id), &[]);
+        self.gpu_result(Some("render scaling"), result);
+        let result = global.render_pass_draw(&mut render_pass, 3, 1, 0, 0);
+        self.gpu_result(Some("render scaling"), result);
+        let result = global.render_pass_end(&mut render_pass);
+        self.gpu_result(Some("render scaling"), result);
+
+        let command_buffer_id = self.gpu_id(
+            Some("render scaling"),
+            global.command_encoder_finish(
+                command_encoder_id,
+                &wgpu_types::CommandBufferDescriptor { label: None },
+            ),
+        );
+        let result = global
+            .queue_submit(self.queue, &[command_buffer_id])
+            .map_err(|(_, error)| error);
+        self.gpu_result(Some("render scaling"), result);
+
+        global.bind_group_drop(bind_group_id);
+        for view_id in [source_view_id, surface_view_id] {
+            let result = global.texture_view_drop(view_id);
+            self.gpu_result(Some("render scaling"), result);
+        }
+        true
+    }
+
+    /// Maps a position in the window to the pixels the game renders, which differ from the
+    /// window's while render scaling is set.
+    pub(super) fn window_to_render_position(&self, x: f64, y: f64) -> (f32, f32) {
+        match &self.gpu_state.render_scaling {
+            Some(scaling) => scaling.to_target(
+                x as f32,
+                y as f32,
+                self.surface_config.width,
+                self.surface_config.height,
+            ),
+            None => (x as f32, y as f32),
+        }
+    }
+}
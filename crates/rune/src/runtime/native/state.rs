@@ -1,4 +1,8 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+};
 
 use gilrs::Gilrs;
 use slab::Slab;
@@ -8,14 +12,27 @@ use wasmtime_wasi::{ResourceTable, WasiCtx, WasiCtxBuilder};
 use wgpu_types::TextureFormat;
 use winit::dpi::PhysicalSize;
 
-use crate::{runtime::{audio::AudioState, gpu::GpuState, storage::Storage}, GamepadState, KeyboardState};
+use crate::{runtime::{gpu::GpuState, storage::{CloudConfig, Storage}}, GamepadState, KeyboardState, PointerState};
+
+use crate::gpu::{GpuDeviceLostInfo, GpuDeviceLostReason};
+
+use super::audio::{AudioConnections, Playback};
+use super::audio_decode::AudioDecoder;
+use super::audio_devices::AudioDevices;
+use super::audio_output::AudioOutput;
+use super::audio_spatial::Doppler;
+use super::audio_worklet::AudioWorklets;
+use super::cloud::CloudSync;
+use super::debug;
+use super::gpu_backend::Gpu;
+use super::pipeline_cache::PipelineCache;
 
 pub struct RuneRuntimeState {
     pub id: Uuid,
     pub generation: u64,
     pub input_path: PathBuf,
     pub window_size: PhysicalSize<u32>,
-    pub instance: wgpu_core::global::Global,
+    pub gpu: Gpu,
     pub surface: wgpu_core::id::SurfaceId,
     pub surface_resource_id: u32,
     pub surface_config: wgpu_types::SurfaceConfiguration<Vec<TextureFormat>>,
@@ -25,13 +42,41 @@ pub struct RuneRuntimeState {
     pub device_resource_id: u32,
     pub queue: wgpu_core::id::QueueId,
     pub queue_resource_id: u32,
-    pub gilrs: Gilrs,
+    /// None when gamepads couldn't be set up.
+    pub gilrs: Option<Gilrs>,
     pub gpu_state: GpuState,
-    pub audio_state: AudioState,
+    pub pipeline_cache: Option<PipelineCache>,
+    pub audio_devices: AudioDevices,
+    /// The output of each audio context, keyed by the context's table index.
+    pub audio_outputs: HashMap<u32, AudioOutput>,
+    /// Where to record the audio of the contexts the game creates, from `--record-audio`.
+    pub record_audio: Option<PathBuf>,
+    /// Whether rune.toml lets the game listen to audio inputs.
+    pub microphone: bool,
+    /// Whether each source node has ended, keyed by the node's table index.
+    pub audio_ended: HashMap<u32, Arc<AtomicBool>>,
+    /// Created when the game makes its first audio worklet node.
+    pub audio_worklets: Option<AudioWorklets>,
+    /// Created when the game first decodes audio data in the background.
+    pub audio_decoder: Option<AudioDecoder>,
+    /// The doppler effect of each panner that has one, keyed by the panner's table index.
+    pub audio_dopplers: HashMap<u32, Doppler>,
+    /// The connections the game has made between audio nodes.
+    pub audio_connections: AudioConnections,
+    /// Whether each source node has been started or stopped, keyed by the node's table index.
+    pub audio_playback: HashMap<u32, Playback>,
     pub gamepad_state: GamepadState,
     pub keyboard_state: KeyboardState,
+    pub pointer_state: PointerState,
     pub paths: Slab<VfsPath>,
     pub storages: Slab<Storage>,
+    /// The game's platform data directory, which save data and the cloud mirror are kept in.
+    pub data_dir: Option<PathBuf>,
+    /// The server from `[cloud]` in rune.toml.
+    pub cloud_config: Option<CloudConfig>,
+    /// Each player's cloud storage, keyed by their player id, which is started when the game first
+    /// asks for it.
+    pub cloud: HashMap<String, CloudSync>,
     pub wasi_ctx: WasiCtx,
     pub table: ResourceTable,
 }
@@ -41,41 +86,80 @@ impl RuneRuntimeState {
         id: Uuid,
         input_path: PathBuf,
         window_size: PhysicalSize<u32>,
-        audio_device: cpal::Device,
-        instance: wgpu_core::global::Global,
+        audio_devices: AudioDevices,
+        gpu: Gpu,
         surface: wgpu_core::id::SurfaceId,
         adapter: wgpu_core::id::AdapterId,
         device: wgpu_core::id::DeviceId,
         queue: wgpu_core::id::QueueId,
-        gilrs: Gilrs,
+        gilrs: Option<Gilrs>,
+        pipeline_cache: Option<PipelineCache>,
     ) -> Self {
         let mut table = ResourceTable::new();
 
-        let swapchain_capabilities = instance
-            .surface_get_capabilities(surface, adapter)
-            .unwrap();
-        let swapchain_format = swapchain_capabilities.formats[0];
+        let swapchain_capabilities = gpu.surface_capabilities(surface, adapter);
+        // Prefer an sRGB format and vsync so the defaults don't vary between machines.
+        // Guests can pick something else with gpu-surface.configure.
+        let swapchain_format = swapchain_capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|format| format.is_srgb())
+            .unwrap_or(swapchain_capabilities.formats[0]);
+
+        // Frame capture copies out of the surface texture, so request COPY_SRC where the surface allows it.
+        let surface_usage = wgpu_types::TextureUsages::RENDER_ATTACHMENT
+            | (swapchain_capabilities.usages & wgpu_types::TextureUsages::COPY_SRC);
 
         let surface_config = wgpu_types::SurfaceConfiguration {
-            usage: wgpu_types::TextureUsages::RENDER_ATTACHMENT,
+            usage: surface_usage,
             format: swapchain_format,
             width: window_size.width,
             height: window_size.height,
-            present_mode: swapchain_capabilities.present_modes[0],
-            alpha_mode: swapchain_capabilities.alpha_modes[0],
+            present_mode: wgpu_types::PresentMode::Fifo,
+            alpha_mode: wgpu_types::CompositeAlphaMode::Auto,
             view_formats: vec![],
             desired_maximum_frame_latency: 1,
         };
 
-        instance.surface_configure(surface, device, &surface_config);
+        // The null backend has no surface to configure.
+        if let Some(global) = gpu.wgpu() {
+            global.surface_configure(surface, device, &surface_config);
+        }
+
+        let gpu_state = GpuState::new();
+
+        let device_lost = gpu_state.device_lost.clone();
+        gpu.set_device_lost_closure(
+            device,
+            Box::new(move |reason, message| {
+                debug::error(&format!("The GPU device was lost: {}", message));
+                *device_lost.lock().unwrap() = Some(GpuDeviceLostInfo {
+                    reason: match reason {
+                        wgpu_types::DeviceLostReason::Destroyed => GpuDeviceLostReason::Destroyed,
+                        _ => GpuDeviceLostReason::Unknown,
+                    },
+                    message,
+                });
+            }),
+        );
 
         RuneRuntimeState {
             id,
             generation: 0,
             input_path,
             window_size,
-            audio_state: AudioState::new(audio_device),
-            instance,
+            audio_devices,
+            audio_outputs: HashMap::new(),
+            record_audio: None,
+            microphone: false,
+            audio_ended: HashMap::new(),
+            audio_worklets: None,
+            audio_decoder: None,
+            audio_dopplers: HashMap::new(),
+            audio_connections: HashMap::new(),
+            audio_playback: HashMap::new(),
+            gpu,
             surface,
             surface_resource_id: table.push(surface).unwrap().rep(),
             surface_config,
@@ -86,11 +170,16 @@ impl RuneRuntimeState {
             queue,
             queue_resource_id: table.push(queue).unwrap().rep(),
             gilrs,
-            gpu_state: GpuState::new(),
+            gpu_state,
+            pipeline_cache,
             gamepad_state: GamepadState::new(),
             keyboard_state: KeyboardState::new(),
+            pointer_state: PointerState::new(),
             paths: Slab::new(),
             storages: Slab::new(),
+            data_dir: None,
+            cloud_config: None,
+            cloud: HashMap::new(),
             wasi_ctx: WasiCtxBuilder::new()
                 .inherit_stderr()
                 .inherit_stdout()
@@ -1,7 +1,7 @@
 use std::fs;
 use std::io::Write;
 
-use vfs::{AltrootFS, FileSystem, PhysicalFS, VfsPath};
+use vfs::{error::VfsErrorKind, AltrootFS, FileSystem, MemoryFS, PhysicalFS, VfsPath};
 
 use wasmtime::component::Resource;
 use wasmtime::Result;
@@ -9,6 +9,8 @@ use wasmtime::Result;
 use crate::rune::runtime::storage::*;
 use crate::runtime::storage::Storage;
 
+use super::cloud::CloudSync;
+use super::debug;
 use super::state::RuneRuntimeState;
 
 impl Host for RuneRuntimeState {
@@ -25,24 +27,91 @@ impl Host for RuneRuntimeState {
         Resource::new_own(storage as u32)
     }
 
-    async fn cloud(&mut self) -> Option<Resource<StorageDevice>> {
-        None
+    async fn assets(&mut self) -> Resource<StorageDevice> {
+        let root = VfsPath::new(PhysicalFS::new(self.input_path.clone()));
+        let storage = self
+            .storages
+            .insert(Storage::Assets(root.clone(), AltrootFS::new(root)));
+        Resource::new_own(storage as u32)
+    }
+
+    async fn save_data(&mut self) -> Resource<StorageDevice> {
+        let root = match self.data_dir.as_ref().map(|dir| dir.join("saves")) {
+            Some(dir) => match fs::create_dir_all(&dir) {
+                Ok(()) => VfsPath::new(PhysicalFS::new(dir)),
+                Err(err) => {
+                    debug::warn(&format!(
+                        "Couldn't create the save data directory {}, so save data is kept in memory until the game exits: {}",
+                        dir.display(),
+                        err
+                    ));
+                    VfsPath::new(MemoryFS::new())
+                }
+            },
+            None => {
+                debug::warn("The game has no [package] identifier to name its save data directory, so save data is kept in memory until the game exits");
+                VfsPath::new(MemoryFS::new())
+            }
+        };
+        let storage = self
+            .storages
+            .insert(Storage::SaveData(root.clone(), AltrootFS::new(root)));
+        Resource::new_own(storage as u32)
+    }
+
+    async fn cloud(&mut self, account: CloudAccount) -> Option<Resource<StorageDevice>> {
+        match self.cloud.get(&account.player) {
+            Some(cloud) => cloud.set_token(account.token),
+            None => {
+                let config = self.cloud_config.clone()?;
+                let dir = match &self.data_dir {
+                    Some(dir) => dir.join("cloud"),
+                    None => {
+                        debug::warn("The game has no [package] identifier to name its cloud storage directory, so there's no cloud storage");
+                        return None;
+                    }
+                };
+                let cloud = CloudSync::start(config, &account.player, account.token, &dir)?;
+                self.cloud.insert(account.player.clone(), cloud);
+            }
+        }
+
+        let root = VfsPath::new(PhysicalFS::new(self.cloud[&account.player].root.clone()));
+        let storage = self.storages.insert(Storage::Cloud(
+            account.player,
+            root.clone(),
+            AltrootFS::new(root),
+        ));
+        Some(Resource::new_own(storage as u32))
+    }
+
+    async fn cloud_status(&mut self, player: String) -> Option<CloudSyncStatus> {
+        let cloud = self.cloud.get(&player)?;
+        Some(CloudSyncStatus {
+            online: cloud.online(),
+            pending: cloud.pending(),
+            conflicts: cloud.conflicts(),
+        })
     }
 }
 
 impl HostStorageDevice for RuneRuntimeState {
     async fn create_dir(&mut self, storage: Resource<StorageDevice>, path: Resource<Path>) {
         let storage = self.storages.get(storage.rep() as usize).unwrap();
-
-        match storage {
-            Storage::Local(_root, vfs) => {
-                let full_path = self.paths.get(path.rep() as usize).unwrap();
-                vfs.create_dir(full_path.as_str()).unwrap();
-            }
-            Storage::Cloud => todo!(),
+        if storage.is_read_only() {
+            return debug::warn("Couldn't create a directory, since the assets are read-only");
         }
 
-        ()
+        let (_root, vfs) = storage.vfs();
+        let full_path = self.paths.get(path.rep() as usize).unwrap();
+        match vfs.create_dir(full_path.as_str()) {
+            Err(err) if !matches!(err.kind(), VfsErrorKind::DirectoryExists) => debug::warn(&format!(
+                "Couldn't create the directory {}: {}",
+                full_path.as_str(),
+                err
+            )),
+            _ => {}
+        }
     }
 
     async fn list_dir(
@@ -52,21 +121,20 @@ impl HostStorageDevice for RuneRuntimeState {
     ) -> Vec<Resource<Path>> {
         let storage = self.storages.get(storage.rep() as usize).unwrap();
 
-        match storage {
-            Storage::Local(root, vfs) => {
-                let full_path = self.paths.get(path.rep() as usize).unwrap();
-                match vfs.read_dir(full_path.as_str()) {
-                    Ok(entries) => entries
-                        .filter_map(|entry| {
-                            Some(Resource::new_borrow(
-                                self.paths.insert(root.join(entry).unwrap()) as u32,
-                            ))
-                        })
-                        .collect(),
-                    Err(err) => panic!("{}", err),
-                }
+        let (root, vfs) = storage.vfs();
+        let full_path = self.paths.get(path.rep() as usize).unwrap();
+        match vfs.read_dir(full_path.as_str()) {
+            Ok(entries) => entries
+                .filter_map(|entry| {
+                    Some(Resource::new_borrow(
+                        self.paths.insert(root.join(entry).unwrap()) as u32,
+                    ))
+                })
+                .collect(),
+            Err(err) => {
+                debug::warn(&format!("Couldn't list the directory {}: {}", full_path.as_str(), err));
+                Vec::new()
             }
-            Storage::Cloud => todo!(),
         }
     }
 
@@ -74,9 +142,13 @@ impl HostStorageDevice for RuneRuntimeState {
         let storage = self.storages.get(storage.rep() as usize).unwrap();
         let path = self.paths.get(path.rep() as usize).unwrap();
 
-        match storage {
-            Storage::Local(_root, vfs) => vfs.exists(path.as_str()).unwrap(),
-            Storage::Cloud => todo!(),
+        let (_root, vfs) = storage.vfs();
+        match vfs.exists(path.as_str()) {
+            Ok(exists) => exists,
+            Err(err) => {
+                debug::warn(&format!("Couldn't check whether {} exists: {}", path.as_str(), err));
+                false
+            }
         }
     }
 
@@ -88,15 +160,14 @@ impl HostStorageDevice for RuneRuntimeState {
         let storage = self.storages.get(storage.rep() as usize).unwrap();
         let path = self.paths.get(path.rep() as usize).unwrap();
 
-        match storage {
-            Storage::Local(_root, vfs) => {
-                let mut file = vfs.open_file(path.as_str()).unwrap();
-                let mut buffer = Vec::new();
-                file.read_to_end(&mut buffer).unwrap();
-                Some(buffer)
-            }
-            Storage::Cloud => todo!(),
+        let (_root, vfs) = storage.vfs();
+        let mut file = vfs.open_file(path.as_str()).ok()?;
+        let mut buffer = Vec::new();
+        if let Err(err) = file.read_to_end(&mut buffer) {
+            debug::warn(&format!("Couldn't read {}: {}", path.as_str(), err));
+            return None;
         }
+        Some(buffer)
     }
 
     async fn read_string(
@@ -107,15 +178,14 @@ impl HostStorageDevice for RuneRuntimeState {
         let storage = self.storages.get(storage.rep() as usize).unwrap();
         let path = self.paths.get(path.rep() as usize).unwrap();
 
-        match storage {
-            Storage::Local(_, vfs) => {
-                let mut file = vfs.open_file(path.as_str()).unwrap();
-                let mut str = String::new();
-                file.read_to_string(&mut str).unwrap();
-                Some(str)
-            }
-            Storage::Cloud => todo!(),
+        let (_, vfs) = storage.vfs();
+        let mut file = vfs.open_file(path.as_str()).ok()?;
+        let mut str = String::new();
+        if let Err(err) = file.read_to_string(&mut str) {
+            debug::warn(&format!("Couldn't read {}: {}", path.as_str(), err));
+            return None;
         }
+        Some(str)
     }
 
     async fn write(
@@ -126,23 +196,32 @@ impl HostStorageDevice for RuneRuntimeState {
     ) {
         let storage = self.storages.get(storage.rep() as usize).unwrap();
         let path = self.paths.get(path.rep() as usize).unwrap();
+        if storage.is_read_only() {
+            return debug::warn(&format!(
+                "Couldn't write {}, since the assets are read-only",
+                path.as_str()
+            ));
+        }
 
-        match storage {
-            Storage::Local(_, vfs) => {
-                let mut file: Box<dyn Write + Send>;
-                if path.exists().unwrap() {
-                    file = vfs.append_file(path.as_str()).unwrap();
-                } else {
-                    file = vfs.create_file(path.as_str()).unwrap();
-                }
+        let (_, vfs) = storage.vfs();
+        let written = path.parent().create_dir_all().and_then(|_| {
+            // Replaces the whole file, so a save that gets shorter doesn't keep the old end.
+            let mut file: Box<dyn Write + Send> = vfs.create_file(path.as_str())?;
 
-                match content {
-                    // WriteableContent::Stream(_) => todo!(),
-                    WriteableContent::String(data) => file.write_all(data.as_bytes()).unwrap(),
-                    WriteableContent::Bytes(bytes) => file.write_all(&bytes).unwrap(),
-                }
+            match content {
+                // WriteableContent::Stream(_) => todo!(),
+                WriteableContent::String(data) => file.write_all(data.as_bytes())?,
+                WriteableContent::Bytes(bytes) => file.write_all(&bytes)?,
             }
-            Storage::Cloud => todo!(),
+            Ok(())
+        });
+        if let Err(err) = written {
+            return debug::warn(&format!("Couldn't write {}: {}", path.as_str(), err));
+        }
+
+        // Sent once the file is closed, so the sync thread doesn't upload half of it.
+        if let Storage::Cloud(player, ..) = storage {
+            self.cloud[player].changed(path.as_str(), false);
         }
     }
 
@@ -153,26 +232,33 @@ impl HostStorageDevice for RuneRuntimeState {
     ) -> Option<bool> {
         let storage = self.storages.get(storage.rep() as usize).unwrap();
         let path = self.paths.get(path.rep() as usize).unwrap();
+        if storage.is_read_only() {
+            debug::warn(&format!(
+                "Couldn't remove {}, since the assets are read-only",
+                path.as_str()
+            ));
+            return Some(false);
+        }
 
-        match storage {
-            Storage::Local(_, vfs) => {
-                if path.is_root() {
-                    None
-                } else if path.is_dir().unwrap() {
-                    if vfs.remove_dir(path.as_str()).is_ok() {
-                        Some(true)
-                    } else {
-                        Some(false)
-                    }
-                } else {
-                    if vfs.remove_file(path.as_str()).is_ok() {
-                        Some(true)
-                    } else {
-                        Some(false)
-                    }
+        let (_, vfs) = storage.vfs();
+        if path.is_root() {
+            None
+        } else if path.is_dir().unwrap_or(false) {
+            if vfs.remove_dir(path.as_str()).is_ok() {
+                Some(true)
+            } else {
+                Some(false)
+            }
+        } else {
+            if vfs.remove_file(path.as_str()).is_ok() {
+                // Only files are synced, and the server's directories are left in place.
+                if let Storage::Cloud(player, ..) = storage {
+                    self.cloud[player].changed(path.as_str(), true);
                 }
+                Some(true)
+            } else {
+                Some(false)
             }
-            Storage::Cloud => todo!(),
         }
     }
 
@@ -186,10 +272,8 @@ impl HostPath for RuneRuntimeState {
     async fn new(&mut self, storage: Resource<StorageDevice>, path: String) -> Resource<Path> {
         let storage = self.storages.get(storage.rep() as usize).unwrap();
 
-        Resource::new_own(match storage {
-            Storage::Local(root, _) => self.paths.insert(root.join(path).unwrap()) as u32,
-            Storage::Cloud => todo!(),
-        })
+        let (root, _) = storage.vfs();
+        Resource::new_own(self.paths.insert(root.join(path).unwrap()) as u32)
     }
 
     async fn to_string(&mut self, res: Resource<Path>) -> String {
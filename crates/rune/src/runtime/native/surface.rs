@@ -0,0 +1,89 @@
+use wgpu_core::{device::DeviceError, id::TextureId, present::SurfaceError};
+use wgpu_types::SurfaceStatus;
+
+use crate::rune::runtime::gpu::GpuSurfaceError;
+
+use super::debug;
+use super::state::RuneRuntimeState;
+
+impl RuneRuntimeState {
+    /// Acquires the next surface texture. A surface that is outdated or lost, such as after the
+    /// window moved to another display or the driver reset, is configured again and retried once.
+    pub(super) fn acquire_surface_texture(&mut self) -> Result<TextureId, GpuSurfaceError> {
+        if self.gpu_state.reconfigure_surface {
+            self.reconfigure_surface();
+        }
+
+        let mut retried = false;
+        loop {
+            let error = match self.gpu.surface_current_texture(self.surface) {
+                Ok(output) => match (output.status, output.texture_id) {
+                    (SurfaceStatus::Good, Some(texture_id)) => return Ok(texture_id),
+                    // Still usable, but drawn at the wrong size or format until it's configured again.
+                    (SurfaceStatus::Suboptimal, Some(texture_id)) => {
+                        self.gpu_state.reconfigure_surface = true;
+                        return Ok(texture_id);
+                    }
+                    (SurfaceStatus::Timeout, _) => GpuSurfaceError::Timeout,
+                    (SurfaceStatus::Outdated, _) => GpuSurfaceError::Outdated,
+                    _ => GpuSurfaceError::Lost,
+                },
+                Err(SurfaceError::Device(DeviceError::OutOfMemory)) => GpuSurfaceError::OutOfMemory,
+                Err(error) => {
+                    debug::warn(&format!("Couldn't get the surface texture: {}", error));
+                    GpuSurfaceError::Lost
+                }
+            };
+
+            let recoverable = matches!(error, GpuSurfaceError::Outdated | GpuSurfaceError::Lost);
+            if retried || !recoverable || self.device_lost() {
+                return Err(error);
+            }
+            self.reconfigure_surface();
+            retried = true;
+        }
+    }
+
+    /// Presents the frame the guest rendered, first drawing the render scaling target onto the
+    /// surface when there is one. A stale surface is configured again before the next frame.
+    pub fn present_surface(&mut self) {
+        // The null backend has nothing to show.
+        let Some(global) = self.gpu.wgpu() else {
+            return;
+        };
+        if self.gpu_state.render_scaling.is_some() && !self.blit_scaled_target() {
+            return;
+        }
+
+        match global.surface_present(self.surface) {
+            Ok(SurfaceStatus::Good) => {}
+            Ok(_) => self.gpu_state.reconfigure_surface = true,
+            Err(error) => {
+                if !self.device_lost() {
+                    debug::warn(&format!("Couldn't present the frame: {}", error));
+                }
+                self.gpu_state.reconfigure_surface = true;
+            }
+        }
+    }
+
+    /// Configures the surface again with the configuration it already has.
+    pub(super) fn reconfigure_surface(&mut self) {
+        self.gpu_state.reconfigure_surface = false;
+        // A minimized window has no size to configure, and is configured again when restored.
+        if self.surface_config.width == 0 || self.surface_config.height == 0 {
+            return;
+        }
+        let (surface_id, device_id) = (self.surface, self.device);
+        let surface_config = &self.surface_config;
+        let result = self.gpu.call(
+            || format!("configure {:?}", surface_config),
+            |global| global.surface_configure(surface_id, device_id, surface_config).map_or(Ok(()), Err),
+        );
+        self.gpu_result(Some("surface"), result);
+    }
+
+    fn device_lost(&self) -> bool {
+        self.gpu_state.device_lost.lock().unwrap().is_some()
+    }
+}
@@ -0,0 +1,585 @@
+use std::io::Cursor;
+
+use ddsfile::{D3DFormat, Dds, DxgiFormat};
+use image::{imageops::FilterType, Rgba32FImage};
+use wgpu_types::{Extent3d, TextureDimension, TextureFormat};
+
+const KTX2_MAGIC: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const DDS_MAGIC: &[u8] = b"DDS ";
+
+/// An image decoded into the layout of a texture.
+pub struct DecodedImage {
+    pub format: TextureFormat,
+    pub size: Extent3d,
+    /// Texel data for each mip level, starting from the largest.
+    pub mips: Vec<Vec<u8>>,
+}
+
+pub struct DecodeOptions {
+    /// The format to decode into, or None to pick one from the file.
+    pub format: Option<TextureFormat>,
+    /// Downsamples the image to fill the mip levels below the first. Compressed files can't be
+    /// downsampled, so they use the levels they contain either way.
+    pub generate_mipmaps: bool,
+    /// The most mip levels the destination can hold.
+    pub max_mip_level_count: u32,
+    /// The largest width and height the destination can hold, checked before any texel data
+    /// is read.
+    pub max_size: u32,
+    pub flip_y: bool,
+    /// Picks an sRGB format when `format` is None.
+    pub srgb: bool,
+    pub premultiply_alpha: bool,
+}
+
+/// Decodes a PNG, JPEG, KTX2 or DDS file.
+pub fn decode(data: &[u8], options: &DecodeOptions) -> Result<DecodedImage, String> {
+    if data.starts_with(&KTX2_MAGIC) {
+        decode_ktx2(data, options)
+    } else if data.starts_with(DDS_MAGIC) {
+        decode_dds(data, options)
+    } else {
+        decode_image(data, options)
+    }
+}
+
+/// The number of mip levels in a full chain for a 2D texture of this size.
+pub fn full_mip_level_count(size: Extent3d) -> u32 {
+    size.max_mips(TextureDimension::D2)
+}
+
+/// Rejects images with no texels or more than the destination can hold.
+fn check_size(width: u32, height: u32, options: &DecodeOptions) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err(format!(
+            "The image is {}x{}, which has no texels",
+            width, height
+        ));
+    }
+    if width > options.max_size || height > options.max_size {
+        return Err(format!(
+            "The image is {}x{}, but textures can be at most {}x{} here",
+            width, height, options.max_size, options.max_size
+        ));
+    }
+    Ok(())
+}
+
+fn decode_image(data: &[u8], options: &DecodeOptions) -> Result<DecodedImage, String> {
+    // The header is read first, so huge images are rejected before their pixels are.
+    let (width, height) = image::io::Reader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|err| format!("Couldn't decode image: {}", err))?
+        .into_dimensions()
+        .map_err(|err| format!("Couldn't decode image: {}", err))?;
+    check_size(width, height, options)?;
+
+    let image =
+        image::load_from_memory(data).map_err(|err| format!("Couldn't decode image: {}", err))?;
+    let image = if options.flip_y { image.flipv() } else { image };
+
+    let format = match options.format {
+        Some(format) => format,
+        None if options.srgb => TextureFormat::Rgba8UnormSrgb,
+        None => TextureFormat::Rgba8Unorm,
+    };
+    // PNG and JPEG store sRGB-encoded values. Filtering and premultiplying are done on linear
+    // values wherever the texture will be read as linear.
+    let linearize = match format {
+        TextureFormat::Rgba8UnormSrgb
+        | TextureFormat::Bgra8UnormSrgb
+        | TextureFormat::Rgba32Float => true,
+        TextureFormat::Rgba8Unorm | TextureFormat::Bgra8Unorm => false,
+        _ => {
+            return Err(format!(
+                "Images can't be decoded into {:?} textures",
+                format
+            ))
+        }
+    };
+
+    let mut base = image.to_rgba32f();
+    for pixel in base.pixels_mut() {
+        if linearize {
+            for channel in &mut pixel.0[..3] {
+                *channel = srgb_to_linear(*channel);
+            }
+        }
+        if options.premultiply_alpha {
+            let alpha = pixel.0[3];
+            for channel in &mut pixel.0[..3] {
+                *channel *= alpha;
+            }
+        }
+    }
+
+    let size = Extent3d {
+        width: base.width(),
+        height: base.height(),
+        depth_or_array_layers: 1,
+    };
+    let mip_level_count = match options.generate_mipmaps {
+        true => options
+            .max_mip_level_count
+            .clamp(1, full_mip_level_count(size)),
+        false => 1,
+    };
+
+    let mut mips = vec![encode(&base, format)];
+    for mip in 1..mip_level_count {
+        let mip_size = size.mip_level_size(mip, TextureDimension::D2);
+        let level =
+            image::imageops::resize(&base, mip_size.width, mip_size.height, FilterType::Triangle);
+        mips.push(encode(&level, format));
+    }
+
+    Ok(DecodedImage { format, size, mips })
+}
+
+/// Converts linear values to the texel layout of `format`.
+fn encode(image: &Rgba32FImage, format: TextureFormat) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(image.len() * 4);
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        match format {
+            TextureFormat::Rgba32Float => {
+                for channel in [r, g, b, a] {
+                    bytes.extend_from_slice(&channel.to_le_bytes());
+                }
+            }
+            _ => {
+                let srgb = format.is_srgb();
+                let [r, g, b] = [r, g, b].map(|channel| {
+                    unorm8(if srgb {
+                        linear_to_srgb(channel)
+                    } else {
+                        channel
+                    })
+                });
+                let a = unorm8(a);
+                match format {
+                    TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+                        bytes.extend_from_slice(&[b, g, r, a])
+                    }
+                    _ => bytes.extend_from_slice(&[r, g, b, a]),
+                }
+            }
+        }
+    }
+    bytes
+}
+
+fn unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn decode_ktx2(data: &[u8], options: &DecodeOptions) -> Result<DecodedImage, String> {
+    let reader =
+        ktx2::Reader::new(data).map_err(|err| format!("Couldn't read KTX2 file: {:?}", err))?;
+    let header = reader.header();
+
+    if header.supercompression_scheme.is_some() {
+        return Err("Supercompressed KTX2 files aren't supported".to_owned());
+    }
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+        return Err("Only 2D KTX2 textures are supported".to_owned());
+    }
+    let file_format = header
+        .format
+        .and_then(ktx2_format)
+        .ok_or_else(|| format!("Unsupported KTX2 format {:?}", header.format))?;
+
+    let size = Extent3d {
+        width: header.pixel_width,
+        height: header.pixel_height.max(1),
+        depth_or_array_layers: 1,
+    };
+    check_size(size.width, size.height, options)?;
+    let mips = reader.levels().map(|level| level.to_vec()).collect();
+    stored_image(file_format, size, mips, options)
+}
+
+fn decode_dds(data: &[u8], options: &DecodeOptions) -> Result<DecodedImage, String> {
+    let dds = Dds::read(&mut Cursor::new(data))
+        .map_err(|err| format!("Couldn't read DDS file: {}", err))?;
+
+    if dds.get_depth() > 1 || dds.get_num_array_layers() > 1 {
+        return Err("Only 2D DDS textures are supported".to_owned());
+    }
+    let file_format = match (dds.get_dxgi_format(), dds.get_d3d_format()) {
+        (Some(format), _) => dxgi_format(format),
+        (None, Some(format)) => d3d_format(format),
+        (None, None) => None,
+    }
+    .ok_or("Unsupported DDS format")?;
+
+    let size = Extent3d {
+        width: dds.get_width(),
+        height: dds.get_height(),
+        depth_or_array_layers: 1,
+    };
+    check_size(size.width, size.height, options)?;
+    let mip_level_count = dds.get_num_mipmap_levels().max(1);
+    if mip_level_count > full_mip_level_count(size) {
+        return Err(format!(
+            "A {}x{} DDS file can't have {} mip levels",
+            size.width, size.height, mip_level_count
+        ));
+    }
+
+    // DDS stores every level back to back, largest first.
+    let mut mips = Vec::new();
+    let mut offset = 0usize;
+    for mip in 0..mip_level_count {
+        let length = level_size(file_format, size.mip_level_size(mip, TextureDimension::D2))
+            .ok_or("DDS file's mip levels are too large")?;
+        let level = offset
+            .checked_add(length)
+            .and_then(|end| dds.data.get(offset..end))
+            .ok_or("DDS file is shorter than its mip levels")?;
+        mips.push(level.to_vec());
+        offset += length;
+    }
+    stored_image(file_format, size, mips, options)
+}
+
+/// Checks a file's own texel data against the requested format. The data is used as stored, so
+/// only the sRGB-ness of the format can differ.
+fn stored_image(
+    file_format: TextureFormat,
+    size: Extent3d,
+    mut mips: Vec<Vec<u8>>,
+    options: &DecodeOptions,
+) -> Result<DecodedImage, String> {
+    if options.flip_y || options.premultiply_alpha {
+        return Err("KTX2 and DDS images can't be flipped or premultiplied".to_owned());
+    }
+    if mips.len() as u64 > full_mip_level_count(size) as u64 {
+        return Err(format!(
+            "A {}x{} image can't have {} mip levels",
+            size.width,
+            size.height,
+            mips.len()
+        ));
+    }
+    for (mip, level) in mips.iter().enumerate() {
+        let length = level_size(
+            file_format,
+            size.mip_level_size(mip as u32, TextureDimension::D2),
+        );
+        if length.map_or(true, |length| level.len() < length) {
+            return Err(format!(
+                "Mip level {} of the image is shorter than its size",
+                mip
+            ));
+        }
+    }
+    let format = match options.format {
+        Some(format) if format.remove_srgb_suffix() == file_format.remove_srgb_suffix() => format,
+        Some(format) => {
+            return Err(format!(
+                "The image is {:?}, which can't be written into a {:?} texture",
+                file_format, format
+            ))
+        }
+        None => file_format,
+    };
+    mips.truncate(options.max_mip_level_count.max(1) as usize);
+    Ok(DecodedImage { format, size, mips })
+}
+
+/// The bytes one mip level of `format` takes when tightly packed, or None when that's more than
+/// can be addressed.
+pub fn level_size(format: TextureFormat, size: Extent3d) -> Option<usize> {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4);
+    let blocks =
+        u64::from(size.width.div_ceil(block_width)) * u64::from(size.height.div_ceil(block_height));
+    usize::try_from(blocks.checked_mul(u64::from(block_size))?).ok()
+}
+
+fn ktx2_format(format: ktx2::Format) -> Option<TextureFormat> {
+    use ktx2::Format;
+    use wgpu_types::AstcBlock;
+    use wgpu_types::AstcChannel;
+
+    Some(match format {
+        Format::R8G8B8A8_UNORM => TextureFormat::Rgba8Unorm,
+        Format::R8G8B8A8_SRGB => TextureFormat::Rgba8UnormSrgb,
+        Format::B8G8R8A8_UNORM => TextureFormat::Bgra8Unorm,
+        Format::B8G8R8A8_SRGB => TextureFormat::Bgra8UnormSrgb,
+        Format::R16G16B16A16_SFLOAT => TextureFormat::Rgba16Float,
+        Format::R32G32B32A32_SFLOAT => TextureFormat::Rgba32Float,
+        Format::BC1_RGBA_UNORM_BLOCK => TextureFormat::Bc1RgbaUnorm,
+        Format::BC1_RGBA_SRGB_BLOCK => TextureFormat::Bc1RgbaUnormSrgb,
+        Format::BC2_UNORM_BLOCK => TextureFormat::Bc2RgbaUnorm,
+        Format::BC2_SRGB_BLOCK => TextureFormat::Bc2RgbaUnormSrgb,
+        Format::BC3_UNORM_BLOCK => TextureFormat::Bc3RgbaUnorm,
+        Format::BC3_SRGB_BLOCK => TextureFormat::Bc3RgbaUnormSrgb,
+        Format::BC4_UNORM_BLOCK => TextureFormat::Bc4RUnorm,
+        Format::BC5_UNORM_BLOCK => TextureFormat::Bc5RgUnorm,
+        Format::BC6H_UFLOAT_BLOCK => TextureFormat::Bc6hRgbUfloat,
+        Format::BC7_UNORM_BLOCK => TextureFormat::Bc7RgbaUnorm,
+        Format::BC7_SRGB_BLOCK => TextureFormat::Bc7RgbaUnormSrgb,
+        Format::ETC2_R8G8B8A8_UNORM_BLOCK => TextureFormat::Etc2Rgba8Unorm,
+        Format::ETC2_R8G8B8A8_SRGB_BLOCK => TextureFormat::Etc2Rgba8UnormSrgb,
+        Format::ASTC_4x4_UNORM_BLOCK => TextureFormat::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::Unorm,
+        },
+        Format::ASTC_4x4_SRGB_BLOCK => TextureFormat::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::UnormSrgb,
+        },
+        _ => return None,
+    })
+}
+
+fn dxgi_format(format: DxgiFormat) -> Option<TextureFormat> {
+    Some(match format {
+        DxgiFormat::R8G8B8A8_UNorm => TextureFormat::Rgba8Unorm,
+        DxgiFormat::R8G8B8A8_UNorm_sRGB => TextureFormat::Rgba8UnormSrgb,
+        DxgiFormat::B8G8R8A8_UNorm => TextureFormat::Bgra8Unorm,
+        DxgiFormat::B8G8R8A8_UNorm_sRGB => TextureFormat::Bgra8UnormSrgb,
+        DxgiFormat::R16G16B16A16_Float => TextureFormat::Rgba16Float,
+        DxgiFormat::R32G32B32A32_Float => TextureFormat::Rgba32Float,
+        DxgiFormat::BC1_UNorm => TextureFormat::Bc1RgbaUnorm,
+        DxgiFormat::BC1_UNorm_sRGB => TextureFormat::Bc1RgbaUnormSrgb,
+        DxgiFormat::BC2_UNorm => TextureFormat::Bc2RgbaUnorm,
+        DxgiFormat::BC2_UNorm_sRGB => TextureFormat::Bc2RgbaUnormSrgb,
+        DxgiFormat::BC3_UNorm => TextureFormat::Bc3RgbaUnorm,
+        DxgiFormat::BC3_UNorm_sRGB => TextureFormat::Bc3RgbaUnormSrgb,
+        DxgiFormat::BC4_UNorm => TextureFormat::Bc4RUnorm,
+        DxgiFormat::BC5_UNorm => TextureFormat::Bc5RgUnorm,
+        DxgiFormat::BC6H_UF16 => TextureFormat::Bc6hRgbUfloat,
+        DxgiFormat::BC7_UNorm => TextureFormat::Bc7RgbaUnorm,
+        DxgiFormat::BC7_UNorm_sRGB => TextureFormat::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+fn d3d_format(format: D3DFormat) -> Option<TextureFormat> {
+    Some(match format {
+        D3DFormat::A8B8G8R8 => TextureFormat::Rgba8Unorm,
+        D3DFormat::A8R8G8B8 => TextureFormat::Bgra8Unorm,
+        D3DFormat::DXT1 => TextureFormat::Bc1RgbaUnorm,
+        D3DFormat::DXT3 => TextureFormat::Bc2RgbaUnorm,
+        D3DFormat::DXT5 => TextureFormat::Bc3RgbaUnorm,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ddsfile::{AlphaMode, D3D10ResourceDimension, NewDxgiParams};
+
+    fn options() -> DecodeOptions {
+        DecodeOptions {
+            format: None,
+            generate_mipmaps: false,
+            max_mip_level_count: 16,
+            max_size: 16384,
+            flip_y: false,
+            srgb: false,
+            premultiply_alpha: false,
+        }
+    }
+
+    /// A KTX2 file with one mip level of `texels`, which needn't match the size in its header.
+    fn ktx2(vk_format: u32, width: u32, height: u32, texels: &[u8]) -> Vec<u8> {
+        // The header, one level index entry and a 4 byte data format descriptor come first.
+        let data_offset = 80 + 24 + 4;
+        let mut file = KTX2_MAGIC.to_vec();
+        for value in [vk_format, 1, width, height, 0, 0, 1, 1, 0, 80 + 24, 4, 0, 0] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+        file.extend_from_slice(&[0; 16]);
+        for value in [data_offset, texels.len() as u64, texels.len() as u64] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+        file.extend_from_slice(&4u32.to_le_bytes());
+        file.extend_from_slice(texels);
+        file
+    }
+
+    fn dds(width: u32, height: u32, mipmap_levels: u32) -> Vec<u8> {
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height,
+            width,
+            depth: None,
+            format: DxgiFormat::R8G8B8A8_UNorm,
+            mipmap_levels: Some(mipmap_levels),
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Straight,
+        })
+        .unwrap();
+        for (i, byte) in dds.data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let mut file = Vec::new();
+        dds.write(&mut file).unwrap();
+        file
+    }
+
+    /// A PNG whose left column is black and right column is white.
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbaImage::from_fn(width, height, |x, _| match x % 2 {
+            0 => image::Rgba([0, 0, 0, 255]),
+            _ => image::Rgba([255, 255, 255, 255]),
+        });
+        let mut file = Cursor::new(Vec::new());
+        image
+            .write_to(&mut file, image::ImageOutputFormat::Png)
+            .unwrap();
+        file.into_inner()
+    }
+
+    #[test]
+    fn ktx2_texels_are_used_as_stored() {
+        let texels: Vec<u8> = (0..16).collect();
+        let image = decode(&ktx2(43, 2, 2, &texels), &options()).unwrap();
+        assert_eq!(image.format, TextureFormat::Rgba8UnormSrgb);
+        assert_eq!((image.size.width, image.size.height), (2, 2));
+        assert_eq!(image.mips, vec![texels]);
+
+        // Only the sRGB-ness of the format can be changed.
+        let linear = DecodeOptions {
+            format: Some(TextureFormat::Rgba8Unorm),
+            ..options()
+        };
+        assert_eq!(
+            decode(&ktx2(43, 2, 2, &[0; 16]), &linear).unwrap().format,
+            TextureFormat::Rgba8Unorm
+        );
+        let float = DecodeOptions {
+            format: Some(TextureFormat::Rgba32Float),
+            ..options()
+        };
+        assert!(decode(&ktx2(37, 2, 2, &[0; 16]), &float).is_err());
+    }
+
+    #[test]
+    fn ktx2_levels_shorter_than_their_size_are_rejected() {
+        assert!(decode(&ktx2(37, 2, 2, &[0; 12]), &options()).is_err());
+    }
+
+    #[test]
+    fn huge_ktx2_files_are_rejected_before_their_levels_are_read() {
+        assert!(decode(&ktx2(37, 65536, 65536, &[0; 16]), &options()).is_err());
+        let unlimited = DecodeOptions {
+            max_size: u32::MAX,
+            ..options()
+        };
+        assert!(decode(&ktx2(37, u32::MAX, u32::MAX, &[0; 16]), &unlimited).is_err());
+    }
+
+    #[test]
+    fn dds_mip_levels_are_read_back_to_back() {
+        let file = dds(4, 2, 2);
+        let image = decode(&file, &options()).unwrap();
+        assert_eq!(image.format, TextureFormat::Rgba8Unorm);
+        assert_eq!((image.size.width, image.size.height), (4, 2));
+        assert_eq!(image.mips.len(), 2);
+        assert_eq!(image.mips[0], (0..32).collect::<Vec<u8>>());
+        assert_eq!(image.mips[1], (32..40).collect::<Vec<u8>>());
+
+        let one_level = DecodeOptions {
+            max_mip_level_count: 1,
+            ..options()
+        };
+        assert_eq!(decode(&file, &one_level).unwrap().mips.len(), 1);
+    }
+
+    #[test]
+    fn huge_dds_files_are_rejected_without_overflowing() {
+        let mut file = dds(4, 4, 1);
+        // The height and width follow the magic number, the header's size and its flags.
+        file[12..16].copy_from_slice(&65536u32.to_le_bytes());
+        file[16..20].copy_from_slice(&65536u32.to_le_bytes());
+        assert!(decode(&file, &options()).is_err());
+
+        let unlimited = DecodeOptions {
+            max_size: u32::MAX,
+            ..options()
+        };
+        assert!(decode(&file, &unlimited).is_err());
+    }
+
+    #[test]
+    fn truncated_dds_files_are_rejected() {
+        let file = dds(4, 4, 3);
+        assert!(decode(&file[..file.len() - 1], &options()).is_err());
+    }
+
+    #[test]
+    fn srgb_images_are_filtered_in_linear_space() {
+        let srgb = DecodeOptions {
+            generate_mipmaps: true,
+            srgb: true,
+            ..options()
+        };
+        let image = decode(&png(2, 2), &srgb).unwrap();
+        assert_eq!(image.format, TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(image.mips.len(), 2);
+        assert_eq!(&image.mips[0][..8], &[0, 0, 0, 255, 255, 255, 255, 255]);
+        // Half of white in linear space is about 188 once encoded as sRGB again.
+        assert!(image.mips[1][0].abs_diff(188) <= 1, "{}", image.mips[1][0]);
+
+        let linear = DecodeOptions {
+            generate_mipmaps: true,
+            ..options()
+        };
+        let image = decode(&png(2, 2), &linear).unwrap();
+        assert_eq!(image.format, TextureFormat::Rgba8Unorm);
+        assert!(image.mips[1][0].abs_diff(128) <= 1, "{}", image.mips[1][0]);
+    }
+
+    #[test]
+    fn images_larger_than_the_destination_are_rejected() {
+        let small = DecodeOptions {
+            max_size: 2,
+            ..options()
+        };
+        assert!(decode(&png(2, 2), &small).is_ok());
+        assert!(decode(&png(4, 2), &small).is_err());
+    }
+
+    #[test]
+    fn level_sizes_dont_overflow() {
+        let size = Extent3d {
+            width: u32::MAX,
+            height: u32::MAX,
+            depth_or_array_layers: 1,
+        };
+        assert_eq!(level_size(TextureFormat::Rgba32Float, size), None);
+        let size = Extent3d {
+            width: 65536,
+            height: 65536,
+            depth_or_array_layers: 1,
+        };
+        assert_eq!(level_size(TextureFormat::Rgba8Unorm, size), Some(1 << 34));
+        assert_eq!(level_size(TextureFormat::Bc1RgbaUnorm, size), Some(1 << 31));
+    }
+}
@@ -9,8 +9,9 @@ use wgpu_core::{
 
 use crate::gpu::{
     GpuAddressMode, GpuBindGroupEntry, GpuBlendComponent, GpuBlendFactor, GpuBlendOperation,
-    GpuBlendState, GpuBufferUsage, GpuColorWrite, GpuCompareFunction, GpuExtentD3, GpuFilterMode,
-    GpuIndexFormat, GpuLoadOp, GpuMapMode, GpuQueryType, GpuShaderStage, GpuStencilFaceState,
+    GpuBlendState, GpuBufferUsage, GpuColorWrite, GpuCompareFunction, GpuCompositeAlphaMode,
+    GpuExtentD3, GpuFilterMode, GpuGlslStage, GpuIndexFormat, GpuLoadOp, GpuMapMode,
+    GpuPresentMode, GpuQueryType, GpuShaderStage, GpuStencilFaceState,
     GpuStencilOperation, GpuStoreOp, GpuTextureAspect, GpuTextureDimension, GpuTextureFormat,
     GpuTextureUsage, GpuVertexFormat, GpuVertexStepMode,
 };
@@ -98,6 +99,16 @@ impl Into<wgpu_types::ShaderStages> for GpuShaderStage {
     }
 }
 
+impl Into<naga::ShaderStage> for GpuGlslStage {
+    fn into(self) -> naga::ShaderStage {
+        match self {
+            GpuGlslStage::Vertex => naga::ShaderStage::Vertex,
+            GpuGlslStage::Fragment => naga::ShaderStage::Fragment,
+            GpuGlslStage::Compute => naga::ShaderStage::Compute,
+        }
+    }
+}
+
 impl Into<wgpu_types::TextureAspect> for GpuTextureAspect {
     fn into(self) -> wgpu_types::TextureAspect {
         match self {
@@ -855,6 +866,56 @@ impl Into<GpuQueryType> for wgpu_types::QueryType {
     }
 }
 
+impl Into<wgpu_types::PresentMode> for GpuPresentMode {
+    fn into(self) -> wgpu_types::PresentMode {
+        match self {
+            GpuPresentMode::AutoVsync => wgpu_types::PresentMode::AutoVsync,
+            GpuPresentMode::AutoNoVsync => wgpu_types::PresentMode::AutoNoVsync,
+            GpuPresentMode::Fifo => wgpu_types::PresentMode::Fifo,
+            GpuPresentMode::FifoRelaxed => wgpu_types::PresentMode::FifoRelaxed,
+            GpuPresentMode::Immediate => wgpu_types::PresentMode::Immediate,
+            GpuPresentMode::Mailbox => wgpu_types::PresentMode::Mailbox,
+        }
+    }
+}
+
+impl Into<GpuPresentMode> for wgpu_types::PresentMode {
+    fn into(self) -> GpuPresentMode {
+        match self {
+            wgpu_types::PresentMode::AutoVsync => GpuPresentMode::AutoVsync,
+            wgpu_types::PresentMode::AutoNoVsync => GpuPresentMode::AutoNoVsync,
+            wgpu_types::PresentMode::Fifo => GpuPresentMode::Fifo,
+            wgpu_types::PresentMode::FifoRelaxed => GpuPresentMode::FifoRelaxed,
+            wgpu_types::PresentMode::Immediate => GpuPresentMode::Immediate,
+            wgpu_types::PresentMode::Mailbox => GpuPresentMode::Mailbox,
+        }
+    }
+}
+
+impl Into<wgpu_types::CompositeAlphaMode> for GpuCompositeAlphaMode {
+    fn into(self) -> wgpu_types::CompositeAlphaMode {
+        match self {
+            GpuCompositeAlphaMode::Auto => wgpu_types::CompositeAlphaMode::Auto,
+            GpuCompositeAlphaMode::Opaque => wgpu_types::CompositeAlphaMode::Opaque,
+            GpuCompositeAlphaMode::PreMultiplied => wgpu_types::CompositeAlphaMode::PreMultiplied,
+            GpuCompositeAlphaMode::PostMultiplied => wgpu_types::CompositeAlphaMode::PostMultiplied,
+            GpuCompositeAlphaMode::Inherit => wgpu_types::CompositeAlphaMode::Inherit,
+        }
+    }
+}
+
+impl Into<GpuCompositeAlphaMode> for wgpu_types::CompositeAlphaMode {
+    fn into(self) -> GpuCompositeAlphaMode {
+        match self {
+            wgpu_types::CompositeAlphaMode::Auto => GpuCompositeAlphaMode::Auto,
+            wgpu_types::CompositeAlphaMode::Opaque => GpuCompositeAlphaMode::Opaque,
+            wgpu_types::CompositeAlphaMode::PreMultiplied => GpuCompositeAlphaMode::PreMultiplied,
+            wgpu_types::CompositeAlphaMode::PostMultiplied => GpuCompositeAlphaMode::PostMultiplied,
+            wgpu_types::CompositeAlphaMode::Inherit => GpuCompositeAlphaMode::Inherit,
+        }
+    }
+}
+
 impl Into<HostMap> for GpuMapMode {
     fn into(self) -> HostMap {
         if self.contains(GpuMapMode::READ) {
@@ -4,7 +4,6 @@ pub struct RuneRuntimeState {
     pub input_path: PathBuf,
     pub window_size: PhysicalSize<u32>,
     pub gpu_state: GpuState,
-    pub audio_state: AudioState,
     pub gamepad_state: GamepadState,
     pub keyboard_state: KeyboardState,
     pub paths: Slab<VfsPath>,
@@ -44,7 +43,6 @@ impl RuneRuntimeState {
             generation: 0,
             input_path,
             window_size,
-            audio_state: AudioState::new(audio_device),
             gpu_state: GpuState::new(),
             gamepad_state: GamepadState::new(),
             keyboard_state: KeyboardState::new(),
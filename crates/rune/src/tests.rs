@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use anyhow::{Ok, Result};
 use gilrs::Gilrs;
@@ -9,7 +9,10 @@ use wasmtime::{
 };
 use winit::window::Window;
 
-use crate::{RuneRuntimeState, Runtime, RuntimePre};
+use crate::{
+    runtime::{AudioDevices, Gpu},
+    RuneRuntimeState, Runtime, RuntimePre,
+};
 
 /// Test is used to run wasm component
 
@@ -56,7 +59,6 @@ impl Tests {
         &mut self,
         window: &Window,
         input_path: PathBuf,
-        audio_device: cpal::Device,
         instance: wgpu_core::global::Global,
         surface: wgpu_core::id::SurfaceId,
         adapter: wgpu_core::id::AdapterId,
@@ -70,13 +72,14 @@ impl Tests {
             Uuid::new_v4(),
             input_path,
             window_size,
-            audio_device,
-            instance,
+            AudioDevices::watch(),
+            Gpu::Wgpu(Arc::new(instance)),
             surface,
             adapter,
             device,
             queue,
-            gilrs,
+            Some(gilrs),
+            None,
         );
 
         let mut store = Store::new(&self.engine, runtime_state);
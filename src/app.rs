@@ -62,8 +62,11 @@ impl App {
                 let binary = std::fs::read(input_path.join("test-game.wasm")).unwrap();
                 rune::runtime::test(input_path.to_path_buf(), binary).await;
             }
-            Some(CliCommand::Run { release }) => {
-                crate::commands::run::run(release).await?;
+            Some(CliCommand::Run { release, screenshot_at_frame, gpu_backend, profile, gpu_trace, record_audio, frames }) => {
+                crate::commands::run::run(release, screenshot_at_frame, gpu_backend, profile, gpu_trace, record_audio, frames).await?;
+            }
+            Some(CliCommand::GpuReplay { trace_dir, frames, output }) => {
+                crate::commands::gpu_replay::gpu_replay(trace_dir, frames, output).await?;
             }
             Some(CliCommand::Build { release }) => {
                 crate::commands::build::build(release).await?;
@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
+use rune::runtime::gpu::GpuBackend;
 
 use crate::utils::version;
 
@@ -26,6 +27,36 @@ pub enum CliCommand {
     Run {
         #[clap(long, default_value_t = false)]
         release: bool,
+        /// Save a PNG screenshot of the given render frame to ./screenshots
+        #[clap(long, value_name = "FRAME")]
+        screenshot_at_frame: Option<u64>,
+        /// One of: auto, vulkan, metal, dx12, gl, null. Overrides RUNE_GPU_BACKEND and rune.toml
+        #[clap(long, value_name = "BACKEND")]
+        gpu_backend: Option<GpuBackend>,
+        /// Time each GPU pass and print a report on exit. Passes are named by their first debug group
+        #[clap(long, default_value_t = false)]
+        profile: bool,
+        /// Record a wgpu API trace into the given directory for `rune gpu-replay`. Overrides RUNE_GPU_TRACE
+        #[clap(long, value_name = "DIR")]
+        gpu_trace: Option<PathBuf>,
+        /// Record the game's mixed audio output to the given WAV file
+        #[clap(long, value_name = "FILE")]
+        record_audio: Option<PathBuf>,
+        /// Exit after rendering this many frames. With the null backend, exits with an error if the game caused any uncaptured GPU errors
+        #[clap(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<u64>::new().range(1..))]
+        frames: Option<u64>,
+    },
+    /// Replay a GPU trace recorded with `rune run --gpu-trace` offscreen, saving its final frames
+    GpuReplay {
+        /// The directory the trace was recorded into
+        #[clap(value_name = "DIR")]
+        trace_dir: PathBuf,
+        /// How many of the last presented frames to save
+        #[clap(long, default_value_t = 1, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        frames: usize,
+        /// Where to save the frames. Defaults to a frames directory inside the trace
+        #[clap(long, value_name = "DIR")]
+        output: Option<PathBuf>,
     },
     /// Build the project
     Build {
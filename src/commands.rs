@@ -1,6 +1,7 @@
 pub mod build;
 pub mod bundle;
 pub mod docs;
+pub mod gpu_replay;
 pub mod new;
 pub mod run;
 pub mod upgrade;
@@ -2,6 +2,7 @@ use color_eyre::eyre;
 use rust_embed::Embed;
 use subprocess::{Exec, ExitStatus, Redirection};
 use wasmparser::{Encoding, Payload};
+use std::collections::HashMap;
 use std::env;
 use std::path::{Path, PathBuf};
 
@@ -15,6 +16,8 @@ use crate::cli::NewSubcommand;
 
 use crate::Result;
 
+mod shaders;
+
 #[derive(Embed)]
 #[folder = "wasi"]
 struct WasiWasm;
@@ -61,6 +64,19 @@ pub async fn build(release: &bool) -> Result<()> {
 
     crate::fs::copy_dir_all(input_path, output_path)?;
 
+    // Shaders are checked here so a broken one fails the build instead of the game.
+    let shader_defines = match build.get("shader-defines").and_then(|defines| defines.as_table()) {
+        Some(defines) => defines
+            .iter()
+            .map(|(name, value)| match value.as_str() {
+                Some(value) => (name.clone(), value.to_owned()),
+                None => (name.clone(), value.to_string()),
+            })
+            .collect(),
+        None => HashMap::new(),
+    };
+    shaders::build_shaders(input_path, output_path, &shader_defines)?;
+
     let output_entrypoint_path = current_dir.join(&output_path).join(&entrypoint);
 
     // TODO: Concatenate rune dependencies read from config to wasm binary
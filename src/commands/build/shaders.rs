@@ -0,0 +1,418 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre, Section};
+use naga::{
+    front::wgsl,
+    valid::{ValidationFlags, Validator},
+    Span,
+};
+use rune::runtime::SHADER_CAPABILITIES;
+
+use crate::Result;
+
+/// Preprocesses every `.wgsl` file under `input_path` into the same place under `output_path`,
+/// and validates the result with naga. Every error is reported when the build fails, so one
+/// run shows them all.
+///
+/// The directives are the ones naga_oil uses:
+/// - `#define_import_path a::b` names a file so others can `#import a::b`. Such files are
+///   libraries, and are only validated as part of the shaders importing them.
+/// - `#import a::b` or `#import "relative/path.wgsl"` pastes in a file, once per shader.
+/// - `#define NAME value`, `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`.
+/// - `#{NAME}` is replaced with the value of a define.
+///
+/// `defines` come from `[build] shader-defines` in rune.toml, and apply to every shader.
+pub fn build_shaders(input_path: &Path, output_path: &Path, defines: &HashMap<String, String>) -> Result<()> {
+    let mut shader_paths = Vec::new();
+    find_shaders(input_path, &mut shader_paths)?;
+    if shader_paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut import_paths = HashMap::new();
+    let mut libraries = HashSet::new();
+    for path in &shader_paths {
+        let code = fs::read_to_string(path)?;
+        for line in code.lines() {
+            if let Some(import_path) = line.trim().strip_prefix("#define_import_path") {
+                import_paths.insert(import_path.trim().to_owned(), path.clone());
+                libraries.insert(path.clone());
+            }
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    for path in shader_paths.iter().filter(|path| !libraries.contains(*path)) {
+        let source = match preprocess(path, &import_paths, defines) {
+            Ok(source) => source,
+            Err(diagnostic) => {
+                diagnostics.push(diagnostic);
+                continue;
+            }
+        };
+
+        let shader_output_path = output_path.join(path.strip_prefix(input_path).unwrap());
+        crate::fs::create_file(&shader_output_path)?;
+        fs::write(&shader_output_path, source)?;
+    }
+
+    let report = match diagnostics.len() {
+        0 => return Ok(()),
+        1 => eyre::eyre!("A shader failed to compile"),
+        failed => eyre::eyre!("{} shaders failed to compile", failed),
+    };
+    Err(diagnostics
+        .into_iter()
+        .fold(report, |report, diagnostic| report.section(diagnostic.to_string())))
+}
+
+/// Preprocesses the shader at `path` and validates it, returning the source to ship.
+fn preprocess(
+    path: &Path,
+    import_paths: &HashMap<String, PathBuf>,
+    defines: &HashMap<String, String>,
+) -> Result<String, Diagnostic> {
+    let mut preprocessor = Preprocessor {
+        import_paths,
+        defines: defines.clone(),
+        included: HashSet::new(),
+        importing: Vec::new(),
+        files: Vec::new(),
+        source: String::new(),
+        origins: Vec::new(),
+    };
+    preprocessor.include(path, None)?;
+    preprocessor.validate()?;
+    Ok(preprocessor.source)
+}
+
+fn find_shaders(dir: &Path, shader_paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_shaders(&path, shader_paths)?;
+        } else if path.extension().is_some_and(|extension| extension == "wgsl") {
+            shader_paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// An error at a line of a shader source file.
+#[derive(Debug)]
+struct Diagnostic {
+    path: PathBuf,
+    line: usize,
+    column: Option<usize>,
+    message: String,
+    notes: Vec<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        write!(f, ": error: {}", self.message)?;
+        for note in &self.notes {
+            write!(f, "\n    {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pastes a shader and its imports into one source, remembering where each line came from.
+struct Preprocessor<'a> {
+    import_paths: &'a HashMap<String, PathBuf>,
+    defines: HashMap<String, String>,
+    included: HashSet<PathBuf>,
+    /// The files being included, from the shader to the innermost import, to catch cycles.
+    importing: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+    source: String,
+    /// The file, as an index into `files`, and line each line of `source` came from.
+    origins: Vec<(usize, usize)>,
+}
+
+impl Preprocessor<'_> {
+    fn include(&mut self, path: &Path, imported_at: Option<(usize, usize)>) -> Result<(), Diagnostic> {
+        let cycle_start = self.importing.iter().position(|importing| importing == path);
+        if let (Some(start), Some((file, line))) = (cycle_start, imported_at) {
+            let cycle = self.importing[start..]
+                .iter()
+                .chain([&path.to_path_buf()])
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>();
+            return Err(self.diagnostic(file, line, format!("import cycle: {}", cycle.join(" -> "))));
+        }
+        if !self.included.insert(path.to_path_buf()) {
+            return Ok(());
+        }
+        self.importing.push(path.to_path_buf());
+        self.include_lines(path, imported_at)?;
+        self.importing.pop();
+        Ok(())
+    }
+
+    fn include_lines(&mut self, path: &Path, imported_at: Option<(usize, usize)>) -> Result<(), Diagnostic> {
+        let code = fs::read_to_string(path).map_err(|err| match imported_at {
+            Some((file, line)) => self.diagnostic(file, line, format!("can't read {}: {}", path.display(), err)),
+            None => Diagnostic {
+                path: path.to_path_buf(),
+                line: 1,
+                column: None,
+                message: format!("can't read the shader: {}", err),
+                notes: vec![],
+            },
+        })?;
+        let file = self.files.len();
+        self.files.push(path.to_path_buf());
+
+        // Each open #ifdef, whether its current branch is kept and whether it has seen #else.
+        let mut conditions: Vec<(bool, bool, usize)> = Vec::new();
+        for (index, text) in code.lines().enumerate() {
+            let line = index + 1;
+            let active = conditions.iter().all(|(keep, _, _)| *keep);
+
+            let directive = text
+                .trim_start()
+                .strip_prefix('#')
+                .filter(|rest| rest.starts_with(|c: char| c.is_ascii_alphabetic()));
+            let Some(directive) = directive else {
+                if active {
+                    let text = self.substitute(text).map_err(|message| self.diagnostic(file, line, message))?;
+                    self.source.push_str(&text);
+                    self.source.push('\n');
+                    self.origins.push((file, line));
+                }
+                continue;
+            };
+
+            let (name, argument) = directive
+                .split_once(char::is_whitespace)
+                .map(|(name, argument)| (name, argument.trim()))
+                .unwrap_or((directive.trim_end(), ""));
+            match name {
+                "ifdef" | "ifndef" => {
+                    let defined = self.defines.contains_key(argument);
+                    conditions.push((defined == (name == "ifdef"), false, line));
+                }
+                "else" => match conditions.last_mut() {
+                    Some((keep, seen_else, _)) if !*seen_else => {
+                        *keep = !*keep;
+                        *seen_else = true;
+                    }
+                    Some(_) => return Err(self.diagnostic(file, line, "#else after #else".to_owned())),
+                    None => return Err(self.diagnostic(file, line, "#else without #ifdef".to_owned())),
+                },
+                "endif" => {
+                    if conditions.pop().is_none() {
+                        return Err(self.diagnostic(file, line, "#endif without #ifdef".to_owned()));
+                    }
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) = argument.split_once(char::is_whitespace).unwrap_or((argument, ""));
+                    if define.is_empty() {
+                        return Err(self.diagnostic(file, line, "#define needs a name".to_owned()));
+                    }
+                    self.defines.insert(define.to_owned(), value.trim().to_owned());
+                }
+                "define_import_path" => {}
+                "import" => {
+                    let import_path = match argument.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+                        Some(relative_path) => path.parent().unwrap().join(relative_path),
+                        None => match self.import_paths.get(argument) {
+                            Some(import_path) => import_path.clone(),
+                            None => {
+                                return Err(self.diagnostic(
+                                    file,
+                                    line,
+                                    format!("no shader has #define_import_path {}", argument),
+                                ))
+                            }
+                        },
+                    };
+                    self.include(&import_path, Some((file, line)))?;
+                }
+                _ => return Err(self.diagnostic(file, line, format!("unknown directive #{}", name))),
+            }
+        }
+
+        match conditions.last() {
+            Some((_, _, line)) => Err(self.diagnostic(file, *line, "#ifdef without #endif".to_owned())),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces each `#{NAME}` in a line with the value of the define.
+    fn substitute(&self, text: &str) -> Result<String, String> {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("#{") {
+            result.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| "unclosed #{".to_owned())?;
+            let name = &rest[start + 2..start + end];
+            let value = self
+                .defines
+                .get(name)
+                .ok_or_else(|| format!("#{{{}}} isn't defined", name))?;
+            result.push_str(value);
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Parses and validates the preprocessed source, pointing errors at the original files.
+    fn validate(&self) -> Result<(), Diagnostic> {
+        let module = wgsl::parse_str(&self.source).map_err(|err| {
+            let labels = err.labels().map(|(span, label)| (span, label.to_owned())).collect::<Vec<_>>();
+            self.span_diagnostic(err.message().to_owned(), &labels)
+        })?;
+
+        // The runtime validates with the same capabilities, so what passes here also loads there.
+        Validator::new(ValidationFlags::all(), SHADER_CAPABILITIES)
+            .validate(&module)
+            .map_err(|err| {
+                let mut message = err.as_inner().to_string();
+                let mut source = err.as_inner().source();
+                while let Some(inner) = source {
+                    message.push_str(&format!(": {}", inner));
+                    source = inner.source();
+                }
+                self.span_diagnostic(message, &err.spans().cloned().collect::<Vec<_>>())
+            })?;
+
+        Ok(())
+    }
+
+    /// Reports an error at its first labelled span, with the other labels as notes.
+    fn span_diagnostic(&self, message: String, labels: &[(Span, String)]) -> Diagnostic {
+        let locations = labels
+            .iter()
+            .filter(|(span, _)| span.is_defined())
+            .map(|(span, label)| {
+                let location = span.location(&self.source);
+                let (file, line) = self.origins[(location.line_number as usize - 1).min(self.origins.len() - 1)];
+                (file, line, location.line_position as usize, label)
+            })
+            .collect::<Vec<_>>();
+
+        let Some(&(file, line, column, label)) = locations.first() else {
+            // Errors without a span are about the shader as a whole.
+            let (file, line) = self.origins.first().copied().unwrap_or((0, 1));
+            return self.diagnostic(file, line, message);
+        };
+        let mut diagnostic = self.diagnostic(file, line, message);
+        diagnostic.column = Some(column);
+        if !label.is_empty() {
+            diagnostic.notes.push(label.clone());
+        }
+        for &(file, line, column, label) in &locations[1..] {
+            diagnostic
+                .notes
+                .push(format!("{}:{}:{}: {}", self.files[file].display(), line, column, label));
+        }
+        diagnostic
+    }
+
+    fn diagnostic(&self, file: usize, line: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            path: self.files.get(file).cloned().unwrap_or_default(),
+            line,
+            column: None,
+            message,
+            notes: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Writes `files` to a fresh directory named after the test.
+    fn shader_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rune-shaders-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, code) in files {
+            fs::write(dir.join(file), code).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_preprocess_nested_imports() {
+        let dir = shader_dir(
+            "nested",
+            &[
+                (
+                    "main.wgsl",
+                    "#import shapes::circle\n#import \"color.wgsl\"\n@fragment\nfn main() -> @location(0) vec4<f32> {\n    return color(circle(1.0));\n}\n",
+                ),
+                (
+                    "circle.wgsl",
+                    "#define_import_path shapes::circle\n#import \"color.wgsl\"\nfn circle(radius: f32) -> f32 {\n    return radius * 3.14159;\n}\n",
+                ),
+                ("color.wgsl", "fn color(value: f32) -> vec4<f32> {\n    return vec4<f32>(value);\n}\n"),
+            ],
+        );
+        let import_paths = HashMap::from([("shapes::circle".to_owned(), dir.join("circle.wgsl"))]);
+
+        let source = preprocess(&dir.join("main.wgsl"), &import_paths, &HashMap::new()).unwrap();
+
+        // Imported once, though both shaders import it.
+        assert_eq!(source.matches("fn color").count(), 1);
+        assert!(source.find("fn color") < source.find("fn circle"));
+        assert!(source.find("fn circle") < source.find("fn main"));
+    }
+
+    #[test]
+    fn test_preprocess_unknown_import() {
+        let dir = shader_dir("unknown", &[("main.wgsl", "// Shapes\n#import shapes::square\n")]);
+
+        let diagnostic = preprocess(&dir.join("main.wgsl"), &HashMap::new(), &HashMap::new()).unwrap_err();
+
+        assert_eq!(diagnostic.path, dir.join("main.wgsl"));
+        assert_eq!(diagnostic.line, 2);
+        assert_eq!(diagnostic.message, "no shader has #define_import_path shapes::square");
+    }
+
+    #[test]
+    fn test_preprocess_import_cycle() {
+        let dir = shader_dir(
+            "cycle",
+            &[
+                ("main.wgsl", "#import \"a.wgsl\"\n"),
+                ("a.wgsl", "#import \"b.wgsl\"\n"),
+                ("b.wgsl", "\n#import \"a.wgsl\"\n"),
+            ],
+        );
+
+        let diagnostic = preprocess(&dir.join("main.wgsl"), &HashMap::new(), &HashMap::new()).unwrap_err();
+
+        assert_eq!(diagnostic.path, dir.join("b.wgsl"));
+        assert_eq!(diagnostic.line, 2);
+        assert_eq!(
+            diagnostic.message,
+            format!(
+                "import cycle: {} -> {} -> {}",
+                dir.join("a.wgsl").display(),
+                dir.join("b.wgsl").display(),
+                dir.join("a.wgsl").display()
+            )
+        );
+    }
+}
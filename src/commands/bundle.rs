@@ -13,6 +13,7 @@ use std::{
 use std::os::unix::fs::OpenOptionsExt;
 
 use current_platform::CURRENT_PLATFORM;
+use rune::runtime::gpu::GpuBackend;
 use semver::Version;
 use toml::Table;
 
@@ -41,6 +42,9 @@ pub async fn bundle(target: &String, release: &bool) -> Result<()> {
     }
     .to_owned();
 
+    // The backend has to be one the runtime is built with on the platform being bundled for.
+    let runtime_gpu_backend = super::run::config_gpu_backend(&config, &target)?;
+
     let settings = Settings {
         current_dir: current_dir.clone(),
         rune_dir,
@@ -52,6 +56,9 @@ pub async fn bundle(target: &String, release: &bool) -> Result<()> {
         target,
         target_triplet,
         runtime_version: Version::parse(config["runtime"]["version"].as_str().unwrap()).unwrap(),
+        runtime_gpu_backend,
+        permissions_microphone: super::run::config_microphone(&config)?,
+        cloud: super::run::config_cloud(&config)?,
         build_input_dir: current_dir
             .clone()
             .join(config["build"]["input"].as_str().unwrap()),
@@ -133,6 +140,18 @@ async fn init_rust_project(settings: &Settings) -> Result<()> {
     fs::create_dir_all(&project_dir.join("src"))?;
 
     let entrypoint_path_str = settings.build_entrypoint.to_str().unwrap();
+    // RUNE_GPU_BACKEND still takes precedence over the backend configured in rune.toml.
+    let gpu_backend = settings.runtime_gpu_backend.unwrap_or(GpuBackend::Auto);
+    let identifier = &settings.bundle_identifier;
+    let microphone = settings.permissions_microphone.is_some();
+    let cloud = match &settings.cloud {
+        Some(cloud) => format!(
+            "Some(runtime::storage::CloudConfig {{ url: {:?}.to_owned(), conflicts: runtime::storage::CloudConflicts::{:?} }})",
+            cloud.url,
+            cloud.conflicts
+        ),
+        None => "None".to_owned(),
+    };
     let main_path = src_dir.join("main.rs");
     let mut main = File::create(&main_path)?;
     main.write_all(format!(r#"
@@ -143,7 +162,14 @@ async fn init_rust_project(settings: &Settings) -> Result<()> {
     fn main() {{
         let input_path = env::current_exe().unwrap().parent().unwrap().join(".rune/input/");
         let binary = fs::read(input_path.join("{entrypoint_path_str}")).expect("Failed to read the WASM file");
-        runtime::run(input_path, binary);
+        let options = runtime::RunOptions {{
+            gpu_backend: runtime::gpu::GpuBackend::from_env().unwrap_or(runtime::gpu::GpuBackend::{gpu_backend:?}),
+            identifier: Some({identifier:?}.to_owned()),
+            microphone: {microphone},
+            cloud: {cloud},
+            ..Default::default()
+        }};
+        runtime::run_with_options(input_path, binary, options);
     }}
     "#).as_bytes())?;
 
@@ -152,6 +152,13 @@ fn create_info_plist(
     // }
     write!(file, "  <key>LSRequiresCarbon</key>\n  <true/>\n")?;
     write!(file, "  <key>NSHighResolutionCapable</key>\n  <true/>\n")?;
+    if let Some(microphone) = &settings.permissions_microphone {
+        write!(
+            file,
+            "  <key>NSMicrophoneUsageDescription</key>\n  <string>{}</string>\n",
+            escape_xml(microphone)
+        )?;
+    }
     // if let Some(copyright) = settings.copyright_string() {
     //     write!(
     //         file,
@@ -164,6 +171,22 @@ fn create_info_plist(
     Ok(())
 }
 
+/// Escapes text for an XML element, since the plist is written by hand.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 fn copy_framework_from(dest_dir: &Path, framework: &str, src_dir: &Path) -> crate::Result<bool> {
     let src_name = format!("{framework}.framework");
     let src_path = src_dir.join(&src_name);
@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre;
+
+use crate::Result;
+
+pub async fn gpu_replay(trace_dir: &PathBuf, frames: &usize, output: &Option<PathBuf>) -> Result<()> {
+    let output_dir = output.clone().unwrap_or_else(|| trace_dir.join("frames"));
+
+    let saved = rune::runtime::replay_gpu_trace(trace_dir, &output_dir, *frames)
+        .map_err(|err| eyre::eyre!("{:#}", err))?;
+    if saved == 0 {
+        return Err(eyre::eyre!("The trace didn't present any frames that could be saved"));
+    }
+
+    Ok(())
+}
@@ -1,5 +1,9 @@
 use std::env;
+use std::path::PathBuf;
 
+use color_eyre::eyre;
+use rune::runtime::gpu::GpuBackend;
+use rune::runtime::storage::CloudConfig;
 use toml::Table;
 
 use crate::cli::NewSubcommand;
@@ -7,7 +11,15 @@ use crate::cli::NewSubcommand;
 use crate::Result;
 
 
-pub async fn run(release: &bool) -> Result<()> {
+pub async fn run(
+    release: &bool,
+    screenshot_at_frame: &Option<u64>,
+    gpu_backend: &Option<GpuBackend>,
+    profile: &bool,
+    gpu_trace: &Option<PathBuf>,
+    record_audio: &Option<PathBuf>,
+    frames: &Option<u64>,
+) -> Result<()> {
     crate::commands::build::build(release).await?;
 
     let current_dir = env::current_dir()?;
@@ -21,15 +33,100 @@ pub async fn run(release: &bool) -> Result<()> {
         None => panic!("No build input provided in config!"),
     };
 
+    // The command line wins over the environment, which wins over rune.toml.
+    let gpu_backend = match gpu_backend.or_else(GpuBackend::from_env) {
+        Some(gpu_backend) => gpu_backend,
+        None => config_gpu_backend(&config, env::consts::OS)?.unwrap_or(GpuBackend::Auto),
+    };
+
     match config["build"]["output"].as_str() {
         Some(output_path) => {
             let output_path = current_dir.join(output_path);
             let entrypoint_path = output_path.join(entrypoint_path);
             let binary = std::fs::read(entrypoint_path).unwrap();
-            rune::runtime::run(output_path.to_path_buf(), binary);
+            let options = rune::runtime::RunOptions {
+                dev: true,
+                screenshot_at_frame: *screenshot_at_frame,
+                screenshot_dir: current_dir.join("screenshots"),
+                gpu_backend,
+                profile: *profile,
+                identifier: config["package"]["identifier"].as_str().map(str::to_owned),
+                // Falls back to RUNE_GPU_TRACE like any other run.
+                gpu_trace: gpu_trace
+                    .clone()
+                    .or_else(|| rune::runtime::RunOptions::default().gpu_trace),
+                record_audio: record_audio.as_ref().map(|path| current_dir.join(path)),
+                microphone: config_microphone(&config)?.is_some(),
+                cloud: config_cloud(&config)?,
+                frames: *frames,
+            };
+            rune::runtime::run_with_options(output_path.to_path_buf(), binary, options);
         }
         None => panic!("No build input provided in config!"),
     }
 
     Ok(())
 }
+
+/// Reads `gpu-backend` from the `[runtime]` table of rune.toml, for a game that runs on `os`.
+pub fn config_gpu_backend(config: &Table, os: &str) -> Result<Option<GpuBackend>> {
+    let gpu_backend = match config.get("runtime").and_then(|runtime| runtime.get("gpu-backend")) {
+        Some(gpu_backend) => gpu_backend,
+        None => return Ok(None),
+    };
+    let gpu_backend = match gpu_backend.as_str() {
+        Some(gpu_backend) => gpu_backend,
+        None => {
+            return Err(eyre::eyre!(
+                "Invalid runtime.gpu-backend in rune.toml: expected a string"
+            ))
+        }
+    };
+    match GpuBackend::parse_for(gpu_backend, os) {
+        Ok(gpu_backend) => Ok(Some(gpu_backend)),
+        Err(err) => Err(eyre::eyre!("Invalid runtime.gpu-backend in rune.toml: {}", err)),
+    }
+}
+
+/// Reads `microphone` from the `[permissions]` table of rune.toml, which is the reason the game
+/// gives players for listening, or `true` for a generic one.
+pub fn config_microphone(config: &Table) -> Result<Option<String>> {
+    let microphone = match config.get("permissions").and_then(|permissions| permissions.get("microphone")) {
+        Some(microphone) => microphone,
+        None => return Ok(None),
+    };
+    match microphone {
+        toml::Value::String(reason) => Ok(Some(reason.clone())),
+        toml::Value::Boolean(true) => Ok(Some("This game uses the microphone.".to_owned())),
+        toml::Value::Boolean(false) => Ok(None),
+        _ => Err(eyre::eyre!(
+            "Invalid permissions.microphone in rune.toml: expected a string or a boolean"
+        )),
+    }
+}
+
+/// Reads the `[cloud]` table of rune.toml, which points the cloud storage device at a WebDAV
+/// server such as `rune-cloud-server`.
+pub fn config_cloud(config: &Table) -> Result<Option<CloudConfig>> {
+    let cloud = match config.get("cloud") {
+        Some(cloud) => cloud,
+        None => return Ok(None),
+    };
+    let url = match cloud.get("url").and_then(|url| url.as_str()) {
+        Some(url) => url.to_owned(),
+        None => return Err(eyre::eyre!("Invalid cloud in rune.toml: expected a url")),
+    };
+    // Anything in rune.toml ships with the game, so players' tokens come from the game instead.
+    if cloud.get("token").is_some() {
+        return Err(eyre::eyre!(
+            "Invalid cloud.token in rune.toml: tokens would ship with the game, so the game passes its player's token to cloud storage instead"
+        ));
+    }
+    let conflicts = match cloud.get("conflicts").and_then(|conflicts| conflicts.as_str()) {
+        Some(conflicts) => conflicts
+            .parse()
+            .map_err(|err| eyre::eyre!("Invalid cloud.conflicts in rune.toml: {}", err))?,
+        None => Default::default(),
+    };
+    Ok(Some(CloudConfig { url, conflicts }))
+}
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use rune::runtime::gpu::GpuBackend;
+use rune::runtime::storage::CloudConfig;
 use semver::Version;
 
 pub struct Settings {
@@ -16,6 +18,11 @@ pub struct Settings {
     pub target_triplet: String,
 
     pub runtime_version: Version,
+    pub runtime_gpu_backend: Option<GpuBackend>,
+
+    pub permissions_microphone: Option<String>,
+
+    pub cloud: Option<CloudConfig>,
 
     pub build_input_dir: PathBuf,
     pub build_output_dir: PathBuf,
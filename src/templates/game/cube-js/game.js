@@ -77,7 +77,9 @@ export const guest = {
     })
 
     let vertexShader = device.createShaderModule({
-      code: `
+      code: {
+        tag: 'wgsl',
+        val: `
         struct Uniforms {
           modelViewProjectionMatrix : mat4x4<f32>,
         }
@@ -100,12 +102,15 @@ export const guest = {
           output.fragPosition = 0.5 * (position + vec4(1.0, 1.0, 1.0, 1.0));
           return output;
         }
-      `,
+      `
+      },
       hints: []
     })
 
     let fragShader = device.createShaderModule({
-      code: `
+      code: {
+        tag: 'wgsl',
+        val: `
         @fragment
         fn main(
           @location(0) fragUV: vec2<f32>,
@@ -113,7 +118,8 @@ export const guest = {
         ) -> @location(0) vec4<f32> {
           return fragPosition;
         }
-      `,
+      `
+      },
       hints: []
     })
 
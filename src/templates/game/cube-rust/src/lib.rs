@@ -80,6 +80,7 @@ impl Guest for Game {
 
         DEPTH_TEXTURE
             .set(device.create_texture(&GpuTextureDescriptor {
+                label: Some("depth".to_owned()),
                 size: GpuExtentD3 {
                     height: window_height,
                     width: window_width,
@@ -105,7 +106,8 @@ impl Guest for Game {
 
         let vertex_shader = device.create_shader_module(&GpuShaderModuleDescriptor {
             label: None,
-            code: "
+            code: GpuShaderSource::Wgsl(
+                "
               struct Uniforms {
                 modelViewProjectionMatrix : mat4x4<f32>,
               }
@@ -129,13 +131,15 @@ impl Guest for Game {
                 return output;
               }
             "
-            .to_string(),
+                .to_string(),
+            ),
             hints: vec![],
         });
 
         let frag_shader = device.create_shader_module(&GpuShaderModuleDescriptor {
             label: None,
-            code: "
+            code: GpuShaderSource::Wgsl(
+                "
               @fragment
               fn main(
                 @location(0) fragUV: vec2<f32>,
@@ -144,11 +148,13 @@ impl Guest for Game {
                 return fragPosition;
               }            
             "
-            .to_string(),
+                .to_string(),
+            ),
             hints: vec![],
         });
 
         let pipeline = device.create_render_pipeline(&GpuRenderPipelineDescriptor {
+            label: Some("cube".to_owned()),
             layout: GpuLayout::Auto,
             vertex: GpuVertexState {
                 module: &vertex_shader,
@@ -244,9 +250,11 @@ impl Guest for Game {
         let adapter = crate::rune::runtime::gpu::request_adapter();
         let device = adapter.request_device();
         let queue = device.queue();
-        let view = crate::rune::runtime::gpu::surface()
-            .current_texture()
-            .create_view();
+        // The host configures the surface again when it can, so a missed frame is skipped.
+        let Ok(surface_texture) = crate::rune::runtime::gpu::surface().current_texture() else {
+            return;
+        };
+        let view = surface_texture.create_view();
 
         let mut encoder =
             device.create_command_encoder(&GpuCommandEncoderDescriptor { label: None });
@@ -276,6 +284,7 @@ impl Guest for Game {
         );
 
         let mut render_pass = encoder.begin_render_pass(&GpuRenderPassDescriptor {
+            label: None,
             color_attachments: vec![GpuRenderPassColorAttachment {
                 view: &view,
                 resolve_target: None,